@@ -0,0 +1,325 @@
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Access control of the node, read from the `access` table of the configuration.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AccessConfig {
+    /// Token required by `/mine`, `/block` and the peer-management routes,
+    /// shared by every node of the network. They are disabled when it is not
+    /// set.
+    pub admin_token: Option<String>,
+    /// Maximum size of a JSON body, in bytes.
+    pub max_body_size: u64,
+    /// Length of the rate limiting window, in seconds.
+    pub window: u64,
+    /// Requests a client may send to a route without its own budget in a window.
+    pub default_budget: u32,
+    /// Requests a client may send to each route (e.g. `/mine`) in a window.
+    pub budgets: HashMap<String, u32>,
+}
+
+impl Default for AccessConfig {
+    fn default() -> Self {
+        let budgets = [("/transaction", 30), ("/mine", 5), ("/block", 60)]
+            .into_iter()
+            .map(|(route, budget)| (route.to_string(), budget))
+            .collect();
+
+        AccessConfig {
+            admin_token: None,
+            max_body_size: 2 * 1024 * 1024,
+            window: 60,
+            default_budget: 120,
+            budgets,
+        }
+    }
+}
+
+/// Counts the requests of every client to every route in fixed windows.
+pub struct RateLimiter {
+    config: AccessConfig,
+    windows: Mutex<HashMap<(IpAddr, String), (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: AccessConfig) -> RateLimiter {
+        RateLimiter {
+            config,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a request of `client` to `route`. Returns how many seconds the
+    /// client has to wait if it already spent its budget.
+    pub fn check(&self, client: IpAddr, route: &str) -> Result<(), u64> {
+        let budget = self
+            .config
+            .budgets
+            .get(route)
+            .copied()
+            .unwrap_or(self.config.default_budget);
+        let window = Duration::from_secs(self.config.window);
+        let now = Instant::now();
+
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|_, (start, _)| now.duration_since(*start) < window);
+
+        let (start, count) = windows
+            .entry((client, route.to_string()))
+            .or_insert((now, 0));
+        if *count >= budget {
+            let retry_after = window.saturating_sub(now.duration_since(*start));
+            return Err(retry_after.as_secs_f64().ceil() as u64);
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Admin token the node sends to its peers.
+    pub fn admin_token(&self) -> Option<&str> {
+        self.config.admin_token.as_deref()
+    }
+
+    /// Checks `token` against the admin token in constant time.
+    pub fn is_admin(&self, token: &str) -> bool {
+        match &self.config.admin_token {
+            Some(admin_token) => {
+                admin_token.len() == token.len()
+                    && admin_token
+                        .bytes()
+                        .zip(token.bytes())
+                        .fold(0, |diff, (a, b)| diff | (a ^ b))
+                        == 0
+            }
+            None => false,
+        }
+    }
+}
+
+/// Seconds a rate limited client has to wait, cached for the 429 catcher.
+pub struct RetryAfter(pub u64);
+
+/// Request guard that spends one request of the client's budget for the route.
+pub struct RateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimit {
+    type Error = u64;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter = request.rocket().state::<RateLimiter>().unwrap();
+        let route = request
+            .route()
+            .map(|route| route.uri.path().to_string())
+            .unwrap_or_default();
+
+        // Clients without a known address share the budget of the unspecified one
+        let client = request.client_ip().unwrap_or(IpAddr::from([0, 0, 0, 0]));
+
+        match limiter.check(client, &route) {
+            Ok(()) => Outcome::Success(RateLimit),
+            Err(retry_after) => {
+                request.local_cache(|| RetryAfter(retry_after));
+                Outcome::Error((Status::TooManyRequests, retry_after))
+            }
+        }
+    }
+}
+
+/// Request guard for routes that require `Authorization: Bearer <admin token>`.
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter = request.rocket().state::<RateLimiter>().unwrap();
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match token {
+            Some(token) if limiter.is_admin(token) => Outcome::Success(Admin),
+            _ => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+#[derive(Responder)]
+#[response(status = 429)]
+pub struct TooManyRequests {
+    message: String,
+    retry_after: Header<'static>,
+}
+
+#[derive(Responder)]
+#[response(status = 401)]
+pub struct Unauthorized {
+    message: &'static str,
+    authenticate: Header<'static>,
+}
+
+#[catch(429)]
+pub fn too_many_requests(request: &Request) -> TooManyRequests {
+    let RetryAfter(seconds) = request.local_cache(|| RetryAfter(1));
+    TooManyRequests {
+        message: format!("Too many requests, retry in {} seconds", seconds),
+        retry_after: Header::new("Retry-After", seconds.to_string()),
+    }
+}
+
+#[catch(401)]
+pub fn unauthorized() -> Unauthorized {
+    Unauthorized {
+        message: "A valid admin token is required",
+        authenticate: Header::new("WWW-Authenticate", "Bearer"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockchain_rust::rules::Rules;
+    use rocket::http::ContentType;
+    use rocket::local::blocking::Client;
+
+    fn client(access: AccessConfig) -> Client {
+        Client::tracked(crate::build(
+            rocket::Config::figment(),
+            access,
+            Rules::default(),
+        ))
+        .unwrap()
+    }
+
+    fn with_admin_token() -> AccessConfig {
+        AccessConfig {
+            admin_token: Some("secreto".to_string()),
+            ..AccessConfig::default()
+        }
+    }
+
+    #[test]
+    fn budgets_are_per_client_and_route() {
+        let limiter = RateLimiter::new(AccessConfig {
+            default_budget: 1,
+            budgets: HashMap::from([("/mine".to_string(), 2)]),
+            ..AccessConfig::default()
+        });
+        let alice = IpAddr::from([10, 0, 0, 1]);
+        let bob = IpAddr::from([10, 0, 0, 2]);
+
+        assert_eq!(limiter.check(alice, "/mine"), Ok(()));
+        assert_eq!(limiter.check(alice, "/mine"), Ok(()));
+        assert!(limiter.check(alice, "/mine").is_err());
+        assert_eq!(limiter.check(alice, "/chain"), Ok(()));
+        assert_eq!(limiter.check(bob, "/mine"), Ok(()));
+        let retry_after = limiter.check(alice, "/chain").unwrap_err();
+        assert!((1..=60).contains(&retry_after), "{}", retry_after);
+    }
+
+    #[test]
+    fn exceeding_the_budget_returns_retry_after() {
+        let client = client(AccessConfig {
+            budgets: HashMap::from([("/chain".to_string(), 2)]),
+            ..AccessConfig::default()
+        });
+
+        for _ in 0..2 {
+            assert_eq!(client.get("/chain").dispatch().status(), Status::Ok);
+        }
+        let response = client.get("/chain").dispatch();
+
+        assert_eq!(response.status(), Status::TooManyRequests);
+        let retry_after: u64 = response
+            .headers()
+            .get_one("Retry-After")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry_after), "{}", retry_after);
+        // Other routes keep their own budget
+        assert_eq!(client.get("/nodes").dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn admin_routes_require_the_token() {
+        let client = client(with_admin_token());
+        let requests = [
+            client.get("/mine"),
+            client.get("/nodes/resolve"),
+            client
+                .post("/nodes/register")
+                .header(ContentType::JSON)
+                .body("[]"),
+            client.post("/block").header(ContentType::JSON).body("{}"),
+        ];
+
+        for request in requests {
+            let uri = request.uri().to_string();
+            let wrong = request
+                .clone()
+                .header(Header::new("Authorization", "Bearer secretO"));
+            for request in [request, wrong] {
+                let response = request.dispatch();
+                assert_eq!(response.status(), Status::Unauthorized, "{}", uri);
+                assert_eq!(
+                    response.headers().get_one("WWW-Authenticate"),
+                    Some("Bearer")
+                );
+            }
+        }
+
+        let response = client
+            .post("/nodes/register")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", "Bearer secreto"))
+            .body(r#"["http://127.0.0.1:5001/"]"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_string().unwrap(),
+            r#"["http://127.0.0.1:5001"]"#
+        );
+    }
+
+    #[test]
+    fn admin_routes_are_disabled_without_a_token() {
+        let client = client(AccessConfig::default());
+
+        let response = client
+            .get("/mine")
+            .header(Header::new("Authorization", "Bearer "))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn large_bodies_are_rejected() {
+        let client = client(AccessConfig {
+            max_body_size: 64,
+            ..AccessConfig::default()
+        });
+        let body = format!(
+            r#"{{"sender":"{}","receiver":"bob","amount":1}}"#,
+            "a".repeat(64)
+        );
+
+        let response = client
+            .post("/transaction")
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::PayloadTooLarge);
+    }
+}
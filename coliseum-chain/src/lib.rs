@@ -0,0 +1,470 @@
+use coliseum_money::Amount;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub mod multisig;
+pub mod rules;
+
+use multisig::Multisig;
+use rules::Rules;
+
+/// Address that issues the mining rewards. It is the only address allowed to
+/// spend money it does not hold.
+pub const COINBASE: &str = "0";
+
+/// Reward paid for mining a block.
+pub const MINING_REWARD: Amount = Amount::from_units(1);
+
+/// Condition that must hold before a transaction can be included in a block.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Lock {
+    /// Not valid before the block with this index.
+    Height(usize),
+    /// Not valid before this UNIX timestamp, in seconds.
+    Timestamp(f64),
+}
+
+impl Lock {
+    /// Checks whether the lock is released for a block at `height` mined at `timestamp`.
+    pub fn is_released(&self, height: usize, timestamp: f64) -> bool {
+        match self {
+            Lock::Height(min_height) => height >= *min_height,
+            Lock::Timestamp(min_timestamp) => timestamp >= *min_timestamp,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub sender: String,
+    pub receiver: String,
+    pub amount: Amount,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<Lock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multisig: Option<Multisig>,
+}
+
+impl Transaction {
+    /// Bytes the owners of a shared address sign to authorize the transaction.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        serde_json::to_vec(&(
+            &self.sender,
+            &self.receiver,
+            self.amount,
+            &self.lock,
+            self.multisig_nonce(),
+        ))
+        .unwrap()
+    }
+
+    /// Nonce of the multisig spend, if the transaction spends from a shared address.
+    pub fn multisig_nonce(&self) -> Option<u64> {
+        self.multisig.as_ref().map(|multisig| multisig.nonce)
+    }
+
+    /// Checks that the transaction may be included in a block at `height`
+    /// mined at `timestamp`: its lock is released and, when spending from a
+    /// shared address, enough owners signed it.
+    pub fn validate(&self, height: usize, timestamp: f64) -> Result<(), String> {
+        self.amount.positive().map_err(|error| error.to_string())?;
+
+        if let Some(lock) = &self.lock {
+            if !lock.is_released(height, timestamp) {
+                return Err(format!("transaction is locked until {:?}", lock));
+            }
+        }
+
+        match &self.multisig {
+            Some(multisig) => {
+                if multisig.policy_address() != self.sender {
+                    return Err("sender is not the address of the multisig policy".to_string());
+                }
+                multisig.verify(&self.signing_payload())
+            }
+            None if Multisig::is_shared_address(&self.sender) => {
+                Err("spending from a shared address requires multisig signatures".to_string())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Block {
+    pub index: usize,
+    pub timestamp: f64,
+    pub transactions: Vec<Transaction>,
+    pub proof: usize,
+    pub previous_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Blockchain {
+    pub blocks: Mutex<Vec<Block>>,
+    pub transactions: Mutex<Vec<Transaction>>,
+    #[serde(default)]
+    pub rules: Rules,
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Blockchain::new()
+    }
+}
+
+impl Blockchain {
+    /// Initializes a new instance of the blockchain.
+    pub fn new() -> Self {
+        Blockchain::with_rules(Rules::default())
+    }
+
+    /// Initializes a new instance of the blockchain enforcing `rules`.
+    pub fn with_rules(rules: Rules) -> Self {
+        Blockchain {
+            transactions: Mutex::new(Vec::new()),
+            blocks: Mutex::new(vec![Blockchain::genesis()]),
+            rules,
+        }
+    }
+
+    /// First block of every chain. It does not depend on when the node
+    /// started, so every node of the network shares it.
+    pub fn genesis() -> Block {
+        Block {
+            index: 1,
+            timestamp: 0.0,
+            transactions: Vec::new(),
+            proof: 1,
+            previous_hash: "1".to_string(),
+        }
+    }
+
+    /// Mines the pending transactions into a new block, mining reward
+    /// first. A block pays a single reward, so any other pending reward is
+    /// dropped. Transactions that are not valid yet for this block, that the
+    /// sender cannot afford or that do not fit in the block stay pending.
+    pub fn create_block(&mut self, previous_hash: &str, proof: usize) -> Block {
+        let mut block = Block {
+            index: self.blocks.lock().unwrap().len() + 1,
+            timestamp: Blockchain::now(),
+            transactions: Vec::new(),
+            proof,
+            previous_hash: previous_hash.to_string(),
+        };
+
+        let (rewards, transactions): (Vec<Transaction>, Vec<Transaction>) = self
+            .transactions
+            .lock()
+            .unwrap()
+            .drain(..)
+            .partition(|transaction| transaction.sender == COINBASE);
+
+        let mut balances = self.get_balances().unwrap();
+        let mut size = Rules::block_size(&block);
+        let mut pending = Vec::new();
+        for transaction in rewards.into_iter().take(1).chain(transactions) {
+            let transaction_size = Rules::transaction_size(&transaction);
+            let fits = block.transactions.len() < self.rules.max_transactions_per_block
+                && size + transaction_size <= self.rules.max_block_size;
+
+            if fits
+                && transaction.validate(block.index, block.timestamp).is_ok()
+                && Blockchain::apply_transaction(&mut balances, &transaction).is_ok()
+            {
+                size += transaction_size;
+                block.transactions.push(transaction);
+            } else {
+                pending.push(transaction);
+            }
+        }
+
+        self.blocks.lock().unwrap().push(block.clone());
+        *self.transactions.lock().unwrap() = pending;
+        block
+    }
+
+    /// Adds a new transaction to the list of pending transactions if it could
+    /// be included in the next block.
+    pub fn add_transaction(&mut self, transaction: Transaction) -> Result<Transaction, String> {
+        if transaction.sender == COINBASE {
+            return Err("only mining rewards can be sent from the coinbase".to_string());
+        }
+        self.rules
+            .check_transaction(&transaction)
+            .map_err(|violation| violation.to_string())?;
+
+        let height = self.blocks.lock().unwrap().len() + 1;
+        transaction.validate(height, Blockchain::now())?;
+
+        if let Some(multisig) = &transaction.multisig {
+            if self.is_nonce_used(&transaction.sender, multisig.nonce) {
+                return Err(format!(
+                    "nonce {} was already used by {}",
+                    multisig.nonce, transaction.sender
+                ));
+            }
+        }
+
+        // The sender must afford it on top of what it already has pending
+        let mut balances = self.get_balances()?;
+        for pending in self.transactions.lock().unwrap().iter() {
+            Blockchain::apply_transaction(&mut balances, pending)?;
+        }
+        Blockchain::apply_transaction(&mut balances, &transaction)?;
+
+        self.transactions.lock().unwrap().push(transaction.clone());
+        Ok(transaction)
+    }
+
+    /// Adds the reward for mining the next block to the pending transactions.
+    pub fn add_reward(&mut self, receiver: &str) -> Transaction {
+        let reward = Transaction {
+            sender: COINBASE.to_string(),
+            receiver: receiver.to_string(),
+            amount: MINING_REWARD,
+            lock: None,
+            multisig: None,
+        };
+        self.transactions.lock().unwrap().push(reward.clone());
+        reward
+    }
+
+    /// Checks a block received from elsewhere against the rules enforced when
+    /// mining: it extends the current last block, respects `rules`, pays at
+    /// most one mining reward of `MINING_REWARD`, every transaction is
+    /// unlocked, properly signed and affordable by its sender, and no
+    /// multisig nonce is spent twice.
+    pub fn validate_block(&self, block: &Block) -> Result<(), String> {
+        let last_block = self.get_last_block();
+        if block.index != last_block.index + 1 {
+            return Err(format!(
+                "block index {} does not follow the last block index {}",
+                block.index, last_block.index
+            ));
+        }
+        if block.previous_hash != Blockchain::get_block_hash(&last_block) {
+            return Err("block does not point to the last block hash".to_string());
+        }
+        if !Blockchain::check_proof(&last_block.proof, &block.proof, self.rules.proof_difficulty) {
+            return Err(format!("proof {} is not valid", block.proof));
+        }
+        self.rules
+            .check_block(block, &last_block, Blockchain::now())
+            .map_err(|violation| violation.to_string())?;
+
+        // The coinbase spends money it does not hold, so a block could
+        // otherwise mint as much as it likes
+        let rewards: Vec<&Transaction> = block
+            .transactions
+            .iter()
+            .filter(|transaction| transaction.sender == COINBASE)
+            .collect();
+        if rewards.len() > 1 {
+            return Err(format!(
+                "block pays {} mining rewards, at most one is allowed",
+                rewards.len()
+            ));
+        }
+        if let Some(reward) = rewards.first() {
+            if reward.amount != MINING_REWARD {
+                return Err(format!(
+                    "mining reward of {} does not match the reward of {}",
+                    reward.amount, MINING_REWARD
+                ));
+            }
+        }
+
+        let mut balances = self.get_balances()?;
+        let mut nonces = Vec::new();
+        for transaction in &block.transactions {
+            transaction.validate(block.index, block.timestamp)?;
+            Blockchain::apply_transaction(&mut balances, transaction)?;
+
+            if let Some(multisig) = &transaction.multisig {
+                let key = (transaction.sender.as_str(), multisig.nonce);
+                if nonces.contains(&key) || self.is_nonce_used_in_blocks(key.0, key.1) {
+                    return Err(format!(
+                        "nonce {} was already used by {}",
+                        multisig.nonce, transaction.sender
+                    ));
+                }
+                nonces.push(key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends a block received from elsewhere once it is validated, dropping
+    /// its transactions from the pending ones.
+    pub fn add_block(&mut self, block: Block) -> Result<Block, String> {
+        self.validate_block(&block)?;
+        self.transactions
+            .lock()
+            .unwrap()
+            .retain(|transaction| !block.transactions.contains(transaction));
+        self.blocks.lock().unwrap().push(block.clone());
+        Ok(block)
+    }
+
+    /// Replaces the blocks with `blocks` if they form a longer valid chain
+    /// that starts with the same genesis block as ours. Transactions of the discarded blocks that are not in the new chain go
+    /// back to the pending ones if they are still valid. Returns whether the
+    /// chain was replaced.
+    pub fn replace_chain(&mut self, blocks: Vec<Block>) -> Result<bool, String> {
+        if blocks.len() <= self.blocks.lock().unwrap().len() {
+            return Ok(false);
+        }
+
+        let mut blocks = blocks.into_iter();
+        let genesis = blocks.next().unwrap();
+        let own_genesis = self.blocks.lock().unwrap()[0].clone();
+        if Blockchain::get_block_hash(&genesis) != Blockchain::get_block_hash(&own_genesis) {
+            return Err("chain does not start with our genesis block".to_string());
+        }
+        let mut candidate = Blockchain {
+            blocks: Mutex::new(vec![genesis]),
+            transactions: Mutex::new(Vec::new()),
+            rules: self.rules.clone(),
+        };
+        for block in blocks {
+            candidate.add_block(block)?;
+        }
+
+        let new_blocks = candidate.blocks.into_inner().unwrap();
+        let old_blocks = std::mem::replace(&mut *self.blocks.lock().unwrap(), new_blocks);
+        let pending = std::mem::take(&mut *self.transactions.lock().unwrap());
+
+        let confirmed: Vec<Transaction> = self
+            .blocks
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|block| block.transactions.clone())
+            .collect();
+        let discarded = old_blocks
+            .into_iter()
+            .flat_map(|block| block.transactions)
+            .filter(|transaction| transaction.sender != COINBASE);
+        for transaction in discarded.chain(pending) {
+            if !confirmed.contains(&transaction) {
+                let _ = self.add_transaction(transaction);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the confirmed balance of `address`.
+    pub fn get_balance(&self, address: &str) -> Result<Amount, String> {
+        let balances = self.get_balances()?;
+        Ok(balances.get(address).copied().unwrap_or(Amount::ZERO))
+    }
+
+    /// Returns the confirmed balance of every address that ever received money.
+    pub fn get_balances(&self) -> Result<HashMap<String, Amount>, String> {
+        let mut balances = HashMap::new();
+        for block in self.blocks.lock().unwrap().iter() {
+            for transaction in &block.transactions {
+                Blockchain::apply_transaction(&mut balances, transaction)?;
+            }
+        }
+        Ok(balances)
+    }
+
+    /// Moves the amount of `transaction` between the balances, failing
+    /// without changes if the sender cannot afford it.
+    fn apply_transaction(
+        balances: &mut HashMap<String, Amount>,
+        transaction: &Transaction,
+    ) -> Result<(), String> {
+        let balance = |address: &str| balances.get(address).copied().unwrap_or(Amount::ZERO);
+
+        let sender_balance = if transaction.sender == COINBASE {
+            None
+        } else {
+            let available = balance(&transaction.sender);
+            let remaining = available.checked_sub(transaction.amount).map_err(|_| {
+                format!(
+                    "{} has insufficient funds: {} available, {} required",
+                    transaction.sender, available, transaction.amount
+                )
+            })?;
+            Some(remaining)
+        };
+
+        let receiver_balance = match sender_balance {
+            Some(remaining) if transaction.receiver == transaction.sender => remaining,
+            _ => balance(&transaction.receiver),
+        }
+        .checked_add(transaction.amount)
+        .map_err(|error| error.to_string())?;
+
+        if let Some(remaining) = sender_balance {
+            balances.insert(transaction.sender.clone(), remaining);
+        }
+        balances.insert(transaction.receiver.clone(), receiver_balance);
+        Ok(())
+    }
+
+    /// Returns the last block in the blockchain.
+    pub fn get_last_block(&self) -> Block {
+        self.blocks.lock().unwrap().last().unwrap().clone()
+    }
+
+    pub fn get_block_hash(block: &Block) -> String {
+        let serialized = serde_json::to_string(block).unwrap();
+        sha256::digest(serialized)
+    }
+
+    /// Checks if a given proof is valid based on the previous proof, that is,
+    /// if their hash starts with `difficulty` zeros.
+    pub fn check_proof(previous_proof: &usize, proof: &usize, difficulty: usize) -> bool {
+        let mut proofs = previous_proof.to_owned().to_string();
+        let proof_str = proof.to_string();
+        proofs.push_str(&proof_str);
+        let hashed_proofs = sha256::digest(&proofs);
+
+        for i in hashed_proofs.chars().take(difficulty) {
+            if i != '0' {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Finds a valid proof of work for the blockchain.
+    pub fn obtain_proof(&self) -> usize {
+        let previous_proof = self.get_last_block().proof;
+        let mut proof: usize = 1;
+        while !Blockchain::check_proof(&previous_proof, &proof, self.rules.proof_difficulty) {
+            proof += 1;
+        }
+        proof
+    }
+
+    /// Checks whether a multisig nonce was already spent by `sender`, either
+    /// in the chain or in the pending transactions.
+    fn is_nonce_used(&self, sender: &str, nonce: u64) -> bool {
+        let pending = self.transactions.lock().unwrap().iter().any(|transaction| {
+            transaction.sender == sender && transaction.multisig_nonce() == Some(nonce)
+        });
+        pending || self.is_nonce_used_in_blocks(sender, nonce)
+    }
+
+    fn is_nonce_used_in_blocks(&self, sender: &str, nonce: u64) -> bool {
+        self.blocks.lock().unwrap().iter().any(|block| {
+            block.transactions.iter().any(|transaction| {
+                transaction.sender == sender && transaction.multisig_nonce() == Some(nonce)
+            })
+        })
+    }
+
+    fn now() -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64()
+    }
+}
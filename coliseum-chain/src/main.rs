@@ -1,144 +1,330 @@
+use access::{AccessConfig, Admin, RateLimit, RateLimiter};
+use blockchain_rust::rules::Rules;
+use blockchain_rust::{Block, Blockchain, Transaction, COINBASE};
+use rocket::figment::Figment;
+use rocket::response::status::BadRequest;
 use rocket::serde::json::Json;
-use serde::{Deserialize, Serialize};
+use rocket::{Build, Rocket};
+use std::collections::HashSet;
 use std::sync::Mutex;
-use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Transaction {
-    pub sender: String,
-    pub receiver: String,
-    pub amount: i32,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Block {
-    pub index: usize,
-    pub timestamp: f64,
-    pub transactions: Vec<Transaction>,
-    pub proof: usize,
-    pub previous_hash: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Blockchain {
-    pub blocks: Mutex<Vec<Block>>,
-    pub transactions: Mutex<Vec<Transaction>>,
-}
-
-impl Blockchain {
-    /// Initializes a new instance of the blockchain.
-    pub fn new() -> Self {
-        let mut blockchain = Blockchain {
-            transactions: Mutex::new(Vec::new()),
-            blocks: Mutex::new(Vec::new()),
-        };
-
-        blockchain.create_block("1", 1);
-        blockchain
-    }
-
-    pub fn create_block(&mut self, previous_hash: &str, proof: usize) -> Block {
-        let block = Block {
-            index: self.blocks.lock().unwrap().len() + 1,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs_f64(),
-            transactions: self.transactions.lock().unwrap().clone(),
-            proof,
-            previous_hash: previous_hash.to_string(),
-        };
-        self.blocks.lock().unwrap().push(block.clone());
-        self.transactions.lock().unwrap().clear();
-        block
-    }
-
-    /// Adds a new transaction to the list of pending transactions.
-    pub fn add_transaction(&mut self, transaction: Transaction) -> Transaction {
-        self.transactions.lock().unwrap().push(transaction.clone());
-        transaction
-    }
+#[macro_use]
+extern crate rocket;
 
-    /// Returns the last block in the blockchain.
-    pub fn get_last_block(&self) -> Block {
-        let last_block = self.blocks.lock().unwrap().last().unwrap().clone();
-        last_block
-    }
+mod access;
 
-    pub fn get_block_hash(block: &Block) -> String {
-        let serialized = serde_json::to_string(block).unwrap();
-        let hash = sha256::digest(serialized);
-        hash
-    }
+/// Base URLs (e.g. `http://127.0.0.1:5001`) of the other nodes of the network.
+struct Peers(Mutex<HashSet<String>>);
 
-    /// Checks if a given proof is valid based on the previous proof.
-    pub fn check_proof(previous_proof: &usize, proof: &usize) -> bool {
-        let mut proofs = previous_proof.to_owned().to_string();
-        let proof_str = proof.to_string();
-        proofs.push_str(&proof_str);
-        let hashed_proofs = sha256::digest(&proofs);
-
-        for i in hashed_proofs.chars().take(6) {
-            if i != '0' {
-                return false;
-            }
-        }
-        true
-    }
-
-    /// Finds a valid proof of work for the blockchain.
-    pub fn obtain_proof(&self) -> usize {
-        let previous_proof = self.get_last_block().proof;
-        let mut proof: usize = 1;
-        while !Blockchain::check_proof(&previous_proof, &proof) {
-            proof += 1;
-        }
-        proof
+impl Peers {
+    fn list(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().cloned().collect()
     }
 }
 
-#[macro_use]
-extern crate rocket;
-
 #[get("/mine")]
-fn mine(blockchain_state: &rocket::State<Mutex<Blockchain>>) -> String {
-    let mut blockchain = blockchain_state.inner().lock().unwrap();
-    let proof = blockchain.obtain_proof();
-    let reward = Transaction {
-        sender: "0".to_string(),
-        receiver: "0".to_string(),
-        amount: 1,
+async fn mine(
+    _limit: RateLimit,
+    _admin: Admin,
+    blockchain_state: &rocket::State<Mutex<Blockchain>>,
+    peers: &rocket::State<Peers>,
+    limiter: &rocket::State<RateLimiter>,
+) -> String {
+    let new_block = {
+        let mut blockchain = blockchain_state.inner().lock().unwrap();
+        let proof = blockchain.obtain_proof();
+        blockchain.add_reward(COINBASE);
+        let last_block = blockchain.get_last_block();
+        let previous_hash = Blockchain::get_block_hash(&last_block);
+        blockchain.create_block(&previous_hash, proof)
     };
-    blockchain.add_transaction(reward);
-    let last_block = blockchain.get_last_block();
-    let previous_hash = Blockchain::get_block_hash(&last_block);
-    let new_block = blockchain.create_block(&previous_hash, proof);
+
+    // The new block is announced to the rest of the network, which shares
+    // the admin token. Peers that are behind or on a fork will catch up on
+    // their next /nodes/resolve.
+    let client = reqwest::Client::new();
+    let token = limiter.admin_token().unwrap_or_default();
+    for peer in peers.list() {
+        let _ = client
+            .post(format!("{}/block", peer))
+            .bearer_auth(token)
+            .json(&new_block)
+            .send()
+            .await;
+    }
     serde_json::to_string(&new_block).unwrap()
 }
 
 #[get("/chain")]
-fn chain(blockchain_state: &rocket::State<Mutex<Blockchain>>) -> String {
+fn chain(_limit: RateLimit, blockchain_state: &rocket::State<Mutex<Blockchain>>) -> String {
     let blockchain = blockchain_state.inner().lock().unwrap();
     serde_json::to_string(&blockchain.blocks).unwrap()
 }
 
 #[post("/transaction", data = "<transaction>")]
 async fn transaction(
+    _limit: RateLimit,
     transaction: Json<Transaction>,
     blockchain_state: &rocket::State<Mutex<Blockchain>>,
+) -> Result<String, BadRequest<String>> {
+    let mut blockchain = blockchain_state.inner().lock().unwrap();
+    match blockchain.add_transaction(transaction.into_inner()) {
+        Ok(transaction) => Ok(serde_json::to_string(&transaction).unwrap()),
+        Err(error) => Err(BadRequest(error)),
+    }
+}
+
+#[post("/block", data = "<block>")]
+fn block(
+    _limit: RateLimit,
+    _admin: Admin,
+    block: Json<Block>,
+    blockchain_state: &rocket::State<Mutex<Blockchain>>,
+) -> Result<String, BadRequest<String>> {
+    let mut blockchain = blockchain_state.inner().lock().unwrap();
+    match blockchain.add_block(block.into_inner()) {
+        Ok(block) => Ok(serde_json::to_string(&block).unwrap()),
+        Err(error) => Err(BadRequest(error)),
+    }
+}
+
+#[get("/nodes")]
+fn nodes(_limit: RateLimit, peers: &rocket::State<Peers>) -> String {
+    serde_json::to_string(&peers.list()).unwrap()
+}
+
+#[post("/nodes/register", data = "<nodes>")]
+fn register_nodes(
+    _limit: RateLimit,
+    _admin: Admin,
+    nodes: Json<Vec<String>>,
+    peers: &rocket::State<Peers>,
+) -> String {
+    let nodes = nodes
+        .into_inner()
+        .into_iter()
+        .map(|node| node.trim_end_matches('/').to_string());
+    peers.0.lock().unwrap().extend(nodes);
+    serde_json::to_string(&peers.list()).unwrap()
+}
+
+/// Adopts the longest valid chain among the peers.
+#[get("/nodes/resolve")]
+async fn resolve_nodes(
+    _limit: RateLimit,
+    _admin: Admin,
+    blockchain_state: &rocket::State<Mutex<Blockchain>>,
+    peers: &rocket::State<Peers>,
 ) -> String {
+    let mut chains = Vec::new();
+    for peer in peers.list() {
+        let response = match reqwest::get(format!("{}/chain", peer)).await {
+            Ok(response) => response,
+            Err(_) => continue,
+        };
+        if let Ok(blocks) = response.json::<Vec<Block>>().await {
+            chains.push(blocks);
+        }
+    }
+    chains.sort_by_key(|blocks| std::cmp::Reverse(blocks.len()));
+
     let mut blockchain = blockchain_state.inner().lock().unwrap();
-    let transaction = blockchain.add_transaction(transaction.into_inner());
-    serde_json::to_string(&transaction).unwrap()
+    let mut replaced = false;
+    for blocks in chains {
+        if let Ok(true) = blockchain.replace_chain(blocks) {
+            replaced = true;
+            break;
+        }
+    }
+    serde_json::json!({
+        "replaced": replaced,
+        "length": blockchain.blocks.lock().unwrap().len(),
+    })
+    .to_string()
 }
 
 #[launch]
 fn rocket() -> _ {
     let port = 5000;
-    let blockchain = Mutex::new(Blockchain::new());
+    let figment = rocket::Config::figment().merge(("port", port));
+    let access: AccessConfig = if figment.contains("access") {
+        figment
+            .extract_inner("access")
+            .expect("Invalid access configuration")
+    } else {
+        AccessConfig::default()
+    };
+    if access.admin_token.is_none() {
+        println!(
+            "No admin token configured: /mine, /block, /nodes/register and /nodes/resolve are disabled"
+        );
+    }
+    let rules: Rules = if figment.contains("rules") {
+        figment
+            .extract_inner("rules")
+            .expect("Invalid rules configuration")
+    } else {
+        Rules::default()
+    };
+    build(figment, access, rules)
+}
 
-    rocket::build()
-        .configure(rocket::Config::figment().merge(("port", port)))
+/// Assembles a node with the given configuration, access control and rules.
+fn build(figment: Figment, access: AccessConfig, rules: Rules) -> Rocket<Build> {
+    let figment = figment.merge(("limits.json", access.max_body_size));
+    let blockchain = Mutex::new(Blockchain::with_rules(rules));
+
+    rocket::custom(figment)
         .manage(blockchain)
-        .mount("/", routes![mine, chain, transaction])
+        .manage(Peers(Mutex::new(HashSet::new())))
+        .manage(RateLimiter::new(access))
+        .register(
+            "/",
+            catchers![access::too_many_requests, access::unauthorized],
+        )
+        .mount(
+            "/",
+            routes![
+                mine,
+                chain,
+                transaction,
+                block,
+                nodes,
+                register_nodes,
+                resolve_nodes
+            ],
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    const TOKEN: &str = "secreto";
+
+    /// Launches a node on a free local port and returns its base URL once it
+    /// accepts requests.
+    async fn spawn_node() -> String {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let figment = rocket::Config::figment()
+            .merge(("address", "127.0.0.1"))
+            .merge(("port", port))
+            .merge(("log_level", "off"));
+        let access = AccessConfig {
+            admin_token: Some(TOKEN.to_string()),
+            ..AccessConfig::default()
+        };
+        let rules = Rules {
+            proof_difficulty: 2,
+            ..Rules::default()
+        };
+        rocket::tokio::spawn(build(figment, access, rules).launch());
+
+        let url = format!("http://127.0.0.1:{}", port);
+        while reqwest::get(format!("{}/chain", url)).await.is_err() {
+            rocket::tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        url
+    }
+
+    async fn admin_get(url: String) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(url)
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    async fn register(node: &str, peers: &[&str]) {
+        let response = reqwest::Client::new()
+            .post(format!("{}/nodes/register", node))
+            .bearer_auth(TOKEN)
+            .json(peers)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+
+    /// Hashes of the blocks of `node`.
+    async fn chain(node: &str) -> Vec<String> {
+        let blocks: Vec<Block> = reqwest::get(format!("{}/chain", node))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        blocks.iter().map(Blockchain::get_block_hash).collect()
+    }
+
+    #[async_test]
+    async fn mined_blocks_are_announced_to_registered_peers() {
+        let miner = spawn_node().await;
+        let peer = spawn_node().await;
+        register(&miner, &[&peer]).await;
+
+        assert!(admin_get(format!("{}/mine", miner))
+            .await
+            .status()
+            .is_success());
+
+        assert_eq!(chain(&peer).await.len(), 2);
+        assert_eq!(chain(&peer).await, chain(&miner).await);
+    }
+
+    #[async_test]
+    async fn resolve_adopts_the_longest_chain_of_the_peers() {
+        let miner = spawn_node().await;
+        let node = spawn_node().await;
+        admin_get(format!("{}/mine", miner)).await;
+        admin_get(format!("{}/mine", miner)).await;
+        register(&node, &[&miner]).await;
+
+        let response: Value = admin_get(format!("{}/nodes/resolve", node))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["replaced"], true);
+        assert_eq!(response["length"], 3);
+        assert_eq!(chain(&node).await, chain(&miner).await);
+
+        // Nothing longer to adopt the second time
+        let response: Value = admin_get(format!("{}/nodes/resolve", node))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["replaced"], false);
+    }
+
+    #[async_test]
+    async fn blocks_that_do_not_extend_the_chain_are_rejected() {
+        let miner = spawn_node().await;
+        let node = spawn_node().await;
+        admin_get(format!("{}/mine", miner)).await;
+        let block: Block = admin_get(format!("{}/mine", miner))
+            .await
+            .json()
+            .await
+            .unwrap();
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/block", node))
+            .bearer_auth(TOKEN)
+            .json(&block)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        assert_eq!(chain(&node).await.len(), 1);
+    }
 }
@@ -0,0 +1,265 @@
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Prefix that distinguishes shared addresses from plain ones.
+const SHARED_ADDRESS_PREFIX: &str = "ms";
+
+/// A signature produced by one of the owners of a shared address.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PartySignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// Spending conditions of a shared (M-of-N) address together with the
+/// signatures collected for a specific transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Multisig {
+    pub threshold: usize,
+    pub public_keys: Vec<String>,
+    pub nonce: u64,
+    pub signatures: Vec<PartySignature>,
+}
+
+impl Multisig {
+    /// Derives the shared address owned by `public_keys` when `threshold`
+    /// signatures are required. The order of the keys does not matter.
+    pub fn address(threshold: usize, public_keys: &[String]) -> String {
+        let mut keys: Vec<String> = public_keys.iter().map(|key| key.to_lowercase()).collect();
+        keys.sort();
+        let digest = sha256::digest(format!("{}:{}", threshold, keys.join(",")));
+        format!("{}{}", SHARED_ADDRESS_PREFIX, digest)
+    }
+
+    /// Checks whether `address` has the shape of a shared address.
+    pub fn is_shared_address(address: &str) -> bool {
+        address.len() == SHARED_ADDRESS_PREFIX.len() + 64
+            && address.starts_with(SHARED_ADDRESS_PREFIX)
+            && address[SHARED_ADDRESS_PREFIX.len()..]
+                .chars()
+                .all(|c| c.is_ascii_hexdigit())
+    }
+
+    /// Shared address this policy spends from.
+    pub fn policy_address(&self) -> String {
+        Multisig::address(self.threshold, &self.public_keys)
+    }
+
+    /// Checks the policy itself and that at least `threshold` distinct owners
+    /// signed `payload`.
+    pub fn verify(&self, payload: &[u8]) -> Result<(), String> {
+        if self.public_keys.is_empty() {
+            return Err("multisig policy has no public keys".to_string());
+        }
+        if self.threshold == 0 || self.threshold > self.public_keys.len() {
+            return Err(format!(
+                "multisig threshold {} is not between 1 and {}",
+                self.threshold,
+                self.public_keys.len()
+            ));
+        }
+
+        let mut owners = HashSet::new();
+        for key in &self.public_keys {
+            if !owners.insert(key.to_lowercase()) {
+                return Err(format!("public key {} is repeated in the policy", key));
+            }
+            parse_public_key(key)?;
+        }
+
+        let mut signers = HashSet::new();
+        for party in &self.signatures {
+            let key = party.public_key.to_lowercase();
+            if !owners.contains(&key) {
+                return Err(format!(
+                    "{} is not an owner of the address",
+                    party.public_key
+                ));
+            }
+            let verifying_key = parse_public_key(&key)?;
+            let signature = parse_signature(&party.signature)?;
+            if verifying_key.verify_strict(payload, &signature).is_err() {
+                return Err(format!("invalid signature from {}", party.public_key));
+            }
+            signers.insert(key);
+        }
+
+        if signers.len() < self.threshold {
+            return Err(format!(
+                "{} of {} required signatures provided",
+                signers.len(),
+                self.threshold
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_public_key(key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(format!("public key {} is not a 32 byte hex string", key))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| format!("public key {} is not valid", key))
+}
+
+fn parse_signature(signature: &str) -> Result<Signature, String> {
+    let bytes = hex::decode(signature).map_err(|_| "signature is not hex encoded".to_string())?;
+    Signature::from_slice(&bytes).map_err(|_| "signature is not 64 bytes long".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use crate::{Block, Blockchain, Lock, Transaction};
+    use coliseum_money::Amount;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn public_key(seed: u8) -> String {
+        hex::encode(signing_key(seed).verifying_key().as_bytes())
+    }
+
+    /// Spend of 1 unit from the 2-of-3 address of keys 1, 2 and 3.
+    fn spend(nonce: u64) -> Transaction {
+        let public_keys = vec![public_key(1), public_key(2), public_key(3)];
+        Transaction {
+            sender: Multisig::address(2, &public_keys),
+            receiver: "bob".to_string(),
+            amount: Amount::from_units(1),
+            lock: None,
+            multisig: Some(Multisig {
+                threshold: 2,
+                public_keys,
+                nonce,
+                signatures: Vec::new(),
+            }),
+        }
+    }
+
+    fn sign(transaction: &mut Transaction, seeds: &[u8]) {
+        let payload = transaction.signing_payload();
+        let multisig = transaction.multisig.as_mut().unwrap();
+        for seed in seeds {
+            multisig.signatures.push(PartySignature {
+                public_key: public_key(*seed),
+                signature: hex::encode(signing_key(*seed).sign(&payload).to_bytes()),
+            });
+        }
+    }
+
+    fn verify(transaction: &Transaction) -> Result<(), String> {
+        let multisig = transaction.multisig.as_ref().unwrap();
+        multisig.verify(&transaction.signing_payload())
+    }
+
+    #[test]
+    fn the_threshold_must_be_met() {
+        let mut transaction = spend(1);
+        sign(&mut transaction, &[1]);
+        assert!(verify(&transaction).unwrap_err().contains("1 of 2"));
+
+        sign(&mut transaction, &[3]);
+        assert_eq!(verify(&transaction), Ok(()));
+        assert_eq!(transaction.validate(1, 0.0), Ok(()));
+    }
+
+    #[test]
+    fn duplicate_signers_count_once() {
+        let mut transaction = spend(1);
+        sign(&mut transaction, &[2, 2]);
+
+        assert!(verify(&transaction).unwrap_err().contains("1 of 2"));
+    }
+
+    #[test]
+    fn only_owners_can_sign() {
+        let mut transaction = spend(1);
+        sign(&mut transaction, &[1, 4]);
+
+        assert_eq!(
+            verify(&transaction).unwrap_err(),
+            format!("{} is not an owner of the address", public_key(4))
+        );
+    }
+
+    #[test]
+    fn signatures_must_match_the_transaction() {
+        let mut transaction = spend(1);
+        sign(&mut transaction, &[1, 2]);
+        transaction.amount = Amount::from_units(2);
+
+        assert_eq!(
+            verify(&transaction).unwrap_err(),
+            format!("invalid signature from {}", public_key(1))
+        );
+    }
+
+    #[test]
+    fn locks_hold_until_released() {
+        let mut transaction = spend(1);
+        transaction.lock = Some(Lock::Height(5));
+        sign(&mut transaction, &[1, 2]);
+        assert!(transaction.validate(4, 0.0).is_err());
+        assert_eq!(transaction.validate(5, 0.0), Ok(()));
+
+        let mut transaction = spend(2);
+        transaction.lock = Some(Lock::Timestamp(1000.0));
+        sign(&mut transaction, &[1, 2]);
+        assert!(transaction.validate(100, 999.0).is_err());
+        assert_eq!(transaction.validate(1, 1000.0), Ok(()));
+    }
+
+    #[test]
+    fn blocks_cannot_reuse_a_nonce() {
+        let mut blockchain = Blockchain::with_rules(Rules {
+            proof_difficulty: 0,
+            ..Rules::default()
+        });
+        let shared = spend(1).sender;
+        let mine = |blockchain: &mut Blockchain| {
+            let previous_hash = Blockchain::get_block_hash(&blockchain.get_last_block());
+            blockchain.create_block(&previous_hash, 1)
+        };
+        for _ in 0..3 {
+            blockchain.add_reward(&shared);
+            mine(&mut blockchain);
+        }
+
+        let mut transaction = spend(7);
+        sign(&mut transaction, &[1, 2]);
+        blockchain.add_transaction(transaction.clone()).unwrap();
+        mine(&mut blockchain);
+        assert_eq!(
+            blockchain.get_balance("bob").unwrap(),
+            Amount::from_units(1)
+        );
+
+        // Replaying the spend, or spending twice in the same block
+        let last_block = blockchain.get_last_block();
+        let block = |transactions: Vec<Transaction>| Block {
+            index: last_block.index + 1,
+            timestamp: Blockchain::now(),
+            transactions,
+            proof: 1,
+            previous_hash: Blockchain::get_block_hash(&last_block),
+        };
+        let mut other = spend(8);
+        sign(&mut other, &[2, 3]);
+        let mut again = other.clone();
+        again.receiver = "carol".to_string();
+        again.multisig.as_mut().unwrap().signatures.clear();
+        sign(&mut again, &[1, 3]);
+
+        for transactions in [vec![transaction], vec![other.clone(), again]] {
+            let error = blockchain.validate_block(&block(transactions)).unwrap_err();
+            assert!(error.contains("was already used"), "{}", error);
+        }
+        assert_eq!(blockchain.validate_block(&block(vec![other])), Ok(()));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::multisig::Multisig;
+use crate::{Block, Transaction, COINBASE};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Limits every block and transaction must respect, both when mining and
+/// when accepting a block from elsewhere.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Rules {
+    /// Maximum number of transactions in a block, mining reward included.
+    pub max_transactions_per_block: usize,
+    /// Maximum size of a block serialized as JSON, in bytes.
+    pub max_block_size: usize,
+    /// Maximum length of a plain (not shared) address.
+    pub max_address_length: usize,
+    /// Whether a sender may send money to itself.
+    pub allow_self_transfers: bool,
+    /// How many seconds a block may be timestamped before the previous block.
+    pub max_past_drift: f64,
+    /// How many seconds a block may be timestamped ahead of the local clock.
+    pub max_future_drift: f64,
+    /// Number of leading zeros the proof of work hash must have.
+    pub proof_difficulty: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            max_transactions_per_block: 1000,
+            max_block_size: 1024 * 1024,
+            max_address_length: 64,
+            allow_self_transfers: false,
+            max_past_drift: 0.0,
+            max_future_drift: 120.0,
+            proof_difficulty: 6,
+        }
+    }
+}
+
+/// Reason why a block or a transaction breaks the rules.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleViolation {
+    TooManyTransactions { count: usize, max: usize },
+    BlockTooLarge { size: usize, max: usize },
+    InvalidAddress(String),
+    SelfTransfer(String),
+    TimestampBeforePrevious { timestamp: f64, previous: f64 },
+    TimestampInFuture { timestamp: f64, now: f64 },
+}
+
+impl fmt::Display for RuleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleViolation::TooManyTransactions { count, max } => write!(
+                f,
+                "block has {} transactions, at most {} are allowed",
+                count, max
+            ),
+            RuleViolation::BlockTooLarge { size, max } => {
+                write!(f, "block takes {} bytes, at most {} are allowed", size, max)
+            }
+            RuleViolation::InvalidAddress(address) => {
+                write!(f, "'{}' is not a valid address", address)
+            }
+            RuleViolation::SelfTransfer(address) => {
+                write!(f, "{} cannot send money to itself", address)
+            }
+            RuleViolation::TimestampBeforePrevious {
+                timestamp,
+                previous,
+            } => write!(
+                f,
+                "block timestamp {} is before the previous block timestamp {}",
+                timestamp, previous
+            ),
+            RuleViolation::TimestampInFuture { timestamp, now } => write!(
+                f,
+                "block timestamp {} is too far ahead of the local clock {}",
+                timestamp, now
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RuleViolation {}
+
+impl Rules {
+    /// Checks the addresses of a transaction and the self-transfer policy.
+    pub fn check_transaction(&self, transaction: &Transaction) -> Result<(), RuleViolation> {
+        for address in [&transaction.sender, &transaction.receiver] {
+            if !self.is_valid_address(address) {
+                return Err(RuleViolation::InvalidAddress(address.clone()));
+            }
+        }
+
+        // Mining rewards are issued by the coinbase to whoever mined the block
+        if !self.allow_self_transfers
+            && transaction.sender != COINBASE
+            && transaction.sender == transaction.receiver
+        {
+            return Err(RuleViolation::SelfTransfer(transaction.sender.clone()));
+        }
+        Ok(())
+    }
+
+    /// Checks the size and timestamp of `block`, which extends `previous`,
+    /// and every one of its transactions.
+    pub fn check_block(
+        &self,
+        block: &Block,
+        previous: &Block,
+        now: f64,
+    ) -> Result<(), RuleViolation> {
+        if block.transactions.len() > self.max_transactions_per_block {
+            return Err(RuleViolation::TooManyTransactions {
+                count: block.transactions.len(),
+                max: self.max_transactions_per_block,
+            });
+        }
+
+        let size = Rules::block_size(block);
+        if size > self.max_block_size {
+            return Err(RuleViolation::BlockTooLarge {
+                size,
+                max: self.max_block_size,
+            });
+        }
+
+        if block.timestamp < previous.timestamp - self.max_past_drift {
+            return Err(RuleViolation::TimestampBeforePrevious {
+                timestamp: block.timestamp,
+                previous: previous.timestamp,
+            });
+        }
+        if block.timestamp > now + self.max_future_drift {
+            return Err(RuleViolation::TimestampInFuture {
+                timestamp: block.timestamp,
+                now,
+            });
+        }
+
+        for transaction in &block.transactions {
+            self.check_transaction(transaction)?;
+        }
+        Ok(())
+    }
+
+    /// Plain addresses are made of ASCII letters, digits, `_` and `-`.
+    pub fn is_valid_address(&self, address: &str) -> bool {
+        if address == COINBASE || Multisig::is_shared_address(address) {
+            return true;
+        }
+        !address.is_empty()
+            && address.len() <= self.max_address_length
+            && address
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    }
+
+    /// Size in bytes of the block serialized as JSON.
+    pub fn block_size(block: &Block) -> usize {
+        serde_json::to_vec(block).unwrap().len()
+    }
+
+    /// Size in bytes a transaction adds to a serialized block.
+    pub fn transaction_size(transaction: &Transaction) -> usize {
+        serde_json::to_vec(transaction).unwrap().len() + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coliseum_money::Amount;
+
+    fn transfer(sender: &str, receiver: &str) -> Transaction {
+        Transaction {
+            sender: sender.to_string(),
+            receiver: receiver.to_string(),
+            amount: Amount::from_units(1),
+            lock: None,
+            multisig: None,
+        }
+    }
+
+    fn block(timestamp: f64, transactions: Vec<Transaction>) -> Block {
+        Block {
+            index: 2,
+            timestamp,
+            transactions,
+            proof: 1,
+            previous_hash: "hash".to_string(),
+        }
+    }
+
+    #[test]
+    fn blocks_are_limited_in_transactions_and_size() {
+        let rules = Rules {
+            max_transactions_per_block: 2,
+            ..Rules::default()
+        };
+        let previous = block(100.0, Vec::new());
+        let transactions = vec![transfer("alice", "bob"); 3];
+
+        assert_eq!(
+            rules.check_block(&block(100.0, transactions.clone()), &previous, 100.0),
+            Err(RuleViolation::TooManyTransactions { count: 3, max: 2 })
+        );
+        assert_eq!(
+            rules.check_block(&block(100.0, transactions[..2].to_vec()), &previous, 100.0),
+            Ok(())
+        );
+
+        let large = block(100.0, transactions);
+        let rules = Rules {
+            max_block_size: Rules::block_size(&large) - 1,
+            ..Rules::default()
+        };
+        assert_eq!(
+            rules.check_block(&large, &previous, 100.0),
+            Err(RuleViolation::BlockTooLarge {
+                size: Rules::block_size(&large),
+                max: Rules::block_size(&large) - 1,
+            })
+        );
+    }
+
+    #[test]
+    fn addresses_must_be_plain_or_shared() {
+        let rules = Rules {
+            max_address_length: 8,
+            ..Rules::default()
+        };
+        let shared = Multisig::address(1, &["aa".to_string()]);
+
+        assert!(rules.is_valid_address("alice_01"));
+        assert!(rules.is_valid_address(COINBASE));
+        // Shared addresses are longer than plain ones may be
+        assert!(rules.is_valid_address(&shared));
+        assert!(!rules.is_valid_address(&shared[..shared.len() - 1]));
+        assert!(!rules.is_valid_address(&format!("{}g", &shared[..shared.len() - 1])));
+        for address in ["", "alice bob", "alice!", "alice_012"] {
+            assert!(!rules.is_valid_address(address), "{}", address);
+        }
+        assert_eq!(
+            rules.check_transaction(&transfer("alice", "bob?")),
+            Err(RuleViolation::InvalidAddress("bob?".to_string()))
+        );
+        assert_eq!(
+            rules.check_block(
+                &block(0.0, vec![transfer("al ice", "bob")]),
+                &block(0.0, Vec::new()),
+                0.0
+            ),
+            Err(RuleViolation::InvalidAddress("al ice".to_string()))
+        );
+    }
+
+    #[test]
+    fn self_transfers_follow_the_policy() {
+        let rules = Rules::default();
+
+        assert_eq!(
+            rules.check_transaction(&transfer("alice", "alice")),
+            Err(RuleViolation::SelfTransfer("alice".to_string()))
+        );
+        assert_eq!(
+            rules.check_transaction(&transfer(COINBASE, COINBASE)),
+            Ok(())
+        );
+        let rules = Rules {
+            allow_self_transfers: true,
+            ..rules
+        };
+        assert_eq!(rules.check_transaction(&transfer("alice", "alice")), Ok(()));
+    }
+
+    #[test]
+    fn timestamps_may_only_drift_so_far() {
+        let rules = Rules {
+            max_past_drift: 10.0,
+            max_future_drift: 60.0,
+            ..Rules::default()
+        };
+        let previous = block(1000.0, Vec::new());
+        let check =
+            |timestamp: f64| rules.check_block(&block(timestamp, Vec::new()), &previous, 1000.0);
+
+        assert_eq!(check(990.0), Ok(()));
+        assert_eq!(
+            check(989.0),
+            Err(RuleViolation::TimestampBeforePrevious {
+                timestamp: 989.0,
+                previous: 1000.0,
+            })
+        );
+        assert_eq!(check(1060.0), Ok(()));
+        assert_eq!(
+            check(1061.0),
+            Err(RuleViolation::TimestampInFuture {
+                timestamp: 1061.0,
+                now: 1000.0,
+            })
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! In-process network of coliseum-chain nodes for tests.
+//!
+//! Nodes talk to each other through an in-memory transport: announcing a
+//! block or pulling a chain is a direct call on the peer's `Blockchain`,
+//! unless a partition separates both nodes. It follows what the routes of a
+//! node do (`/mine` announces to `/block`, `/nodes/resolve` pulls `/chain`),
+//! which are tested over HTTP in `main.rs`.
+
+use blockchain_rust::rules::Rules;
+use blockchain_rust::{Block, Blockchain, Transaction};
+use std::collections::HashMap;
+
+pub struct Network {
+    pub nodes: Vec<Blockchain>,
+    /// Partition group of every node. Nodes only reach the ones in their group.
+    groups: Vec<usize>,
+}
+
+impl Network {
+    /// Starts `size` nodes, which share the genesis block.
+    pub fn new(size: usize) -> Network {
+        let rules = Rules {
+            proof_difficulty: 2,
+            ..Rules::default()
+        };
+        let nodes = (0..size)
+            .map(|_| Blockchain::with_rules(rules.clone()))
+            .collect();
+
+        Network {
+            nodes,
+            groups: vec![0; size],
+        }
+    }
+
+    /// Address that receives the mining rewards of `node`.
+    pub fn address(node: usize) -> String {
+        format!("node-{}", node)
+    }
+
+    pub fn can_reach(&self, from: usize, to: usize) -> bool {
+        from != to && self.groups[from] == self.groups[to]
+    }
+
+    /// Splits the network so that nodes only reach the ones in their group.
+    /// Nodes not listed end up isolated on their own.
+    pub fn partition(&mut self, groups: &[&[usize]]) {
+        let isolated = groups.len();
+        for (node, group) in self.groups.iter_mut().enumerate() {
+            *group = isolated + node;
+        }
+        for (index, group) in groups.iter().enumerate() {
+            for node in group.iter() {
+                self.groups[*node] = index;
+            }
+        }
+    }
+
+    /// Reconnects every node with every other.
+    pub fn heal(&mut self) {
+        self.groups = vec![0; self.nodes.len()];
+    }
+
+    pub fn inject_transaction(
+        &mut self,
+        node: usize,
+        transaction: Transaction,
+    ) -> Result<Transaction, String> {
+        self.nodes[node].add_transaction(transaction)
+    }
+
+    /// Mines a block on `node`, rewarding it, and announces it to the peers
+    /// it can reach. Like `/mine`, peers that cannot append the block keep
+    /// their chain until they sync.
+    pub fn mine(&mut self, node: usize) -> Block {
+        let miner = &mut self.nodes[node];
+        let proof = miner.obtain_proof();
+        miner.add_reward(&Network::address(node));
+        let previous_hash = Blockchain::get_block_hash(&miner.get_last_block());
+        let block = miner.create_block(&previous_hash, proof);
+
+        for peer in 0..self.nodes.len() {
+            if self.can_reach(node, peer) {
+                let _ = self.nodes[peer].add_block(block.clone());
+            }
+        }
+        block
+    }
+
+    /// Runs one round of chain resolution, like `/nodes/resolve` on every
+    /// node: each adopts the longest valid chain among the peers it can reach. Returns whether any node
+    /// replaced its chain.
+    pub fn sync(&mut self) -> bool {
+        let mut replaced = false;
+        for node in 0..self.nodes.len() {
+            for peer in 0..self.nodes.len() {
+                if self.can_reach(node, peer) {
+                    let chain = self.chain(peer);
+                    replaced |= self.nodes[node].replace_chain(chain).unwrap_or(false);
+                }
+            }
+        }
+        replaced
+    }
+
+    /// Syncs until no node changes its chain or `max_rounds` is reached.
+    /// Returns whether the network settled.
+    pub fn settle(&mut self, max_rounds: usize) -> bool {
+        (0..max_rounds).any(|_| !self.sync())
+    }
+
+    pub fn chain(&self, node: usize) -> Vec<Block> {
+        self.nodes[node].blocks.lock().unwrap().clone()
+    }
+
+    /// Hash of the last block of `node`.
+    pub fn tip(&self, node: usize) -> String {
+        Blockchain::get_block_hash(&self.nodes[node].get_last_block())
+    }
+
+    /// Checks whether every node reachable from each other shares the same tip.
+    pub fn is_converged(&self) -> bool {
+        let mut tips = HashMap::new();
+        (0..self.nodes.len()).all(|node| {
+            let tip = self.tip(node);
+            tips.entry(self.groups[node]).or_insert_with(|| tip.clone()) == &tip
+        })
+    }
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use blockchain_rust::{Transaction, COINBASE, MINING_REWARD};
+use coliseum_money::Amount;
+use common::Network;
+
+fn transfer(sender: &str, receiver: &str, units: u64) -> Transaction {
+    Transaction {
+        sender: sender.to_string(),
+        receiver: receiver.to_string(),
+        amount: Amount::from_units(units),
+        lock: None,
+        multisig: None,
+    }
+}
+
+#[test]
+fn mined_blocks_reach_every_node() {
+    let mut network = Network::new(4);
+
+    network.mine(0);
+    network.mine(3);
+
+    assert!(network.is_converged());
+    for node in 0..4 {
+        assert_eq!(network.chain(node).len(), 3);
+    }
+}
+
+#[test]
+fn partitions_converge_on_the_longest_chain_after_healing() {
+    let mut network = Network::new(4);
+    network.partition(&[&[0, 1], &[2, 3]]);
+
+    network.mine(0);
+    network.mine(1);
+    network.mine(2);
+
+    assert!(network.is_converged());
+    assert_ne!(network.tip(0), network.tip(2));
+
+    network.heal();
+    assert!(!network.is_converged());
+    assert!(network.settle(10));
+
+    assert!(network.is_converged());
+    assert_eq!(network.chain(3).len(), 3);
+    for node in 0..4 {
+        let balance = network.nodes[node]
+            .get_balance(&Network::address(2))
+            .unwrap();
+        assert_eq!(balance, Amount::ZERO);
+    }
+}
+
+#[test]
+fn isolated_nodes_keep_their_own_chain() {
+    let mut network = Network::new(3);
+    network.partition(&[&[0, 1]]);
+
+    network.mine(2);
+    network.mine(2);
+    assert!(network.settle(10));
+    assert_eq!(network.chain(0).len(), 1);
+    assert_eq!(network.chain(2).len(), 3);
+
+    network.heal();
+    assert!(network.settle(10));
+    assert!(network.is_converged());
+    assert_eq!(network.chain(0).len(), 3);
+}
+
+#[test]
+fn transactions_of_orphaned_blocks_are_mined_again() {
+    let mut network = Network::new(3);
+    network.mine(0);
+    let payment = transfer(&Network::address(0), "bob", 1);
+
+    network.partition(&[&[0, 1], &[2]]);
+    network.inject_transaction(2, payment.clone()).unwrap();
+    network.mine(2);
+    network.mine(0);
+    network.mine(1);
+
+    network.heal();
+    assert!(network.settle(10));
+    assert!(network.is_converged());
+    assert!(network.nodes[2]
+        .transactions
+        .lock()
+        .unwrap()
+        .contains(&payment));
+
+    network.mine(2);
+    assert!(network.is_converged());
+    for node in 0..3 {
+        let balance = network.nodes[node].get_balance("bob").unwrap();
+        assert_eq!(balance, Amount::from_units(1));
+    }
+}
+
+#[test]
+fn invalid_blocks_are_not_propagated() {
+    let mut network = Network::new(2);
+    network.partition(&[&[0], &[1]]);
+    let mut block = network.mine(0);
+
+    block.transactions.push(transfer("nobody", "bob", 5));
+    assert!(network.nodes[1].add_block(block).is_err());
+    assert_eq!(network.chain(1).len(), 1);
+}
+
+#[test]
+fn blocks_minting_extra_coin_are_rejected() {
+    let mut network = Network::new(2);
+    network.partition(&[&[0], &[1]]);
+    let mined = network.mine(0);
+
+    let mut inflated = mined.clone();
+    inflated.transactions[0].amount = Amount::from_units(1_000_000);
+    assert!(network.nodes[1].add_block(inflated).is_err());
+
+    let mut doubled = mined.clone();
+    doubled.transactions.push(transfer(
+        COINBASE,
+        &Network::address(0),
+        MINING_REWARD.units(),
+    ));
+    assert!(network.nodes[1].add_block(doubled).is_err());
+    assert_eq!(network.chain(1).len(), 1);
+
+    network.heal();
+    assert!(network.nodes[1].add_block(mined).is_ok());
+    assert_eq!(
+        network.nodes[1].get_balance(&Network::address(0)).unwrap(),
+        MINING_REWARD
+    );
+}
+
+#[test]
+fn chains_with_another_genesis_are_not_adopted() {
+    let mut network = Network::new(2);
+    network.partition(&[&[0], &[1]]);
+    network.nodes[1].blocks.lock().unwrap()[0].proof = 7;
+    network.mine(1);
+    network.mine(1);
+
+    network.heal();
+    assert!(network.settle(10));
+    assert_eq!(network.chain(0).len(), 1);
+    let forked = network.chain(1);
+    assert!(network.nodes[0].replace_chain(forked).is_err());
+}
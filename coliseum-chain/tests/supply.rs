@@ -0,0 +1,88 @@
+use blockchain_rust::{Blockchain, Transaction, COINBASE};
+use coliseum_money::Amount;
+use proptest::prelude::*;
+
+const ADDRESSES: [&str; 4] = ["alice", "bob", "carol", "dave"];
+
+#[derive(Debug, Clone)]
+enum Operation {
+    Reward(usize),
+    Transfer(usize, usize, u64),
+    Mine,
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        (0..ADDRESSES.len()).prop_map(Operation::Reward),
+        (0..ADDRESSES.len(), 0..ADDRESSES.len(), 0..4u64)
+            .prop_map(|(from, to, amount)| Operation::Transfer(from, to, amount)),
+        Just(Operation::Mine),
+    ]
+}
+
+fn issued(blockchain: &Blockchain) -> u64 {
+    blockchain
+        .blocks
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|block| block.transactions.clone())
+        .filter(|transaction| transaction.sender == COINBASE)
+        .map(|transaction| transaction.amount.units())
+        .sum()
+}
+
+proptest! {
+    #[test]
+    fn transfers_conserve_total_supply(operations in prop::collection::vec(operation(), 1..60)) {
+        let mut blockchain = Blockchain::new();
+
+        for operation in operations {
+            match operation {
+                Operation::Reward(receiver) => {
+                    blockchain.add_reward(ADDRESSES[receiver]);
+                }
+                Operation::Transfer(from, to, amount) => {
+                    let transaction = Transaction {
+                        sender: ADDRESSES[from].to_string(),
+                        receiver: ADDRESSES[to].to_string(),
+                        amount: Amount::from_units(amount),
+                        lock: None,
+                        multisig: None,
+                    };
+                    let _ = blockchain.add_transaction(transaction);
+                }
+                Operation::Mine => {
+                    let previous_hash = Blockchain::get_block_hash(&blockchain.get_last_block());
+                    blockchain.create_block(&previous_hash, 1);
+                }
+            }
+
+            let balances = blockchain.get_balances().unwrap();
+            let total: u64 = balances.values().map(|balance| balance.units()).sum();
+            prop_assert_eq!(total, issued(&blockchain));
+        }
+    }
+
+    #[test]
+    fn overdrafts_are_rejected(reward_count in 0..5usize, amount in 1..10u64) {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..reward_count {
+            blockchain.add_reward("alice");
+        }
+        blockchain.create_block("hash", 1);
+
+        let transaction = Transaction {
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            amount: Amount::from_units(amount),
+            lock: None,
+            multisig: None,
+        };
+        let balance = blockchain.get_balance("alice").unwrap();
+        prop_assert_eq!(
+            blockchain.add_transaction(transaction).is_ok(),
+            amount <= balance.units()
+        );
+    }
+}
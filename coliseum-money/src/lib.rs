@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Number of decimals used when none is configured.
+pub const DEFAULT_DECIMALS: u32 = 2;
+
+/// Largest number of decimals an amount can be expressed with.
+pub const MAX_DECIMALS: u32 = 18;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    Empty,
+    Negative,
+    Zero,
+    InvalidFormat,
+    TooManyDecimals(u32),
+    Overflow,
+    Underflow,
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmountError::Empty => write!(f, "amount is empty"),
+            AmountError::Negative => write!(f, "amount cannot be negative"),
+            AmountError::Zero => write!(f, "amount must be greater than zero"),
+            AmountError::InvalidFormat => write!(f, "amount is not a valid decimal number"),
+            AmountError::TooManyDecimals(decimals) => {
+                write!(f, "amount has more than {} decimals", decimals)
+            }
+            AmountError::Overflow => write!(f, "amount overflow"),
+            AmountError::Underflow => write!(f, "amount underflow"),
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+/// An amount of money expressed in minimal units (e.g. cents when working
+/// with 2 decimals). The number of decimals is not stored in the amount, it
+/// is only needed to parse or display it, so it is serialized as the integer
+/// number of units: 2.50 with 2 decimals is `250`.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(transparent)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    /// Creates an amount from minimal units.
+    pub const fn from_units(units: u64) -> Amount {
+        Amount(units)
+    }
+
+    /// Creates an amount of `whole` units with `decimals` decimals, e.g.
+    /// `from_whole(10, 2)` is 1000 minimal units.
+    pub fn from_whole(whole: u64, decimals: u32) -> Result<Amount, AmountError> {
+        whole
+            .checked_mul(scale(decimals)?)
+            .map(Amount)
+            .ok_or(AmountError::Overflow)
+    }
+
+    /// Parses a decimal string such as `"12.5"` into minimal units.
+    pub fn parse(value: &str, decimals: u32) -> Result<Amount, AmountError> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(AmountError::Empty);
+        }
+        if value.starts_with('-') {
+            return Err(AmountError::Negative);
+        }
+
+        let (whole, fraction) = match value.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (value, ""),
+        };
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+            return Err(AmountError::InvalidFormat);
+        }
+        if fraction.len() > decimals as usize {
+            return Err(AmountError::TooManyDecimals(decimals));
+        }
+
+        let whole: u64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| AmountError::Overflow)?
+        };
+        let mut units = Amount::from_whole(whole, decimals)?;
+        if !fraction.is_empty() {
+            let padding = decimals - fraction.len() as u32;
+            let fraction: u64 = fraction.parse().map_err(|_| AmountError::Overflow)?;
+            units = units.checked_add(Amount(fraction * 10u64.pow(padding)))?;
+        }
+        Ok(units)
+    }
+
+    /// Minimal units in the amount.
+    pub const fn units(self) -> u64 {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the amount if it can be transferred, that is, if it is not zero.
+    pub fn positive(self) -> Result<Amount, AmountError> {
+        if self.is_zero() {
+            Err(AmountError::Zero)
+        } else {
+            Ok(self)
+        }
+    }
+
+    pub fn checked_add(self, other: Amount) -> Result<Amount, AmountError> {
+        self.0
+            .checked_add(other.0)
+            .map(Amount)
+            .ok_or(AmountError::Overflow)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Result<Amount, AmountError> {
+        self.0
+            .checked_sub(other.0)
+            .map(Amount)
+            .ok_or(AmountError::Underflow)
+    }
+
+    /// Formats the amount as a decimal string with `decimals` decimals.
+    pub fn to_decimal_string(self, decimals: u32) -> String {
+        if decimals == 0 {
+            return self.0.to_string();
+        }
+        let digits = format!("{:0>width$}", self.0, width = decimals as usize + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - decimals as usize);
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn scale(decimals: u32) -> Result<u64, AmountError> {
+    if decimals > MAX_DECIMALS {
+        return Err(AmountError::TooManyDecimals(MAX_DECIMALS));
+    }
+    Ok(10u64.pow(decimals))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_decimal_strings() {
+        assert_eq!(Amount::parse("12.5", 2), Ok(Amount(1250)));
+        assert_eq!(Amount::parse(" 7 ", 2), Ok(Amount(700)));
+        assert_eq!(Amount::parse(".05", 2), Ok(Amount(5)));
+        assert_eq!(Amount::parse("3.", 2), Ok(Amount(300)));
+        assert_eq!(Amount::parse("42", 0), Ok(Amount(42)));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        assert_eq!(
+            Amount::parse("1.234", 2),
+            Err(AmountError::TooManyDecimals(2))
+        );
+        assert_eq!(
+            Amount::parse("1.5", 0),
+            Err(AmountError::TooManyDecimals(0))
+        );
+        assert_eq!(Amount::parse("-1", 2), Err(AmountError::Negative));
+        assert_eq!(Amount::parse("", 2), Err(AmountError::Empty));
+        assert_eq!(Amount::parse("   ", 2), Err(AmountError::Empty));
+        for value in [".", "1.2.3", "1e3", "+1", "1,5", "abc"] {
+            assert_eq!(
+                Amount::parse(value, 2),
+                Err(AmountError::InvalidFormat),
+                "{}",
+                value
+            );
+        }
+        assert_eq!(
+            Amount::parse("0", 2).unwrap().positive(),
+            Err(AmountError::Zero)
+        );
+    }
+
+    #[test]
+    fn detects_overflow_and_underflow() {
+        assert_eq!(
+            Amount::parse(&u64::MAX.to_string(), 0),
+            Ok(Amount(u64::MAX))
+        );
+        assert_eq!(
+            Amount::parse("18446744073709551616", 0),
+            Err(AmountError::Overflow)
+        );
+        // Fits in a u64 until it is scaled to cents
+        assert_eq!(
+            Amount::parse("184467440737095517", 2),
+            Err(AmountError::Overflow)
+        );
+        assert_eq!(
+            Amount::from_whole(1, MAX_DECIMALS + 1),
+            Err(AmountError::TooManyDecimals(MAX_DECIMALS))
+        );
+
+        assert_eq!(
+            Amount(u64::MAX).checked_add(Amount(1)),
+            Err(AmountError::Overflow)
+        );
+        assert_eq!(Amount(1).checked_add(Amount(2)), Ok(Amount(3)));
+        assert_eq!(
+            Amount(1).checked_sub(Amount(2)),
+            Err(AmountError::Underflow)
+        );
+        assert_eq!(Amount(2).checked_sub(Amount(2)), Ok(Amount::ZERO));
+    }
+
+    #[test]
+    fn formats_back_to_what_was_parsed() {
+        assert_eq!(Amount(5).to_decimal_string(2), "0.05");
+        assert_eq!(Amount(1250).to_decimal_string(2), "12.50");
+        assert_eq!(Amount(42).to_decimal_string(0), "42");
+        assert_eq!(Amount(7).to_decimal_string(3), "0.007");
+
+        for (value, decimals) in [("0.00", 2), ("12.34", 2), ("1000000.5", 1), ("9", 0)] {
+            let amount = Amount::parse(value, decimals).unwrap();
+            assert_eq!(amount.to_decimal_string(decimals), value);
+            assert_eq!(
+                Amount::parse(&amount.to_decimal_string(decimals), decimals),
+                Ok(amount)
+            );
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Coste de buscar cuentas y transacciones en una App con 100k cuentas.
+//!
+//! `cargo bench --bench lookup`
+
+use coliseum_money::Amount;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lib::storage::{MemoryStorage, State, Storage};
+use lib::{Account, AccountStatus, App, Direction, Transaction};
+
+const ACCOUNTS: usize = 100_000;
+const TRANSACTIONS: usize = 100_000;
+
+/// App con `ACCOUNTS` cuentas y `TRANSACTIONS` transacciones entre ellas
+fn app() -> App {
+    let accounts: Vec<Account> = (0..ACCOUNTS)
+        .map(|n| Account {
+            id: App::create_uuid(),
+            created_time: 0.0,
+            last_login: 0.0,
+            username: format!("user{}", n),
+            balance: Amount::from_units(1000),
+            status: AccountStatus::Active,
+        })
+        .collect();
+    let transactions = (0..TRANSACTIONS)
+        .map(|n| Transaction {
+            id: App::create_uuid(),
+            from_id: accounts[n % ACCOUNTS].id.clone(),
+            to_id: accounts[(n * 7 + 1) % ACCOUNTS].id.clone(),
+            amount: Amount::from_units(1),
+            timestamp: n as f64,
+            node: "127.0.0.1:5000".to_string(),
+            idempotency_key: None,
+            batch_id: None,
+            from_balance: None,
+            to_balance: None,
+        })
+        .collect();
+
+    let mut storage = MemoryStorage::new();
+    storage
+        .snapshot(&State {
+            accounts,
+            transactions,
+            ..State::default()
+        })
+        .unwrap();
+    App::open("127.0.0.1:5000".to_string(), storage).unwrap()
+}
+
+fn lookup(c: &mut Criterion) {
+    let app = app();
+    // La última cuenta es el peor caso para una búsqueda lineal
+    let account = app.accounts().last().unwrap().clone();
+    let transaction = app.transactions().last().unwrap().clone();
+
+    let mut group = c.benchmark_group("lookup_100k");
+    group.bench_function("get_account", |b| {
+        b.iter(|| app.get_account(black_box(&account.id)).unwrap().balance)
+    });
+    group.bench_function("get_account_linear_scan", |b| {
+        b.iter(|| {
+            app.accounts()
+                .iter()
+                .find(|candidate| candidate.id == *black_box(&account.id))
+                .unwrap()
+                .balance
+        })
+    });
+    group.bench_function("get_account_by_username", |b| {
+        b.iter(|| {
+            app.get_account_by_username(black_box(&account.username))
+                .unwrap()
+                .balance
+        })
+    });
+    group.bench_function("get_transaction", |b| {
+        b.iter(|| app.get_transaction(black_box(&transaction.id)).unwrap())
+    });
+    group.bench_function("get_transaction_by_account", |b| {
+        b.iter(|| app.get_transaction_by_account(black_box(&account.id), Direction::Both))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
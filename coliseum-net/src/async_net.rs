@@ -0,0 +1,119 @@
+//! Transporte asíncrono (tokio) del protocolo. Usa el mismo formato de
+//! tramas que el transporte bloqueante, de manera que clientes y servidores
+//! de ambos tipos se pueden comunicar entre sí.
+
+use crate::framing::{decode_header, encode_header, HEADER_SIZE, MAX_FRAME_SIZE};
+use crate::server::Handler;
+use crate::{Request, Response};
+use std::future::Future;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn};
+
+/// Tiempo máximo que se espera a que un cliente envíe su petición
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Escribe `payload` como una única trama
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&encode_header(payload.len())?).await?;
+    writer.write_all(payload).await?;
+    writer.flush().await
+}
+
+/// Lee una trama completa, rechazando las de más de `max_size` bytes
+pub async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_size: usize,
+) -> io::Result<Vec<u8>> {
+    let mut header = [0; HEADER_SIZE];
+    reader.read_exact(&mut header).await?;
+    let mut payload = vec![0; decode_header(header, max_size)?];
+    reader.read_exact(&mut payload).await?;
+    Ok(payload)
+}
+
+impl Request {
+    /// Envia una petición y espera su respuesta sin bloquear el runtime.
+    /// Se espera como mucho `limit` a la conexión, al envío y a la respuesta.
+    pub async fn send_async(&self, limit: Duration) -> io::Result<Response> {
+        let json = serde_json::to_vec(self)?;
+
+        let mut stream = within(limit, TcpStream::connect(&self.target_addr)).await?;
+        within(limit, write_frame(&mut stream, &json)).await?;
+
+        let frame = within(limit, read_frame(&mut stream, MAX_FRAME_SIZE)).await?;
+        let response: Response = serde_json::from_slice(&frame)?;
+        Ok(response)
+    }
+}
+
+/// Espera a `future` durante `limit` como mucho
+async fn within<T>(limit: Duration, future: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    match timeout(limit, future).await {
+        Ok(result) => result,
+        Err(elapsed) => Err(io::Error::new(io::ErrorKind::TimedOut, elapsed)),
+    }
+}
+
+/// Lee la petición de una conexión y envía su respuesta
+async fn handle_stream(handler: Handler, mut stream: TcpStream) {
+    let peer = match stream.peer_addr() {
+        Ok(peer) => peer.to_string(),
+        Err(error) => {
+            warn!("{}", error);
+            return;
+        }
+    };
+
+    let frame = within(
+        READ_TIMEOUT,
+        read_frame(&mut stream, handler.max_frame_size),
+    )
+    .await;
+
+    // Los handlers bloquean la App, así que no se ejecutan en el runtime
+    let response = tokio::task::spawn_blocking(move || match frame {
+        Ok(frame) => Some(handler.handle_request(&frame, &peer)),
+        Err(error) => handler.handle_invalid_frame(error, &peer),
+    })
+    .await;
+
+    if let Ok(Some(response)) = response {
+        let json = serde_json::to_vec(&response).unwrap();
+        if let Err(error) = write_frame(&mut stream, &json).await {
+            warn!("Unable to send response: {}", error);
+        }
+    }
+}
+
+/// Atiende las conexiones de `listener` con `handler` hasta que se complete
+/// `shutdown`. Las conexiones ya aceptadas se terminan de atender.
+pub async fn serve<F>(listener: TcpListener, handler: Handler, shutdown: F)
+where
+    F: Future<Output = ()>,
+{
+    info!("Listening on: {} (async)", &handler.addr);
+
+    let mut connections = JoinSet::new();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    connections.spawn(handle_stream(handler.clone(), stream));
+                }
+                Err(error) => warn!("{}", error),
+            },
+            // Se liberan las conexiones ya terminadas
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+
+    info!("Shutting down, waiting for in-flight requests");
+    while connections.join_next().await.is_some() {}
+}
@@ -0,0 +1,136 @@
+//! Credenciales y sesiones de las cuentas.
+//!
+//! Las contraseñas se guardan como hashes Argon2id en formato PHC, que
+//! incluye la sal y los parámetros con los que se calcularon. Al iniciar
+//! sesión se emite un token que caduca pasado `AuthConfig::session_ttl`.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Longitud mínima de una contraseña
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Parámetros de las contraseñas y sesiones
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthConfig {
+    /// Tiempo que un token es válido desde que se inicia sesión
+    pub session_ttl: Duration,
+    /// Memoria usada para calcular cada hash, en KiB
+    pub memory_cost: u32,
+    /// Número de pasadas sobre la memoria al calcular cada hash
+    pub iterations: u32,
+}
+
+impl Default for AuthConfig {
+    fn default() -> AuthConfig {
+        AuthConfig {
+            session_ttl: Duration::from_secs(3600),
+            memory_cost: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+        }
+    }
+}
+
+impl AuthConfig {
+    /// Calcula el hash de `password` con una sal aleatoria
+    pub fn hash_password(&self, password: &str) -> Result<String, String> {
+        let params = Params::new(self.memory_cost, self.iterations, 1, None)
+            .map_err(|error| error.to_string())?;
+        let salt =
+            SaltString::encode_b64(Uuid::new_v4().as_bytes()).map_err(|error| error.to_string())?;
+
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|error| error.to_string())
+    }
+}
+
+impl AuthConfig {
+    /// Comprueba `password` contra el hash de una cuenta. Sin cuenta se
+    /// comprueba igualmente contra un hash con los mismos parámetros, de
+    /// manera que el tiempo de respuesta no revela qué usuarios existen.
+    pub fn check_password(&self, hash: Option<&str>, password: &str) -> bool {
+        match hash {
+            Some(hash) => verify_password(hash, password),
+            None => {
+                if let Some(dummy) = self.dummy_hash() {
+                    verify_password(&dummy, password);
+                }
+                false
+            }
+        }
+    }
+
+    /// Hash de una contraseña cualquiera con los parámetros de la
+    /// configuración, que se calcula una vez por cada combinación
+    fn dummy_hash(&self) -> Option<String> {
+        static HASHES: Mutex<Vec<((u32, u32), String)>> = Mutex::new(Vec::new());
+        let params = (self.memory_cost, self.iterations);
+
+        if let Some((_, hash)) = HASHES
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(key, _)| *key == params)
+        {
+            return Some(hash.clone());
+        }
+        let hash = self.hash_password("coliseum dummy password").ok()?;
+        HASHES.lock().unwrap().push((params, hash.clone()));
+        Some(hash)
+    }
+}
+
+/// Comprueba `password` contra un hash de [`AuthConfig::hash_password`]
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Sesión iniciada por una cuenta
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Session {
+    pub token: String,
+    pub account_id: String,
+    /// Timestamp UNIX en segundos a partir del que el token deja de ser válido
+    pub expires_at: f64,
+}
+
+/// Motivos por los que se rechaza un inicio de sesión o un token
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    /// El usuario no existe o la contraseña no es correcta
+    InvalidCredentials,
+    /// El token no corresponde a ninguna sesión
+    InvalidToken,
+    /// La sesión del token ha caducado
+    SessionExpired,
+    /// La sesión es de una cuenta distinta de la que se quiere usar
+    Forbidden,
+    /// No se ha podido guardar el inicio de sesión
+    Storage(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::InvalidCredentials => write!(f, "Invalid username or password"),
+            AuthError::InvalidToken => write!(f, "Invalid session token"),
+            AuthError::SessionExpired => write!(f, "Session expired, login again"),
+            AuthError::Forbidden => write!(f, "Session does not belong to the account"),
+            AuthError::Storage(error) => write!(f, "Unable to store login: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
@@ -1,23 +1,820 @@
+//! Cliente de línea de comandos del servidor.
+//!
+//! Cada subcomando envía una petición a un endpoint y muestra la respuesta
+//! como tabla o como JSON. El proceso termina con código 1 si el servidor
+//! responde con un error, con código 2 si no se puede leer la contraseña y
+//! con código 3 si no se puede conectar con él.
+//! `repl` abre una sesión interactiva que recuerda el token del último login.
+
 extern crate lib;
-use lib::{CreateAccountData, Request};
 
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use coliseum_money::{Amount, DEFAULT_DECIMALS};
+use lib::client::Client;
+use lib::statement::StatementQuery;
+use lib::{Direction, Order, TransactionQuery, TransferLeg};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process;
+use std::time::Duration;
 
+/// El servidor ha respondido con un error
+const EXIT_ERROR_RESPONSE: i32 = 1;
+/// No se ha podido leer la contraseña
+const EXIT_INPUT: i32 = 2;
+/// No se ha podido enviar la petición o leer la respuesta
+const EXIT_CONNECTION: i32 = 3;
 
-fn main() {
-    /* Create a new account */
-    let data = CreateAccountData {
-        username: "Usuario1".to_string(),
+#[derive(Parser, Debug)]
+#[command(name = "client", about = "Coliseum command line client")]
+struct Cli {
+    #[command(flatten)]
+    options: Options,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Comando escrito en el REPL, que usa las opciones con las que se abrió
+#[derive(Parser, Debug)]
+#[command(name = "", no_binary_name = true)]
+struct Line {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args, Debug)]
+struct Options {
+    /// Address of the server
+    #[arg(
+        long,
+        global = true,
+        env = "COLISEUM_SERVER",
+        default_value = "127.0.0.1:5000"
+    )]
+    server: String,
+
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = Output::Table)]
+    output: Output,
+
+    /// Seconds to wait for the server to accept, read or answer a request
+    #[arg(long, global = true, default_value_t = lib::DEFAULT_TIMEOUT.as_secs())]
+    timeout: u64,
+
+    /// Decimals used to display amounts in tables
+    #[arg(long, global = true, default_value_t = DEFAULT_DECIMALS)]
+    decimals: u32,
+
+    /// PEM bundle with the CAs used to verify the server over TLS
+    #[cfg(feature = "tls")]
+    #[arg(long, global = true, env = "COLISEUM_TLS_CA")]
+    tls_ca: Option<std::path::PathBuf>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum Output {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create a new account
+    CreateAccount {
+        username: String,
+        /// Password, asked without echo when missing. The flag is kept in the
+        /// shell history and shown in the process list
+        #[arg(long, env = "COLISEUM_PASSWORD")]
+        password: Option<String>,
+    },
+    /// Login and print the session token
+    Login {
+        username: String,
+        /// Password, asked without echo when missing. The flag is kept in the
+        /// shell history and shown in the process list
+        #[arg(long, env = "COLISEUM_PASSWORD")]
+        password: Option<String>,
+    },
+    /// Show an account
+    GetAccount { account_id: String },
+    /// Transfer an amount between two accounts
+    Transfer {
+        from_id: String,
+        to_id: String,
+        amount: String,
+        #[command(flatten)]
+        session: SessionArgs,
+        /// Key to retry the transfer without moving the funds twice
+        #[arg(long)]
+        idempotency_key: Option<String>,
+    },
+    /// Transfer funds to several accounts at once, all or nothing
+    TransferBatch {
+        from_id: String,
+        /// Transfers as <to_id>=<amount>
+        #[arg(required = true, value_parser = parse_leg)]
+        legs: Vec<TransferLeg>,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Schedule a transfer once or on a recurring interval
+    ScheduleTransfer {
+        from_id: String,
+        to_id: String,
+        amount: String,
+        /// UNIX timestamp of the first run
+        #[arg(long)]
+        at: Option<f64>,
+        /// Seconds between runs of a recurring transfer
+        #[arg(long)]
+        every: Option<u64>,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// List the scheduled transfers of an account
+    ScheduledTransfers {
+        account_id: String,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Cancel a scheduled transfer
+    CancelScheduledTransfer {
+        schedule_id: String,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Show the balance history of an account
+    Statement {
+        account_id: String,
+        /// Start of the statement, as a UNIX timestamp
+        #[arg(long)]
+        since: Option<f64>,
+        /// End of the statement, as a UNIX timestamp
+        #[arg(long)]
+        until: Option<f64>,
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        #[arg(long, default_value_t = lib::MAX_PAGE_SIZE)]
+        limit: usize,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// List every transaction
+    ListTransactions {
+        #[command(flatten)]
+        query: QueryArgs,
+    },
+    /// Show a transaction
+    GetTransaction { transaction_id: String },
+    /// List the transactions of an account
+    AccountTransactions {
+        account_id: String,
+        /// sent, received or both
+        #[arg(long, value_parser = parse_value::<Direction>, default_value = "both")]
+        direction: Direction,
+        #[command(flatten)]
+        query: QueryArgs,
+    },
+    /// Change the username of an account
+    RenameAccount {
+        account_id: String,
+        username: String,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Freeze an account so it cannot send or receive funds
+    FreezeAccount {
+        account_id: String,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Unfreeze a frozen account
+    UnfreezeAccount {
+        account_id: String,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Close an account for good
+    CloseAccount {
+        account_id: String,
+        /// Account that receives the remaining balance
+        #[arg(long)]
+        sweep_to: Option<String>,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Start an interactive session
+    Repl,
+}
+
+#[derive(Args, Debug)]
+struct SessionArgs {
+    /// Session token of the account. In the REPL it defaults to the token of
+    /// the last login
+    #[arg(long, env = "COLISEUM_TOKEN")]
+    token: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct QueryArgs {
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+    #[arg(long, default_value_t = lib::MAX_PAGE_SIZE)]
+    limit: usize,
+    /// Only transactions from this UNIX timestamp
+    #[arg(long)]
+    since: Option<f64>,
+    /// Only transactions before this UNIX timestamp
+    #[arg(long)]
+    until: Option<f64>,
+    /// ascending or descending
+    #[arg(long, value_parser = parse_value::<Order>, default_value = "ascending")]
+    order: Order,
+}
+
+impl QueryArgs {
+    fn into_query(self, direction: Direction) -> TransactionQuery {
+        TransactionQuery {
+            offset: self.offset,
+            limit: self.limit,
+            since: self.since,
+            until: self.until,
+            direction,
+            order: self.order,
+        }
+    }
+}
+
+/// Lee un valor con el mismo nombre que tiene en el protocolo
+fn parse_value<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(Value::String(value.to_string())).map_err(|error| error.to_string())
+}
+
+/// Lee una transferencia de un lote escrita como `<to_id>=<amount>`
+fn parse_leg(value: &str) -> Result<TransferLeg, String> {
+    match value.split_once('=') {
+        Some((to_id, amount)) if !to_id.is_empty() && !amount.is_empty() => Ok(TransferLeg {
+            to_id: to_id.to_string(),
+            amount: amount.to_string(),
+        }),
+        _ => Err(format!("expected <to_id>=<amount>, got {}", value)),
+    }
+}
+
+/// Motivo por el que falla un comando
+enum Failure {
+    Response,
+    Input,
+    Connection,
+}
+
+impl Failure {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Failure::Response => EXIT_ERROR_RESPONSE,
+            Failure::Input => EXIT_INPUT,
+            Failure::Connection => EXIT_CONNECTION,
+        }
+    }
+}
+
+/// Contraseña de `--password` o `COLISEUM_PASSWORD`. Sin ninguna de las dos
+/// se pide por la terminal sin mostrarla, o se lee una línea de la entrada
+/// estándar si no es una terminal.
+fn read_password(password: Option<String>) -> Result<String, Failure> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    prompt_password().map_err(|error| {
+        eprintln!("Unable to read the password: {}", error);
+        Failure::Input
+    })
+}
+
+fn prompt_password() -> io::Result<String> {
+    let stdin = io::stdin();
+    let hidden = if stdin.is_terminal() {
+        eprint!("Password: ");
+        Some(HiddenInput::new()?)
+    } else {
+        None
     };
 
-    let json = serde_json::to_string(&data).unwrap();
-    let request = Request {
-        endpoint:"CreateAccount".to_string(),
-        origin_addr: "localhost".to_string(),
-        target_addr: "127.0.0.1:5000".to_string(),
-        data: json,
+    let mut line = String::new();
+    if stdin.lock().read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "no password given",
+        ));
+    }
+    if hidden.is_some() {
+        // El salto de línea tampoco se ha mostrado
+        eprintln!();
+    }
+    Ok(line.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// Desactiva el eco de la terminal mientras existe
+#[cfg(unix)]
+struct HiddenInput(libc::termios);
+
+#[cfg(unix)]
+impl HiddenInput {
+    fn new() -> io::Result<HiddenInput> {
+        // SAFETY: termios es una estructura de C sin punteros, que rellena
+        // tcgetattr
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut hidden = original;
+        hidden.c_lflag &= !libc::ECHO;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &hidden) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(HiddenInput(original))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for HiddenInput {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0) };
+    }
+}
+
+/// Fuera de Unix la contraseña se muestra al escribirla
+#[cfg(not(unix))]
+struct HiddenInput;
+
+#[cfg(not(unix))]
+impl HiddenInput {
+    fn new() -> io::Result<HiddenInput> {
+        Ok(HiddenInput)
+    }
+}
+
+struct Session {
+    client: Client,
+    options: Options,
+    /// Token del último login, usado por `transfer` en el REPL
+    token: Option<String>,
+}
+
+impl Session {
+    fn new(options: Options) -> Session {
+        let client = Client::new("localhost", &options.server)
+            .with_timeout(Duration::from_secs(options.timeout));
+
+        // TLS verificando el servidor con las CAs de --tls-ca
+        #[cfg(feature = "tls")]
+        let client = match &options.tls_ca {
+            Some(ca) => match lib::tls::client_config(ca) {
+                Ok(config) => client.with_tls(config),
+                Err(error) => {
+                    eprintln!("Invalid CA bundle {}: {}", ca.display(), error);
+                    process::exit(EXIT_CONNECTION);
+                }
+            },
+            None => client,
+        };
+
+        Session {
+            client,
+            options,
+            token: None,
+        }
+    }
+
+    fn run(&mut self, command: Command) -> Result<(), Failure> {
+        let response = match command {
+            Command::CreateAccount { username, password } => {
+                let password = read_password(password)?;
+                self.client.create_account(&username, &password)
+            }
+            Command::Login { username, password } => {
+                let password = read_password(password)?;
+                self.client.login(&username, &password)
+            }
+            Command::GetAccount { account_id } => self.client.get_account(&account_id),
+            Command::Transfer {
+                from_id,
+                to_id,
+                amount,
+                session,
+                idempotency_key,
+            } => {
+                let token = self.session_token(session);
+                match idempotency_key {
+                    Some(key) => self
+                        .client
+                        .create_idempotent_transaction(&token, &from_id, &to_id, &amount, &key),
+                    None => self
+                        .client
+                        .create_transaction(&token, &from_id, &to_id, &amount),
+                }
+            }
+            Command::TransferBatch {
+                from_id,
+                legs,
+                session,
+            } => {
+                let token = self.session_token(session);
+                self.client.create_batch_transaction(&token, &from_id, legs)
+            }
+            Command::ScheduleTransfer {
+                from_id,
+                to_id,
+                amount,
+                at,
+                every,
+                session,
+            } => {
+                let token = self.session_token(session);
+                self.client
+                    .schedule_transfer(&token, &from_id, &to_id, &amount, at, every)
+            }
+            Command::ScheduledTransfers {
+                account_id,
+                session,
+            } => {
+                let token = self.session_token(session);
+                self.client.list_scheduled_transfers(&token, &account_id)
+            }
+            Command::CancelScheduledTransfer {
+                schedule_id,
+                session,
+            } => {
+                let token = self.session_token(session);
+                self.client.cancel_scheduled_transfer(&token, &schedule_id)
+            }
+            Command::Statement {
+                account_id,
+                since,
+                until,
+                offset,
+                limit,
+                session,
+            } => {
+                let token = self.session_token(session);
+                let query = StatementQuery {
+                    since,
+                    until,
+                    offset,
+                    limit,
+                };
+                self.client.get_statement(&token, &account_id, query)
+            }
+            Command::ListTransactions { query } => self
+                .client
+                .list_transactions(query.into_query(Direction::Both)),
+            Command::GetTransaction { transaction_id } => {
+                self.client.get_transaction(&transaction_id)
+            }
+            Command::AccountTransactions {
+                account_id,
+                direction,
+                query,
+            } => self
+                .client
+                .get_account_transactions(&account_id, query.into_query(direction)),
+            Command::RenameAccount {
+                account_id,
+                username,
+                session,
+            } => {
+                let token = self.session_token(session);
+                self.client.rename_account(&token, &account_id, &username)
+            }
+            Command::FreezeAccount {
+                account_id,
+                session,
+            } => {
+                let token = self.session_token(session);
+                self.client.freeze_account(&token, &account_id)
+            }
+            Command::UnfreezeAccount {
+                account_id,
+                session,
+            } => {
+                let token = self.session_token(session);
+                self.client.unfreeze_account(&token, &account_id)
+            }
+            Command::CloseAccount {
+                account_id,
+                sweep_to,
+                session,
+            } => {
+                let token = self.session_token(session);
+                self.client
+                    .close_account(&token, &account_id, sweep_to.as_deref())
+            }
+            Command::Repl => {
+                self.repl();
+                return Ok(());
+            }
+        };
+
+        let response = response.map_err(|error| {
+            eprintln!("Unable to reach {}: {}", self.options.server, error);
+            Failure::Connection
+        })?;
+
+        match response.into_result() {
+            Ok(data) => {
+                let value: Value = serde_json::from_str(&data).unwrap_or(Value::String(data));
+                if let Some(token) = value.get("token").and_then(Value::as_str) {
+                    self.token = Some(token.to_string());
+                }
+                self.print(&value);
+                Ok(())
+            }
+            Err(error) => {
+                match self.options.output {
+                    Output::Json => eprintln!("{}", serde_json::to_string(&error).unwrap()),
+                    Output::Table | Output::Csv => {
+                        eprintln!("error ({:?}): {}", error.code, error)
+                    }
+                }
+                Err(Failure::Response)
+            }
+        }
+    }
+
+    /// Token indicado en el comando o, si no hay, el del último login
+    fn session_token(&self, session: SessionArgs) -> String {
+        session
+            .token
+            .or_else(|| self.token.clone())
+            .unwrap_or_default()
+    }
+
+    fn print(&self, value: &Value) {
+        match self.options.output {
+            Output::Json => println!("{}", serde_json::to_string_pretty(value).unwrap()),
+            Output::Table => print!("{}", render(value, self.options.decimals)),
+            Output::Csv => print!("{}", render_csv(value, self.options.decimals)),
+        }
+    }
+
+    /// Lee comandos de la entrada estándar hasta `exit` o el final de la entrada
+    fn repl(&mut self) {
+        let stdin = io::stdin();
+        let mut line = String::new();
+
+        loop {
+            print!("coliseum> ");
+            let _ = io::stdout().flush();
+
+            line.clear();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(error) => {
+                    eprintln!("{}", error);
+                    break;
+                }
+            }
+
+            let words = match split_words(&line) {
+                Ok(words) => words,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    continue;
+                }
+            };
+            match words.first().map(String::as_str) {
+                None => continue,
+                Some("exit" | "quit") => break,
+                Some("repl") => {
+                    eprintln!("Already in the REPL");
+                    continue;
+                }
+                Some(_) => {}
+            }
+
+            match Line::try_parse_from(words) {
+                Ok(line) => {
+                    let _ = self.run(line.command);
+                }
+                Err(error) => {
+                    let _ = error.print();
+                }
+            }
+        }
+    }
+}
+
+/// Separa una línea en palabras, respetando las comillas simples y dobles
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err("Unterminated quote".to_string());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/* Tablas */
+
+/// Muestra los campos simples de un objeto como `campo  valor`, sus listas
+/// de objetos, como las transacciones de una página, como tablas y los
+/// objetos anidados a continuación con su nombre
+fn render(value: &Value, decimals: u32) -> String {
+    match value {
+        Value::Object(fields) => {
+            let rows: Vec<Vec<String>> = fields
+                .iter()
+                .filter(|(_, value)| !value.is_array() && !value.is_object())
+                .map(|(key, value)| vec![key.clone(), cell(key, value, decimals)])
+                .collect();
+            let mut output = table(None, &rows);
+
+            for (key, value) in fields {
+                match value {
+                    Value::Array(items) => {
+                        output.push('\n');
+                        output.push_str(&render_list(items, decimals));
+                        if items.is_empty() {
+                            output.push_str(&format!("(no {})\n", key));
+                        }
+                    }
+                    Value::Object(_) => {
+                        output.push_str(&format!("\n{}\n", key));
+                        output.push_str(&render(value, decimals));
+                    }
+                    _ => {}
+                }
+            }
+            output
+        }
+        Value::Array(items) => render_list(items, decimals),
+        value => format!("{}\n", cell("", value, decimals)),
+    }
+}
+
+fn render_list(items: &[Value], decimals: u32) -> String {
+    match list_rows(items, decimals) {
+        Some((columns, rows)) => table(Some(&columns), &rows),
+        None => String::new(),
+    }
+}
+
+/// Columnas y filas de una lista de objetos, o `None` si está vacía
+fn list_rows(items: &[Value], decimals: u32) -> Option<(Vec<String>, Vec<Vec<String>>)> {
+    let columns: Vec<String> = match items.first()? {
+        Value::Object(fields) => fields.keys().cloned().collect(),
+        _ => vec!["value".to_string()],
     };
 
-    let response = request.send();
-    print!("{}", response.data);
+    let rows: Vec<Vec<String>> = items
+        .iter()
+        .map(|item| match item {
+            Value::Object(fields) => columns
+                .iter()
+                .map(|key| {
+                    fields
+                        .get(key)
+                        .map_or(String::new(), |v| cell(key, v, decimals))
+                })
+                .collect(),
+            item => vec![cell("", item, decimals)],
+        })
+        .collect();
+    Some((columns, rows))
+}
+
+/// Muestra una respuesta como CSV: los campos simples de un objeto como filas
+/// `campo,valor` y cada lista como una tabla con cabecera, separadas por una
+/// línea en blanco
+fn render_csv(value: &Value, decimals: u32) -> String {
+    match value {
+        Value::Object(fields) => {
+            let rows: Vec<Vec<String>> = fields
+                .iter()
+                .filter(|(_, value)| !value.is_array() && !value.is_object())
+                .map(|(key, value)| vec![key.clone(), cell(key, value, decimals)])
+                .collect();
+            let mut sections = vec![csv(None, &rows)];
+
+            for value in fields.values() {
+                if value.is_array() || value.is_object() {
+                    sections.push(render_csv(value, decimals));
+                }
+            }
+            sections.retain(|section| !section.is_empty());
+            sections.join("\n")
+        }
+        Value::Array(items) => match list_rows(items, decimals) {
+            Some((columns, rows)) => csv(Some(&columns), &rows),
+            None => String::new(),
+        },
+        value => csv(None, &[vec![cell("", value, decimals)]]),
+    }
+}
+
+/// Texto de un valor en una tabla. Los importes se muestran con decimales y
+/// los timestamps como fechas
+fn cell(key: &str, value: &Value, decimals: u32) -> String {
+    match (key, value) {
+        (
+            "balance" | "amount" | "opening_balance" | "closing_balance" | "from_balance"
+            | "to_balance",
+            Value::Number(units),
+        ) if units.is_u64() => {
+            Amount::from_units(units.as_u64().unwrap()).to_decimal_string(decimals)
+        }
+        (
+            "timestamp" | "created_time" | "last_login" | "expires_at" | "next_run" | "since"
+            | "until",
+            Value::Number(seconds),
+        ) => {
+            let seconds = seconds.as_f64().unwrap_or_default();
+            match chrono::DateTime::from_timestamp(seconds as i64, 0) {
+                Some(date) => date.format("%Y-%m-%d %H:%M:%S").to_string(),
+                None => seconds.to_string(),
+            }
+        }
+        (_, Value::String(text)) => text.clone(),
+        (_, Value::Null) => String::new(),
+        (_, value) => value.to_string(),
+    }
+}
+
+fn csv(header: Option<&[String]>, rows: &[Vec<String>]) -> String {
+    let line = |row: &[String]| {
+        let fields: Vec<String> = row.iter().map(|text| csv_field(text)).collect();
+        format!("{}\n", fields.join(","))
+    };
+    header
+        .into_iter()
+        .chain(rows.iter().map(Vec::as_slice))
+        .map(line)
+        .collect()
+}
+
+/// Campo de CSV, entre comillas si contiene separadores o comillas
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn table(header: Option<&[String]>, rows: &[Vec<String>]) -> String {
+    let columns = header.map_or_else(|| rows.first().map_or(0, Vec::len), <[String]>::len);
+    let mut widths = vec![0; columns];
+    for row in header.into_iter().chain(rows.iter().map(Vec::as_slice)) {
+        for (width, text) in widths.iter_mut().zip(row) {
+            *width = (*width).max(text.chars().count());
+        }
+    }
+
+    let line = |row: &[String]| {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(text, width)| format!("{:<width$}", text, width = width))
+            .collect();
+        format!("{}\n", cells.join("  ").trim_end())
+    };
+
+    let mut output = String::new();
+    if let Some(header) = header {
+        let header: Vec<String> = header.iter().map(|name| name.to_uppercase()).collect();
+        output.push_str(&line(&header));
+    }
+    for row in rows {
+        output.push_str(&line(row));
+    }
+    output
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let mut session = Session::new(cli.options);
 
+    if let Err(failure) = session.run(cli.command) {
+        process::exit(failure.exit_code());
+    }
 }
@@ -1,216 +1,188 @@
 extern crate lib;
 
-use chrono::Utc;
-use lib::{App, CreateAccountData, CreateTransactionData, GetAccountData, Request, Response};
-use std::io::{BufReader, Read};
-use std::net::{TcpListener, TcpStream};
-
-struct Server {
-    addr: String,
-    app: App,
+use clap::Parser;
+use lib::config::{LogFormat, LogLevel, ServerConfig};
+use lib::scheduler::Scheduler;
+use lib::server::{Handler, Server};
+use lib::storage::DiskStorage;
+use lib::App;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process;
+use tracing::{error, info};
+
+/// Fichero de configuración que se usa si existe y no se indica otro
+const DEFAULT_CONFIG_FILE: &str = "coliseum.toml";
+
+/// Servidor de Coliseum. Cada opción sustituye al valor del fichero de
+/// configuración.
+#[derive(Parser, Debug)]
+#[command(name = "server", about = "Coliseum server")]
+struct Cli {
+    /// TOML configuration file, coliseum.toml by default if it exists
+    #[arg(long, env = "COLISEUM_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Address to listen on
+    #[arg(long, env = "COLISEUM_ADDR")]
+    addr: Option<String>,
+
+    /// Number of worker threads
+    #[arg(long, env = "COLISEUM_WORKERS")]
+    workers: Option<usize>,
+
+    /// Balance of new accounts
+    #[arg(long, env = "COLISEUM_STARTING_BALANCE")]
+    starting_balance: Option<String>,
+
+    /// Directory with the stored data
+    #[arg(long, env = "COLISEUM_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+
+    /// Maximum size of a request, in bytes
+    #[arg(long, env = "COLISEUM_MAX_FRAME_SIZE")]
+    max_frame_size: Option<usize>,
+
+    /// Seconds during which idempotency keys are remembered
+    #[arg(long, env = "COLISEUM_IDEMPOTENCY_RETENTION_SECS")]
+    idempotency_retention_secs: Option<u64>,
+
+    /// Milliseconds between checks for due scheduled transfers
+    #[arg(long, env = "COLISEUM_SCHEDULER_INTERVAL_MS")]
+    scheduler_interval_ms: Option<u64>,
+
+    /// error, warn, info, debug or trace
+    #[arg(long, env = "COLISEUM_LOG_LEVEL")]
+    log_level: Option<LogLevel>,
+
+    /// text or json
+    #[arg(long, env = "COLISEUM_LOG_FORMAT")]
+    log_format: Option<LogFormat>,
+
+    /// PEM certificate used for TLS
+    #[arg(long, env = "COLISEUM_TLS_CERT")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key used for TLS
+    #[arg(long, env = "COLISEUM_TLS_KEY")]
+    tls_key: Option<PathBuf>,
 }
 
-impl Server {
-    /// Gestiona una conexión entrante para la creación de una cuenta
-    fn create_account(&mut self, request: Request, mut stream: TcpStream) {
-        let data: Result<CreateAccountData, serde_json::Error> =
-            serde_json::from_str(&request.data);
-        match data {
-            Err(_) => {
-                let response = Response {
-                    origin_addr: self.addr.clone().to_string(),
-                    target_addr: stream.peer_addr().unwrap().to_string(),
-                    data: "Request Data is not valid".to_string(),
-                    status: 401,
-                };
-                response.send(&mut stream);
+impl Cli {
+    /// Lee el fichero de configuración y le aplica las opciones
+    fn config(self) -> Result<ServerConfig, lib::config::ConfigError> {
+        let mut config = match self.config {
+            Some(path) => ServerConfig::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                ServerConfig::from_file(Path::new(DEFAULT_CONFIG_FILE))?
             }
+            None => ServerConfig::default(),
+        };
 
-            Ok(data) => {
-                let account = self.app.create_account(data.username);
-
-                match account {
-                    Ok(data) => {
-                        let response = Response {
-                            origin_addr: self.addr.clone().to_string(),
-                            target_addr: stream.peer_addr().unwrap().to_string(),
-                            data: data,
-                            status: 200,
-                        };
-                        response.send(&mut stream);
-                    }
-
-                    // Internal server error
-                    Err(error) => {
-                        let response = Response {
-                            origin_addr: self.addr.clone().to_string(),
-                            target_addr: stream.peer_addr().unwrap().to_string(),
-                            data: error,
-                            status: 500,
-                        };
-                        response.send(&mut stream);
-                    }
-                }
-            }
+        if let Some(addr) = self.addr {
+            config.addr = addr;
         }
-    }
-
-    /// Gestiona una conexión entrante para la obtención de una cuenta
-    fn get_account(&mut self, request: Request, mut stream: TcpStream) {
-        let data: Result<GetAccountData, serde_json::Error> = serde_json::from_str(&request.data);
-
-        match data {
-            Err(_) => {
-                let response = Response {
-                    origin_addr: self.addr.clone().to_string(),
-                    target_addr: stream.peer_addr().unwrap().to_string(),
-                    data: "Request Data is not valid".to_string(),
-                    status: 401,
-                };
-                response.send(&mut stream);
-            }
-
-            Ok(data) => {
-                let account = self.app.get_account(&data.account_id);
-                match account {
-                    Err(_) => {
-                        let response = Response {
-                            origin_addr: self.addr.clone().to_string(),
-                            target_addr: stream.peer_addr().unwrap().to_string(),
-                            data: format!("Account with ID: {} not found", data.account_id),
-                            status: 402,
-                        };
-                        response.send(&mut stream);
-                    }
-
-                    Ok(account) => {
-                        let response = Response {
-                            origin_addr: self.addr.clone().to_string(),
-                            target_addr: stream.peer_addr().unwrap().to_string(),
-                            data: serde_json::to_string(account).unwrap(),
-                            status: 200,
-                        };
-                        response.send(&mut stream);
-                    }
-                }
-            }
+        if let Some(workers) = self.workers {
+            config.workers = workers;
         }
-    }
-
-    /// Gestiona una conexión entrante para la creación de una transacción
-    fn create_transaction(&mut self, request: Request, mut stream: TcpStream) {
-        
-        let data: Result<CreateTransactionData, serde_json::Error> =
-            serde_json::from_str(&request.data);
-
-        match data {
-            Err(_) => {
-                let response = Response {
-                    origin_addr: self.addr.clone().to_string(),
-                    target_addr: stream.peer_addr().unwrap().to_string(),
-                    data: "Request Data is not valid".to_string(),
-                    status: 401,
-                };
-                response.send(&mut stream);
-            }
-
-            Ok(data) => {
-                let transaction =
-                    self.app
-                        .create_transaction(data.from_id, data.to_id, data.amount);
-
-                let response = Response {
-                    origin_addr: self.addr.clone().to_string(),
-                    target_addr: stream.peer_addr().unwrap().to_string(),
-                    data: serde_json::to_string(&transaction).unwrap(),
-                    status: 200,
-                };
-                response.send(&mut stream);
-            }
+        if let Some(starting_balance) = self.starting_balance {
+            config.starting_balance = starting_balance;
         }
-    }
-
-    fn handle_incorrect_endpoint(&mut self, request: Request, mut stream: TcpStream) {
-        let response = Response {
-            origin_addr: self.addr.clone().to_string(),
-            target_addr: stream.peer_addr().unwrap().to_string(),
-            data: format!("{} endpoint not found", request.endpoint),
-            status: 400,
-        };
+        if let Some(data_dir) = self.data_dir {
+            config.data_dir = data_dir;
+        }
+        if let Some(max_frame_size) = self.max_frame_size {
+            config.max_frame_size = max_frame_size;
+        }
+        if let Some(idempotency_retention_secs) = self.idempotency_retention_secs {
+            config.idempotency_retention_secs = idempotency_retention_secs;
+        }
+        if let Some(scheduler_interval_ms) = self.scheduler_interval_ms {
+            config.scheduler_interval_ms = scheduler_interval_ms;
+        }
+        if let Some(log_level) = self.log_level {
+            config.log_level = log_level;
+        }
+        if let Some(log_format) = self.log_format {
+            config.log_format = log_format;
+        }
+        config.tls_cert = self.tls_cert.or(config.tls_cert);
+        config.tls_key = self.tls_key.or(config.tls_key);
 
-        response.send(&mut stream);
+        config.validate()?;
+        Ok(config)
     }
+}
 
-    /// Gestiona la conexiones entrantes según el endpoint
-    fn handle_connection(&mut self, connection: String, mut stream: TcpStream) {
-        let request: Result<Request, serde_json::Error> = serde_json::from_str(&connection);
-
-        match request {
-            Err(_) => {
-                println!(
-                    "{} - {} - {}",
-                    Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-                    stream.peer_addr().unwrap().to_string(),
-                    "Request does not satisfied protocol"
-                );
-
-                let response = Response {
-                    origin_addr: self.addr.clone().to_string(),
-                    target_addr: stream.peer_addr().unwrap().to_string(),
-                    data: "Request is not valid".to_string(),
-                    status: 405,
-                };
-                response.send(&mut stream);
-            }
-
-            Ok(request) => {
-                println!(
-                    "{} - {} - {}",
-                    Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-                    request.origin_addr,
-                    request.endpoint
-                );
-
-                // El servidor actua según el endpoint dentro de la Request
-                if request.endpoint == "GetAccount" {
-                    self.get_account(request, stream);
-                } else if request.endpoint == "CreateAccount" {
-                    self.create_account(request, stream);
-                } else if request.endpoint == "CreateTransaction" {
-                    self.create_transaction(request, stream);
-                } else {
-                    self.handle_incorrect_endpoint(request, stream);
-                }
-            }
+fn main() {
+    let config = match Cli::parse().config() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(2);
         }
+    };
+
+    // Los campos de cada petición (ID, cliente, endpoint, estado y latencia)
+    // van en el span `request` y en el mensaje con el que termina
+    let logger = tracing_subscriber::fmt().with_max_level(tracing::Level::from(config.log_level));
+    match config.log_format {
+        LogFormat::Text => logger.init(),
+        LogFormat::Json => logger
+            .json()
+            .flatten_event(true)
+            .with_span_list(false)
+            .init(),
     }
 
-    fn run(&mut self) {
-        println!("Listening on: {}", &self.addr);
-
-        match TcpListener::bind(&self.addr){
-            Err(_) => {
-                println!("Unavaliable to bind to {}", &self.addr);
-            }
-            Ok(listener)=>{
-                for stream in listener.incoming() {
-                    // Procesamiento de las conexiones
-                    let mut stream = stream.unwrap();
-                    let mut buf = [0; 1024];
-                    let mut reader = BufReader::new(&mut stream);
-                    let n = reader.read(&mut buf).unwrap();
-                    let connection = String::from_utf8_lossy(&buf[..n]).to_string();
-                    // Se transforma el str a una estructura Request para procesar
-                    self.handle_connection(connection, stream);
-                }
-            }
-        };
+    if let Err(error) = run(config) {
+        error!("{}", error);
+        process::exit(1);
     }
 }
 
-fn main() {
-    let addr = "127.0.0.1:5000".to_string();
-    let app = App::new(addr.clone());
-    let mut server = Server { addr, app };
-    server.run();
+fn run(config: ServerConfig) -> Result<(), String> {
+    let listener = TcpListener::bind(&config.addr)
+        .map_err(|error| format!("Unable to bind to {}: {}", &config.addr, error))?;
+
+    let mut app = DiskStorage::open(&config.data_dir)
+        .and_then(|storage| App::open(config.addr.clone(), storage))
+        .map_err(|error| {
+            format!(
+                "Unable to open data in {}: {}",
+                config.data_dir.display(),
+                error
+            )
+        })?;
+    app.starting_balance = config
+        .starting_balance()
+        .map_err(|error| error.to_string())?;
+    app.idempotency_retention = config.idempotency_retention();
+    info!(
+        "Opened {} with {} accounts",
+        config.data_dir.display(),
+        app.accounts().len()
+    );
+
+    let mut handler = Handler::new(config.addr.clone(), app);
+    let app = handler.app.clone();
+    handler.max_frame_size = config.max_frame_size;
+    let server = Server::new(handler, config.workers);
+
+    // TLS con el certificado y la clave de la configuración
+    #[cfg(feature = "tls")]
+    let server = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => lib::tls::server_config(cert, key)
+            .map(|tls| server.with_tls(tls))
+            .map_err(|error| format!("Unable to load TLS certificate: {}", error))?,
+        _ => server,
+    };
+
+    let handle = server.clone();
+    ctrlc::set_handler(move || handle.stop()).expect("Unable to handle Ctrl-C");
+
+    let scheduler = Scheduler::spawn(app, config.scheduler_interval());
+    server.run(listener);
+    scheduler.stop();
+    Ok(())
 }
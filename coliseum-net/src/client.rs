@@ -0,0 +1,267 @@
+//! Funciones de ayuda para hacer peticiones a un servidor.
+
+use crate::statement::StatementQuery;
+use crate::{
+    CancelScheduledTransferData, CloseAccountData, CreateAccountData, CreateBatchTransactionData,
+    CreateTransactionData, FreezeAccountData, GetAccountData, GetAccountTransactionsData,
+    GetStatementData, GetTransactionData, ListScheduledTransfersData, ListTransactionsData,
+    LoginData, RenameAccountData, Request, RequestBody, Response, ScheduleTransferData,
+    TransactionQuery, TransferLeg, UnfreezeAccountData, DEFAULT_TIMEOUT,
+};
+use std::io;
+use std::time::Duration;
+
+/// Cliente de un servidor en `server_addr`
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub origin_addr: String,
+    pub server_addr: String,
+    timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<std::sync::Arc<rustls::ClientConfig>>,
+}
+
+impl Client {
+    pub fn new(origin_addr: &str, server_addr: &str) -> Client {
+        Client {
+            origin_addr: origin_addr.to_string(),
+            server_addr: server_addr.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Cifra las peticiones con TLS, ver [`crate::tls::client_config`]
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: std::sync::Arc<rustls::ClientConfig>) -> Client {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Tiempo máximo para conectar, enviar cada petición y recibir su
+    /// respuesta. Por defecto es [`DEFAULT_TIMEOUT`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Client {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Envia al servidor una petición con `body`
+    pub fn send(&self, body: RequestBody) -> io::Result<Response> {
+        let request = Request::new(self.origin_addr.clone(), self.server_addr.clone(), body);
+
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.tls {
+            return request.send_tls(config.clone(), self.timeout);
+        }
+        request.send(self.timeout)
+    }
+
+    /// Como `send` sin bloquear el runtime de tokio. No usa TLS.
+    #[cfg(feature = "tokio")]
+    pub async fn send_async(&self, body: RequestBody) -> io::Result<Response> {
+        Request::new(self.origin_addr.clone(), self.server_addr.clone(), body)
+            .send_async(self.timeout)
+            .await
+    }
+
+    pub fn create_account(&self, username: &str, password: &str) -> io::Result<Response> {
+        self.send(RequestBody::CreateAccount(CreateAccountData {
+            username: username.to_string(),
+            password: password.to_string(),
+        }))
+    }
+
+    /// Inicia sesión. Los datos de la respuesta son una `Session` con el token
+    pub fn login(&self, username: &str, password: &str) -> io::Result<Response> {
+        self.send(RequestBody::Login(LoginData {
+            username: username.to_string(),
+            password: password.to_string(),
+        }))
+    }
+
+    pub fn get_account(&self, account_id: &str) -> io::Result<Response> {
+        self.send(RequestBody::GetAccount(GetAccountData {
+            account_id: account_id.to_string(),
+        }))
+    }
+
+    pub fn create_transaction(
+        &self,
+        token: &str,
+        from_id: &str,
+        to_id: &str,
+        amount: &str,
+    ) -> io::Result<Response> {
+        self.send(RequestBody::CreateTransaction(CreateTransactionData {
+            token: token.to_string(),
+            from_id: from_id.to_string(),
+            to_id: to_id.to_string(),
+            amount: amount.to_string(),
+            idempotency_key: None,
+        }))
+    }
+
+    /// Crea una transacción que se puede reenviar con la misma
+    /// `idempotency_key` si no llega la respuesta, sin duplicar la transferencia
+    pub fn create_idempotent_transaction(
+        &self,
+        token: &str,
+        from_id: &str,
+        to_id: &str,
+        amount: &str,
+        idempotency_key: &str,
+    ) -> io::Result<Response> {
+        self.send(RequestBody::CreateTransaction(CreateTransactionData {
+            token: token.to_string(),
+            from_id: from_id.to_string(),
+            to_id: to_id.to_string(),
+            amount: amount.to_string(),
+            idempotency_key: Some(idempotency_key.to_string()),
+        }))
+    }
+
+    /// Transfiere desde `from_id` a varias cuentas a la vez: se hacen todas
+    /// las transferencias o ninguna. Los datos de la respuesta son un `Batch`
+    pub fn create_batch_transaction(
+        &self,
+        token: &str,
+        from_id: &str,
+        legs: Vec<TransferLeg>,
+    ) -> io::Result<Response> {
+        self.send(RequestBody::CreateBatchTransaction(
+            CreateBatchTransactionData {
+                token: token.to_string(),
+                from_id: from_id.to_string(),
+                legs,
+            },
+        ))
+    }
+
+    /// Programa una transferencia para `execute_at` o, con `interval_secs`,
+    /// para que se repita. Los datos de la respuesta son un
+    /// `ScheduledTransfer`
+    pub fn schedule_transfer(
+        &self,
+        token: &str,
+        from_id: &str,
+        to_id: &str,
+        amount: &str,
+        execute_at: Option<f64>,
+        interval_secs: Option<u64>,
+    ) -> io::Result<Response> {
+        self.send(RequestBody::ScheduleTransfer(ScheduleTransferData {
+            token: token.to_string(),
+            from_id: from_id.to_string(),
+            to_id: to_id.to_string(),
+            amount: amount.to_string(),
+            execute_at,
+            interval_secs,
+        }))
+    }
+
+    pub fn list_scheduled_transfers(&self, token: &str, account_id: &str) -> io::Result<Response> {
+        self.send(RequestBody::ListScheduledTransfers(
+            ListScheduledTransfersData {
+                token: token.to_string(),
+                account_id: account_id.to_string(),
+            },
+        ))
+    }
+
+    pub fn cancel_scheduled_transfer(
+        &self,
+        token: &str,
+        schedule_id: &str,
+    ) -> io::Result<Response> {
+        self.send(RequestBody::CancelScheduledTransfer(
+            CancelScheduledTransferData {
+                token: token.to_string(),
+                schedule_id: schedule_id.to_string(),
+            },
+        ))
+    }
+
+    /// Extracto de una cuenta, con un token de la cuenta. Los datos de la
+    /// respuesta son un `Statement`
+    pub fn get_statement(
+        &self,
+        token: &str,
+        account_id: &str,
+        query: StatementQuery,
+    ) -> io::Result<Response> {
+        self.send(RequestBody::GetStatement(GetStatementData {
+            token: token.to_string(),
+            account_id: account_id.to_string(),
+            query,
+        }))
+    }
+
+    pub fn list_transactions(&self, query: TransactionQuery) -> io::Result<Response> {
+        self.send(RequestBody::ListTransactions(ListTransactionsData {
+            query,
+        }))
+    }
+
+    pub fn get_transaction(&self, transaction_id: &str) -> io::Result<Response> {
+        self.send(RequestBody::GetTransaction(GetTransactionData {
+            transaction_id: transaction_id.to_string(),
+        }))
+    }
+
+    pub fn rename_account(
+        &self,
+        token: &str,
+        account_id: &str,
+        username: &str,
+    ) -> io::Result<Response> {
+        self.send(RequestBody::RenameAccount(RenameAccountData {
+            token: token.to_string(),
+            account_id: account_id.to_string(),
+            username: username.to_string(),
+        }))
+    }
+
+    pub fn freeze_account(&self, token: &str, account_id: &str) -> io::Result<Response> {
+        self.send(RequestBody::FreezeAccount(FreezeAccountData {
+            token: token.to_string(),
+            account_id: account_id.to_string(),
+        }))
+    }
+
+    pub fn unfreeze_account(&self, token: &str, account_id: &str) -> io::Result<Response> {
+        self.send(RequestBody::UnfreezeAccount(UnfreezeAccountData {
+            token: token.to_string(),
+            account_id: account_id.to_string(),
+        }))
+    }
+
+    /// Cierra una cuenta. Si tiene saldo hay que indicar `sweep_to`, la
+    /// cuenta a la que se transfiere. Los datos de la respuesta son un
+    /// `ClosedAccount`
+    pub fn close_account(
+        &self,
+        token: &str,
+        account_id: &str,
+        sweep_to: Option<&str>,
+    ) -> io::Result<Response> {
+        self.send(RequestBody::CloseAccount(CloseAccountData {
+            token: token.to_string(),
+            account_id: account_id.to_string(),
+            sweep_to: sweep_to.map(str::to_string),
+        }))
+    }
+
+    pub fn get_account_transactions(
+        &self,
+        account_id: &str,
+        query: TransactionQuery,
+    ) -> io::Result<Response> {
+        self.send(RequestBody::GetAccountTransactions(
+            GetAccountTransactionsData {
+                account_id: account_id.to_string(),
+                query,
+            },
+        ))
+    }
+}
@@ -0,0 +1,236 @@
+//! Configuración del servidor.
+//!
+//! La configuración se lee de un fichero TOML en el que todos los campos son
+//! opcionales. El binario `server` permite sustituir cada valor con una
+//! variable de entorno o un argumento, y la valida antes de abrir el puerto.
+
+use crate::framing::MAX_FRAME_SIZE;
+use crate::scheduler::DEFAULT_SCHEDULER_INTERVAL;
+use crate::{DEFAULT_IDEMPOTENCY_RETENTION, DEFAULT_STARTING_BALANCE};
+use coliseum_money::{Amount, DEFAULT_DECIMALS};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+/// Nivel mínimo de los mensajes que se registran
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<LogLevel, String> {
+        match level.to_lowercase().as_str() {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            _ => Err(format!(
+                "Unknown log level {}, expected error, warn, info, debug or trace",
+                level
+            )),
+        }
+    }
+}
+
+/// Formato de los mensajes registrados
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Una línea de texto legible por mensaje
+    #[default]
+    Text,
+    /// Un objeto JSON por línea, con los campos de cada mensaje
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<LogFormat, String> {
+        match format.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "Unknown log format {}, expected text or json",
+                format
+            )),
+        }
+    }
+}
+
+impl From<LogLevel> for tracing::Level {
+    fn from(level: LogLevel) -> tracing::Level {
+        match level {
+            LogLevel::Error => tracing::Level::ERROR,
+            LogLevel::Warn => tracing::Level::WARN,
+            LogLevel::Info => tracing::Level::INFO,
+            LogLevel::Debug => tracing::Level::DEBUG,
+            LogLevel::Trace => tracing::Level::TRACE,
+        }
+    }
+}
+
+/// Configuración del binario `server`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Dirección en la que escucha el servidor
+    pub addr: String,
+    /// Número de hilos que atienden las conexiones
+    pub workers: usize,
+    /// Saldo con el que se crean las cuentas, como número decimal
+    pub starting_balance: String,
+    /// Directorio con el log y las instantáneas de la App
+    pub data_dir: PathBuf,
+    /// Tamaño máximo de las peticiones, en bytes
+    pub max_frame_size: usize,
+    /// Segundos durante los que se recuerdan las claves de idempotencia
+    pub idempotency_retention_secs: u64,
+    /// Milisegundos entre comprobaciones de las transferencias programadas
+    pub scheduler_interval_ms: u64,
+    pub log_level: LogLevel,
+    pub log_format: LogFormat,
+    /// Certificado y clave privada en PEM. Requieren la feature `tls`
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            addr: "127.0.0.1:5000".to_string(),
+            workers: thread::available_parallelism().map_or(4, |workers| workers.get()),
+            starting_balance: DEFAULT_STARTING_BALANCE.to_string(),
+            data_dir: PathBuf::from("data"),
+            max_frame_size: MAX_FRAME_SIZE,
+            idempotency_retention_secs: DEFAULT_IDEMPOTENCY_RETENTION.as_secs(),
+            scheduler_interval_ms: DEFAULT_SCHEDULER_INTERVAL.as_millis() as u64,
+            log_level: LogLevel::default(),
+            log_format: LogFormat::default(),
+            tls_cert: None,
+            tls_key: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Lee la configuración de un fichero TOML
+    pub fn from_file(path: &Path) -> Result<ServerConfig, ConfigError> {
+        let data =
+            fs::read_to_string(path).map_err(|error| ConfigError::Read(path.into(), error))?;
+        toml::from_str(&data).map_err(|error| ConfigError::Parse(path.into(), error.to_string()))
+    }
+
+    pub fn idempotency_retention(&self) -> Duration {
+        Duration::from_secs(self.idempotency_retention_secs)
+    }
+
+    pub fn scheduler_interval(&self) -> Duration {
+        Duration::from_millis(self.scheduler_interval_ms)
+    }
+
+    /// Saldo inicial con los decimales por defecto de la App
+    pub fn starting_balance(&self) -> Result<Amount, ConfigError> {
+        Amount::parse(&self.starting_balance, DEFAULT_DECIMALS)
+            .map_err(|error| ConfigError::invalid("starting_balance", error))
+    }
+
+    /// Comprueba que todos los valores se pueden usar
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let addrs = self
+            .addr
+            .to_socket_addrs()
+            .map_err(|error| ConfigError::invalid("addr", error))?;
+        if addrs.count() == 0 {
+            return Err(ConfigError::invalid("addr", "resolves to no address"));
+        }
+        if self.workers == 0 {
+            return Err(ConfigError::invalid("workers", "must be at least 1"));
+        }
+        self.starting_balance()?;
+        if self.max_frame_size == 0 || self.max_frame_size > u32::MAX as usize {
+            return Err(ConfigError::invalid(
+                "max_frame_size",
+                format!("must be between 1 and {} bytes", u32::MAX),
+            ));
+        }
+        if self.idempotency_retention_secs == 0 {
+            return Err(ConfigError::invalid(
+                "idempotency_retention_secs",
+                "must be at least 1 second",
+            ));
+        }
+        if self.scheduler_interval_ms == 0 {
+            return Err(ConfigError::invalid(
+                "scheduler_interval_ms",
+                "must be at least 1 millisecond",
+            ));
+        }
+
+        match (&self.tls_cert, &self.tls_key) {
+            (None, None) => Ok(()),
+            (Some(_), Some(_)) if cfg!(feature = "tls") => Ok(()),
+            (Some(_), Some(_)) => Err(ConfigError::invalid(
+                "tls_cert",
+                "the server was built without the tls feature",
+            )),
+            (Some(_), None) => Err(ConfigError::invalid("tls_key", "required with tls_cert")),
+            (None, Some(_)) => Err(ConfigError::invalid("tls_cert", "required with tls_key")),
+        }
+    }
+}
+
+/// Motivos por los que no se puede usar una configuración
+#[derive(Debug)]
+pub enum ConfigError {
+    /// No se ha podido leer el fichero
+    Read(PathBuf, io::Error),
+    /// El fichero no es TOML válido o tiene campos desconocidos
+    Parse(PathBuf, String),
+    /// El valor de un campo no es válido
+    Invalid {
+        field: &'static str,
+        message: String,
+    },
+}
+
+impl ConfigError {
+    fn invalid(field: &'static str, message: impl fmt::Display) -> ConfigError {
+        ConfigError::Invalid {
+            field,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, error) => {
+                write!(f, "Unable to read {}: {}", path.display(), error)
+            }
+            ConfigError::Parse(path, error) => {
+                write!(f, "Invalid configuration in {}: {}", path.display(), error)
+            }
+            ConfigError::Invalid { field, message } => write!(f, "Invalid {}: {}", field, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
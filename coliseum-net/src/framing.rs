@@ -0,0 +1,85 @@
+//! Framing of the messages exchanged over TCP.
+//!
+//! Every message is sent as a frame: its length as a 4 byte big-endian
+//! integer followed by that many bytes of JSON. Reading a frame waits for
+//! the whole message, however many TCP reads it takes.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// Default maximum size of a frame payload, in bytes.
+pub const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// Size of the length header that precedes every payload, in bytes.
+pub const HEADER_SIZE: usize = 4;
+
+/// Error of a frame whose header announces more than the maximum size. It
+/// travels inside an [`io::ErrorKind::InvalidData`] error, the same kind TLS
+/// and other transport errors use, see [`is_frame_too_large`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTooLarge {
+    pub length: usize,
+    pub max_size: usize,
+}
+
+impl fmt::Display for FrameTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Frame of {} bytes exceeds the maximum of {} bytes",
+            self.length, self.max_size
+        )
+    }
+}
+
+impl std::error::Error for FrameTooLarge {}
+
+/// Whether `error` comes from a frame over the maximum size.
+pub fn is_frame_too_large(error: &io::Error) -> bool {
+    error
+        .get_ref()
+        .is_some_and(|inner| inner.is::<FrameTooLarge>())
+}
+
+/// Header of a frame carrying `payload_length` bytes. Shared by the blocking
+/// and async transports, which only add the I/O.
+pub fn encode_header(payload_length: usize) -> io::Result<[u8; HEADER_SIZE]> {
+    let length = u32::try_from(payload_length).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Frame of {} bytes is too large", payload_length),
+        )
+    })?;
+    Ok(length.to_be_bytes())
+}
+
+/// Payload length announced by a frame header, rejecting payloads larger
+/// than `max_size` bytes.
+pub fn decode_header(header: [u8; HEADER_SIZE], max_size: usize) -> io::Result<usize> {
+    let length = u32::from_be_bytes(header) as usize;
+    if length > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            FrameTooLarge { length, max_size },
+        ));
+    }
+    Ok(length)
+}
+
+/// Writes `payload` as a single frame. It is up to the receiver to accept
+/// frames larger than [`MAX_FRAME_SIZE`].
+pub fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&encode_header(payload.len())?)?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Reads a whole frame, rejecting payloads larger than `max_size` bytes
+/// without reading them.
+pub fn read_frame<R: Read>(reader: &mut R, max_size: usize) -> io::Result<Vec<u8>> {
+    let mut header = [0; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    let mut payload = vec![0; decode_header(header, max_size)?];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
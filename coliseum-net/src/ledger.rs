@@ -0,0 +1,324 @@
+//! Libro mayor de partida doble.
+//!
+//! Cada movimiento de fondos se apunta en el diario como un débito en la
+//! cuenta que los envía y un crédito por la misma cantidad en la que los
+//! recibe. El saldo de una cuenta son sus créditos menos sus débitos, y el
+//! saldo de [`Account`] es una copia del que calcula el libro. El saldo
+//! inicial de las cuentas sale de la cuenta de emisión, [`ISSUANCE_ACCOUNT`],
+//! que es la única con saldo negativo: la suma de todos los saldos es siempre
+//! cero y lo emitido es igual a lo que tienen las cuentas.
+
+use crate::{Account, Transaction};
+use coliseum_money::Amount;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Cuenta desde la que se emite el saldo inicial de las cuentas
+pub const ISSUANCE_ACCOUNT: &str = "issuance";
+
+/// Lado de un apunte
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    /// Salida de fondos de la cuenta
+    Debit,
+    /// Entrada de fondos en la cuenta
+    Credit,
+}
+
+/// Apunte del diario
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    /// ID de la transacción del apunte, o de la cuenta en la emisión de su
+    /// saldo inicial
+    pub reference: String,
+    pub account_id: String,
+    pub side: Side,
+    pub amount: Amount,
+    pub timestamp: f64,
+}
+
+/// Total emitido y total en las cuentas, que deben coincidir, en unidades.
+/// La suma de los saldos puede superar el máximo de un [`Amount`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Supply {
+    pub issued: u128,
+    pub circulating: u128,
+}
+
+/// Diario con los saldos que resultan de sus apuntes
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    /// Saldos de apertura, en unidades: los de los apuntes ya resumidos por
+    /// [`Ledger::compact`]
+    opening: HashMap<String, i128>,
+    /// Apuntes posteriores a los saldos de apertura
+    entries: Vec<JournalEntry>,
+    /// ID de cuenta -> créditos menos débitos, en unidades
+    balances: HashMap<String, i128>,
+}
+
+impl Ledger {
+    /// Libro con los saldos de apertura y los apuntes guardados en una
+    /// instantánea
+    pub fn load(opening: HashMap<String, i128>, entries: Vec<JournalEntry>) -> Ledger {
+        let mut ledger = Ledger {
+            balances: opening.clone(),
+            opening,
+            ..Ledger::default()
+        };
+        for entry in &entries {
+            *ledger.balances.entry(entry.account_id.clone()).or_default() += signed(entry);
+        }
+        ledger.entries = entries;
+        ledger
+    }
+
+    /// Reconstruye el diario de los datos guardados antes de que existiera.
+    /// El saldo inicial de cada cuenta es su saldo actual sin las
+    /// transacciones en las que participa.
+    pub fn rebuild(accounts: &[Account], transactions: &[Transaction]) -> Result<Ledger, String> {
+        let mut grants: HashMap<&str, i128> = accounts
+            .iter()
+            .map(|account| (account.id.as_str(), account.balance.units() as i128))
+            .collect();
+        for transaction in transactions {
+            let amount = transaction.amount.units() as i128;
+            for (account_id, change) in [
+                (&transaction.from_id, amount),
+                (&transaction.to_id, -amount),
+            ] {
+                *grants
+                    .get_mut(account_id.as_str())
+                    .ok_or_else(|| format!("Account with ID {} not found", account_id))? += change;
+            }
+        }
+
+        let mut ledger = Ledger::default();
+        for account in accounts {
+            let grant = u64::try_from(grants[account.id.as_str()])
+                .map_err(|_| format!("Account with ID {} has a negative grant", account.id))?;
+            ledger.issue(&account.id, Amount::from_units(grant), account.created_time)?;
+        }
+        for transaction in transactions {
+            ledger.transfer(
+                &transaction.id,
+                &transaction.from_id,
+                &transaction.to_id,
+                transaction.amount,
+                transaction.timestamp,
+            )?;
+        }
+        Ok(ledger)
+    }
+
+    /// Apuntes posteriores a los saldos de apertura, en el orden en el que
+    /// se hicieron
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Saldos de apertura de las cuentas, en unidades
+    pub fn opening(&self) -> &HashMap<String, i128> {
+        &self.opening
+    }
+
+    /// Resume los apuntes en los saldos de apertura, para que el diario no
+    /// crezca sin límite
+    pub fn compact(&mut self) {
+        self.opening = self.balances.clone();
+        self.entries.clear();
+    }
+
+    /// Saldo de una cuenta en unidades. Solo la cuenta de emisión puede
+    /// tenerlo negativo.
+    pub fn balance(&self, account_id: &str) -> i128 {
+        self.balances.get(account_id).copied().unwrap_or_default()
+    }
+
+    /// Saldo de una cuenta de usuario
+    pub fn amount(&self, account_id: &str) -> Amount {
+        Amount::from_units(self.balance(account_id) as u64)
+    }
+
+    /// Emite `amount` para una cuenta nueva desde la cuenta de emisión
+    pub fn issue(
+        &mut self,
+        account_id: &str,
+        amount: Amount,
+        timestamp: f64,
+    ) -> Result<(), String> {
+        self.transfer(account_id, ISSUANCE_ACCOUNT, account_id, amount, timestamp)
+    }
+
+    /// Apunta el débito en `from_id` y el crédito en `to_id` de un
+    /// movimiento, o ninguno si deja una cuenta de usuario con saldo negativo
+    /// o mayor que el máximo representable
+    pub fn transfer(
+        &mut self,
+        reference: &str,
+        from_id: &str,
+        to_id: &str,
+        amount: Amount,
+        timestamp: f64,
+    ) -> Result<(), String> {
+        let (from_balance, to_balance) = moved(
+            from_id,
+            to_id,
+            self.balance(from_id),
+            self.balance(to_id),
+            amount,
+        )?;
+
+        self.balances.insert(from_id.to_string(), from_balance);
+        self.balances.insert(to_id.to_string(), to_balance);
+        for (account_id, side) in [(from_id, Side::Debit), (to_id, Side::Credit)] {
+            self.entries.push(JournalEntry {
+                reference: reference.to_string(),
+                account_id: account_id.to_string(),
+                side,
+                amount,
+                timestamp,
+            });
+        }
+        Ok(())
+    }
+
+    /// Comprueba sin apuntar nada que los movimientos `(from_id, to_id,
+    /// amount)` se pueden apuntar uno tras otro, de manera que se apuntan
+    /// todos o ninguno
+    pub fn check_transfers<'a>(
+        &self,
+        transfers: impl IntoIterator<Item = (&'a str, &'a str, Amount)>,
+    ) -> Result<(), String> {
+        let mut staged: HashMap<&str, i128> = HashMap::new();
+        for (from_id, to_id, amount) in transfers {
+            let balance = |account_id| {
+                staged
+                    .get(account_id)
+                    .copied()
+                    .unwrap_or_else(|| self.balance(account_id))
+            };
+            let (from_balance, to_balance) =
+                moved(from_id, to_id, balance(from_id), balance(to_id), amount)?;
+            staged.insert(from_id, from_balance);
+            staged.insert(to_id, to_balance);
+        }
+        Ok(())
+    }
+
+    /// Comprueba que el diario cuadra con las cuentas: cada referencia tiene
+    /// tantos débitos como créditos, los saldos calculados son los de
+    /// apertura más los apuntes y los de las cuentas, y lo emitido es lo que
+    /// tienen las cuentas
+    pub fn check(&self, accounts: &[Account]) -> Result<Supply, LedgerError> {
+        let mut references: HashMap<&str, i128> = HashMap::new();
+        let mut balances: HashMap<&str, i128> = self
+            .opening
+            .iter()
+            .map(|(account_id, balance)| (account_id.as_str(), *balance))
+            .collect();
+        for entry in &self.entries {
+            *references.entry(&entry.reference).or_default() += signed(entry);
+            *balances.entry(&entry.account_id).or_default() += signed(entry);
+        }
+        if let Some((reference, _)) = references.iter().find(|(_, total)| **total != 0) {
+            return Err(LedgerError::Unbalanced(reference.to_string()));
+        }
+        for (account_id, balance) in &balances {
+            if self.balance(account_id) != *balance {
+                return Err(LedgerError::BalanceMismatch(account_id.to_string()));
+            }
+        }
+
+        let mut circulating: i128 = 0;
+        for account in accounts {
+            if self.balance(&account.id) != account.balance.units() as i128 {
+                return Err(LedgerError::BalanceMismatch(account.id.clone()));
+            }
+            circulating += account.balance.units() as i128;
+        }
+        let issued = -self.balance(ISSUANCE_ACCOUNT);
+        // Todas las cuentas con saldo son de usuario o la de emisión
+        let total: i128 = self.balances.values().sum();
+        if issued != circulating || total != 0 {
+            return Err(LedgerError::SupplyMismatch {
+                issued: issued.to_string(),
+                circulating: circulating.to_string(),
+            });
+        }
+
+        Ok(Supply {
+            issued: issued as u128,
+            circulating: circulating as u128,
+        })
+    }
+}
+
+/// Saldos de `from_id` y `to_id` después de mover `amount`, o error si deja
+/// una cuenta de usuario con saldo negativo o mayor que el máximo
+/// representable
+fn moved(
+    from_id: &str,
+    to_id: &str,
+    from_balance: i128,
+    to_balance: i128,
+    amount: Amount,
+) -> Result<(i128, i128), String> {
+    let units = amount.units() as i128;
+    let from_balance = from_balance - units;
+    let to_balance = to_balance + units;
+    if from_id != ISSUANCE_ACCOUNT && from_balance < 0 {
+        return Err(format!("Account with ID {} would be overdrawn", from_id));
+    }
+    if to_id != ISSUANCE_ACCOUNT && to_balance > u64::MAX as i128 {
+        return Err(format!("Account with ID {} would overflow", to_id));
+    }
+    Ok((from_balance, to_balance))
+}
+
+/// Importe de un apunte con signo: positivo en los créditos
+fn signed(entry: &JournalEntry) -> i128 {
+    let units = entry.amount.units() as i128;
+    match entry.side {
+        Side::Credit => units,
+        Side::Debit => -units,
+    }
+}
+
+/// Motivos por los que el diario no cuadra
+#[derive(Debug, Clone, PartialEq)]
+pub enum LedgerError {
+    /// Los débitos y créditos de una referencia no suman lo mismo
+    Unbalanced(String),
+    /// El saldo de una cuenta no es el que resulta de sus apuntes
+    BalanceMismatch(String),
+    /// Lo emitido no coincide con lo que tienen las cuentas, en unidades
+    SupplyMismatch { issued: String, circulating: String },
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LedgerError::Unbalanced(reference) => {
+                write!(f, "Journal entries of {} are not balanced", reference)
+            }
+            LedgerError::BalanceMismatch(account_id) => write!(
+                f,
+                "Balance of account with ID {} does not match the journal",
+                account_id
+            ),
+            LedgerError::SupplyMismatch {
+                issued,
+                circulating,
+            } => write!(
+                f,
+                "Issued supply {} does not match the {} units held by accounts",
+                issued, circulating
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {}
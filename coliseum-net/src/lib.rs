@@ -1,12 +1,40 @@
+use auth::{AuthConfig, AuthError, Session, MIN_PASSWORD_LENGTH};
+use coliseum_money::{Amount, AmountError, DEFAULT_DECIMALS};
+use framing::{read_frame, write_frame, MAX_FRAME_SIZE};
+use ledger::{Ledger, LedgerError, Supply};
+use scheduler::ScheduledTransfer;
 use serde::{Deserialize, Serialize};
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use statement::StatementQuery;
+use status::{ErrorBody, ErrorCode, Status};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io;
+use std::mem;
+use std::net::{TcpStream, ToSocketAddrs};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use storage::{Event, MemoryStorage, State, Storage};
 use uuid::Uuid;
 
+#[cfg(feature = "tokio")]
+pub mod async_net;
+pub mod auth;
+pub mod client;
+pub mod config;
+pub mod framing;
+pub mod ledger;
+pub mod net;
+pub mod router;
+pub mod scheduler;
+pub mod server;
+pub mod statement;
+pub mod status;
+pub mod storage;
+#[cfg(feature = "tls")]
+pub mod tls;
+
 /* Lógica de negocio */
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -14,18 +42,181 @@ pub struct Transaction {
     pub id: String,
     pub from_id: String,
     pub to_id: String,
-    pub amount: f64,
+    pub amount: Amount,
     pub timestamp: f64,
     pub node: String,
+    /// Clave con la que el cliente puede repetir la petición sin que se
+    /// vuelvan a mover los fondos
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// Lote en el que se creó la transacción, junto con las demás del lote
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    /// Saldo de la cuenta origen después de la transacción
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_balance: Option<Amount>,
+    /// Saldo de la cuenta destino después de la transacción
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_balance: Option<Amount>,
+}
+
+impl Transaction {
+    /// Copia sin los saldos, que son datos privados de las cuentas, para las
+    /// consultas que no requieren una sesión
+    pub fn without_balances(mut self) -> Transaction {
+        self.from_balance = None;
+        self.to_balance = None;
+        self
+    }
+
+    /// Copia con solo el saldo de la cuenta origen, para responder a quien
+    /// envía los fondos
+    pub fn for_sender(mut self) -> Transaction {
+        self.to_balance = None;
+        self
+    }
+}
+
+/// Longitud máxima de una clave de idempotencia
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// Número máximo de transferencias de un lote
+pub const MAX_BATCH_LEGS: usize = 100;
+
+/// Tiempo máximo por defecto para conectar con el servidor, enviarle la
+/// petición o recibir su respuesta
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tiempo durante el que se recuerda una clave de idempotencia por defecto
+pub const DEFAULT_IDEMPOTENCY_RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+/// Motivos por los que se rechaza una transferencia
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferError {
+    /// La cantidad no es un número válido mayor que cero
+    InvalidAmount(AmountError),
+    /// La cuenta origen y destino son la misma
+    SelfTransfer,
+    /// No existe la cuenta con el ID indicado
+    AccountNotFound(String),
+    /// La cuenta con el ID indicado está congelada
+    AccountFrozen(String),
+    /// La cuenta con el ID indicado está cerrada
+    AccountClosed(String),
+    /// La cuenta origen no tiene saldo suficiente
+    InsufficientFunds { available: String },
+    /// El saldo de la cuenta destino superaría el máximo representable
+    Overflow(AmountError),
+    /// El token no permite mover fondos de la cuenta origen
+    Unauthorized(AuthError),
+    /// La clave de idempotencia está vacía o es demasiado larga
+    InvalidIdempotencyKey,
+    /// La clave de idempotencia ya se usó en una transferencia distinta
+    IdempotencyKeyReused(String),
+    /// El lote no tiene transferencias o tiene más de `MAX_BATCH_LEGS`
+    InvalidBatch(usize),
+    /// La fecha o el intervalo de una transferencia programada no son válidos
+    InvalidSchedule(String),
+    /// No existe la transferencia programada con el ID indicado
+    ScheduleNotFound(String),
+    /// No se ha podido guardar la transacción
+    Storage(String),
 }
 
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransferError::InvalidAmount(error) => write!(f, "Invalid amount: {}", error),
+            TransferError::SelfTransfer => write!(f, "Cannot transfer to the same account"),
+            TransferError::AccountNotFound(account_id) => {
+                write!(f, "Account with ID {} not found", account_id)
+            }
+            TransferError::AccountFrozen(account_id) => {
+                write!(f, "Account with ID {} is frozen", account_id)
+            }
+            TransferError::AccountClosed(account_id) => {
+                write!(f, "Account with ID {} is closed", account_id)
+            }
+            TransferError::InsufficientFunds { available } => {
+                write!(
+                    f,
+                    "La cuenta origen no tiene fondos suficientes {}",
+                    available
+                )
+            }
+            TransferError::Overflow(error) => write!(f, "Balance {}", error),
+            TransferError::Unauthorized(error) => write!(f, "{}", error),
+            TransferError::InvalidIdempotencyKey => write!(
+                f,
+                "Idempotency key must have between 1 and {} characters",
+                MAX_IDEMPOTENCY_KEY_LENGTH
+            ),
+            TransferError::IdempotencyKeyReused(key) => write!(
+                f,
+                "Idempotency key {} was already used for a different transfer",
+                key
+            ),
+            TransferError::InvalidBatch(legs) => write!(
+                f,
+                "A batch must have between 1 and {} transfers, got {}",
+                MAX_BATCH_LEGS, legs
+            ),
+            TransferError::InvalidSchedule(error) => {
+                write!(f, "Invalid scheduled transfer: {}", error)
+            }
+            TransferError::ScheduleNotFound(schedule_id) => {
+                write!(f, "Scheduled transfer with ID {} not found", schedule_id)
+            }
+            TransferError::Storage(error) => write!(f, "Unable to store transaction: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Account {
     pub id: String,
     pub created_time: f64,
     pub last_login: f64,
     pub username: String,
-    pub balance: f64,
+    pub balance: Amount,
+    #[serde(default)]
+    pub status: AccountStatus,
+}
+
+/// Estado de una cuenta
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    /// No puede enviar ni recibir fondos hasta que se reactive
+    Frozen,
+    /// Cerrada definitivamente, no admite más operaciones
+    Closed,
+}
+
+/// Transferencias creadas a la vez con `create_batch_transaction`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Batch {
+    pub batch_id: String,
+    pub transactions: Vec<Transaction>,
+}
+
+/// Transferencia de un lote, desde la cuenta origen del lote
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransferLeg {
+    pub to_id: String,
+    pub amount: String,
+}
+
+/// Resultado de cerrar una cuenta
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClosedAccount {
+    pub account: Account,
+    /// Transferencia del saldo que le quedaba a la cuenta, si tenía
+    pub sweep: Option<Transaction>,
 }
 
 impl Account {
@@ -33,156 +224,1117 @@ impl Account {
         match serde_json::to_string(&self.clone()) {
             Ok(data) => Ok(data),
             Err(error) => {
-                println!("{}", error);
+                tracing::error!("Unable to serialize account: {}", error);
                 Err("Internal Server Error".to_string())
             }
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Motivos por los que no se puede crear o modificar una cuenta
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountError {
+    /// Ya existe una cuenta con el mismo nombre de usuario
+    UsernameTaken(String),
+    /// La contraseña es demasiado corta
+    InvalidPassword,
+    /// No existe la cuenta con el ID indicado
+    AccountNotFound(String),
+    /// El token no permite operar con la cuenta
+    Unauthorized(AuthError),
+    /// La cuenta está congelada
+    AccountFrozen(String),
+    /// La cuenta está cerrada
+    AccountClosed(String),
+    /// La cuenta tiene saldo y no se ha indicado a qué cuenta transferirlo
+    BalanceNotZero {
+        balance: String,
+    },
+    /// No se ha podido transferir el saldo de la cuenta al cerrarla
+    Sweep(TransferError),
+    /// No se ha podido guardar la cuenta
+    Storage(String),
+    Internal(String),
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccountError::UsernameTaken(username) => {
+                write!(f, "Username {} is already taken", username)
+            }
+            AccountError::InvalidPassword => write!(
+                f,
+                "Password must have at least {} characters",
+                MIN_PASSWORD_LENGTH
+            ),
+            AccountError::AccountNotFound(account_id) => {
+                write!(f, "Account with ID {} not found", account_id)
+            }
+            AccountError::Unauthorized(error) => write!(f, "{}", error),
+            AccountError::AccountFrozen(account_id) => {
+                write!(f, "Account with ID {} is frozen", account_id)
+            }
+            AccountError::AccountClosed(account_id) => {
+                write!(f, "Account with ID {} is closed", account_id)
+            }
+            AccountError::BalanceNotZero { balance } => write!(
+                f,
+                "Account has a balance of {}, close it with an account to transfer it to",
+                balance
+            ),
+            AccountError::Sweep(error) => write!(f, "Unable to transfer the balance: {}", error),
+            AccountError::Storage(error) => write!(f, "Unable to store account: {}", error),
+            AccountError::Internal(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+/// Saldo inicial por defecto de las cuentas, en unidades enteras
+pub const DEFAULT_STARTING_BALANCE: u64 = 10;
+
+/// Las cuentas y transacciones se guardan en el orden en el que se crean y
+/// se buscan por medio de índices con su posición.
+#[derive(Debug)]
 pub struct App {
     pub addr: String,
-    pub accounts: Vec<Account>,
-    pub transactions: Vec<Transaction>,
+    pub decimals: u32,
+    /// Saldo con el que se crean las cuentas
+    pub starting_balance: Amount,
+    pub auth: AuthConfig,
+    /// Tiempo durante el que una transferencia se puede repetir con su
+    /// clave de idempotencia
+    pub idempotency_retention: Duration,
+    accounts: Vec<Account>,
+    transactions: Vec<Transaction>,
+    /// ID de cuenta -> posición en `accounts`
+    account_ids: HashMap<String, usize>,
+    /// Nombre de usuario -> posición en `accounts`
+    usernames: HashMap<String, usize>,
+    /// ID de transacción -> posición en `transactions`
+    transaction_ids: HashMap<String, usize>,
+    /// ID de cuenta -> posiciones de sus transacciones en `transactions`
+    account_transactions: HashMap<String, Vec<usize>>,
+    /// ID de cuenta -> hash de su contraseña
+    credentials: HashMap<String, String>,
+    /// (cuenta origen, clave de idempotencia) -> posición en `transactions`
+    idempotency_keys: HashMap<(String, String), usize>,
+    /// Claves de idempotencia en el orden en el que se usaron, para
+    /// descartar las caducadas
+    idempotency_order: VecDeque<((String, String), usize)>,
+    scheduled_transfers: Vec<ScheduledTransfer>,
+    /// ID de transferencia programada -> posición en `scheduled_transfers`
+    scheduled_ids: HashMap<String, usize>,
+    /// Diario del que salen los saldos de las cuentas
+    ledger: Ledger,
+    /// Token -> sesión. Las sesiones no se guardan, tras reiniciar el
+    /// servidor hay que volver a iniciar sesión.
+    sessions: HashMap<String, Session>,
+    storage: Box<dyn Storage>,
 }
 
 impl App {
-    /// Create a new App/node
+    /// Create a new App/node that keeps its data in memory
     pub fn new(addr: String) -> App {
-        let app = App {
+        App {
             addr,
+            decimals: DEFAULT_DECIMALS,
+            starting_balance: Amount::from_whole(DEFAULT_STARTING_BALANCE, DEFAULT_DECIMALS)
+                .unwrap(),
+            auth: AuthConfig::default(),
+            idempotency_retention: DEFAULT_IDEMPOTENCY_RETENTION,
             accounts: Vec::new(),
             transactions: Vec::new(),
+            account_ids: HashMap::new(),
+            usernames: HashMap::new(),
+            transaction_ids: HashMap::new(),
+            account_transactions: HashMap::new(),
+            credentials: HashMap::new(),
+            idempotency_keys: HashMap::new(),
+            idempotency_order: VecDeque::new(),
+            scheduled_transfers: Vec::new(),
+            scheduled_ids: HashMap::new(),
+            ledger: Ledger::default(),
+            sessions: HashMap::new(),
+            storage: Box::new(MemoryStorage::new()),
+        }
+    }
+
+    /// Open an App/node recovering the data saved in `storage`
+    pub fn open(addr: String, storage: impl Storage + 'static) -> io::Result<App> {
+        let mut app = App::new(addr);
+        app.storage = Box::new(storage);
+
+        let (mut state, events) = app.storage.load()?;
+        let journal = mem::take(&mut state.journal);
+        let ledger_balances = mem::take(&mut state.ledger_balances);
+        let accounts = state
+            .accounts
+            .into_iter()
+            .map(|account| Event::AccountCreated {
+                password_hash: state.credentials.remove(&account.id).unwrap_or_default(),
+                account,
+            });
+        let snapshot = accounts
+            .chain(
+                state
+                    .transactions
+                    .into_iter()
+                    .map(Event::TransactionCreated),
+            )
+            .chain(
+                state
+                    .scheduled_transfers
+                    .into_iter()
+                    .map(Event::TransferScheduled),
+            );
+        let invalid = |error| io::Error::new(io::ErrorKind::InvalidData, error);
+        for event in snapshot {
+            app.apply(event, false).map_err(invalid)?;
+        }
+        // Las instantáneas anteriores al diario solo tienen los saldos
+        app.ledger = if journal.is_empty() && ledger_balances.is_empty() {
+            Ledger::rebuild(&app.accounts, &app.transactions).map_err(invalid)?
+        } else {
+            Ledger::load(ledger_balances, journal)
         };
-        app
+        for event in events {
+            app.apply(event, true).map_err(invalid)?;
+        }
+
+        app.fill_running_balances();
+        app.check_ledger()
+            .map_err(|error| invalid(error.to_string()))?;
+        app.snapshot_if_needed();
+        Ok(app)
+    }
+
+    /// Current data of the App, as saved in a snapshot
+    pub fn state(&self) -> State {
+        State {
+            accounts: self.accounts.clone(),
+            transactions: self.transactions.clone(),
+            credentials: self.credentials.clone(),
+            scheduled_transfers: self.scheduled_transfers.clone(),
+            journal: self.ledger.entries().to_vec(),
+            ledger_balances: self.ledger.opening().clone(),
+        }
+    }
+
+    /// Accounts in creation order
+    pub fn accounts(&self) -> &[Account] {
+        &self.accounts
+    }
+
+    /// Transactions in creation order
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Double-entry journal behind the balances of the accounts
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Check that the journal is balanced, that the balances of the accounts
+    /// are the ones in the journal and that they add up to the issued supply.
+    /// It runs when the App is opened and, in debug builds, after every
+    /// committed event.
+    pub fn check_ledger(&self) -> Result<Supply, LedgerError> {
+        self.ledger.check(&self.accounts)
+    }
+
+    /// Save `event` and then apply it. Events must be validated beforehand;
+    /// one that still does not apply is returned as an error instead of
+    /// panicking while the App is locked.
+    fn commit(&mut self, event: Event) -> io::Result<()> {
+        self.storage.append(&event)?;
+        if let Err(error) = self.apply(event, true) {
+            // Un evento que no se aplica no cambia nada, así que la
+            // instantánea guarda el estado anterior y sustituye al log, de
+            // manera que el evento no impide volver a abrir la App
+            tracing::error!("Unable to apply a validated event: {}", error);
+            if let Err(error) = self.storage.snapshot(&self.state()) {
+                tracing::error!("Unable to save snapshot: {}", error);
+            }
+            return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+        }
+        // Al abrir la App siempre se comprueba, y en debug también tras cada evento
+        debug_assert_eq!(self.check_ledger().err(), None);
+        self.snapshot_if_needed();
+        Ok(())
+    }
+
+    fn snapshot_if_needed(&mut self) {
+        if self.storage.needs_snapshot() {
+            // La instantánea guarda los saldos del diario, no sus apuntes
+            self.ledger.compact();
+            // Sin la instantánea los datos se recuperan igualmente del log
+            if let Err(error) = self.storage.snapshot(&self.state()) {
+                tracing::error!("Unable to save snapshot: {}", error);
+            }
+        }
+    }
+
+    /// Apply a saved event to the data of the App and its indexes. The
+    /// balances in a snapshot already include its transactions and are in its
+    /// journal, so they are only posted when `update_balances` is set.
+    fn apply(&mut self, event: Event, update_balances: bool) -> Result<(), String> {
+        match event {
+            Event::AccountCreated {
+                mut account,
+                password_hash,
+            } => {
+                if self.account_ids.contains_key(&account.id)
+                    || self.usernames.contains_key(&account.username)
+                {
+                    return Err(format!("Duplicated account {}", account.id));
+                }
+
+                if update_balances {
+                    // El saldo de la cuenta nueva es lo que se le emite
+                    self.ledger
+                        .issue(&account.id, account.balance, account.created_time)?;
+                    account.balance = self.ledger.amount(&account.id);
+                }
+                let position = self.accounts.len();
+                self.account_ids.insert(account.id.clone(), position);
+                self.usernames.insert(account.username.clone(), position);
+                self.credentials.insert(account.id.clone(), password_hash);
+                self.accounts.push(account);
+            }
+            Event::LoggedIn {
+                account_id,
+                timestamp,
+            } => {
+                let position = self
+                    .account_index(&account_id)
+                    .map_err(|error| error.to_string())?;
+                self.accounts[position].last_login = timestamp;
+            }
+            Event::AccountRenamed {
+                account_id,
+                username,
+            } => {
+                let position = self
+                    .account_index(&account_id)
+                    .map_err(|error| error.to_string())?;
+                if self.usernames.contains_key(&username) {
+                    return Err(format!("Duplicated username {}", username));
+                }
+
+                let account = &mut self.accounts[position];
+                self.usernames.remove(&account.username);
+                self.usernames.insert(username.clone(), position);
+                account.username = username;
+            }
+            Event::AccountStatusChanged { account_id, status } => {
+                let position = self
+                    .account_index(&account_id)
+                    .map_err(|error| error.to_string())?;
+                self.accounts[position].status = status;
+            }
+            Event::AccountClosed { account_id, sweep } => {
+                let position = self
+                    .account_index(&account_id)
+                    .map_err(|error| error.to_string())?;
+                self.check_transactions(sweep.as_slice(), update_balances)?;
+                if let Some(transaction) = sweep {
+                    self.apply_transaction(transaction, update_balances)?;
+                }
+                self.accounts[position].status = AccountStatus::Closed;
+            }
+            Event::TransactionCreated(transaction) => {
+                self.apply_transaction(transaction, update_balances)?
+            }
+            Event::BatchCreated(transactions) => {
+                self.check_transactions(&transactions, update_balances)?;
+                for transaction in transactions {
+                    self.apply_transaction(transaction, update_balances)?;
+                }
+            }
+            Event::TransferScheduled(schedule) => self.apply_schedule(schedule)?,
+            Event::ScheduledTransferRan(run) => self.apply_scheduled_run(run, update_balances)?,
+            Event::ScheduledTransferCancelled { schedule_id } => {
+                self.apply_schedule_cancelled(&schedule_id)?
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a saved transaction like `apply` does
+    /// Comprueba que las transacciones se pueden aplicar una tras otra antes
+    /// de aplicar ninguna, para que un evento con varias no quede a medias
+    fn check_transactions(
+        &self,
+        transactions: &[Transaction],
+        update_balances: bool,
+    ) -> Result<(), String> {
+        let mut ids = HashSet::new();
+        for transaction in transactions {
+            for account_id in [&transaction.from_id, &transaction.to_id] {
+                self.account_index(account_id)
+                    .map_err(|error| error.to_string())?;
+            }
+            if self.transaction_ids.contains_key(&transaction.id) || !ids.insert(&transaction.id) {
+                return Err(format!("Duplicated transaction {}", transaction.id));
+            }
+        }
+        if update_balances {
+            self.ledger
+                .check_transfers(transactions.iter().map(|transaction| {
+                    (
+                        transaction.from_id.as_str(),
+                        transaction.to_id.as_str(),
+                        transaction.amount,
+                    )
+                }))?;
+        }
+        Ok(())
+    }
+
+    fn apply_transaction(
+        &mut self,
+        mut transaction: Transaction,
+        update_balances: bool,
+    ) -> Result<(), String> {
+        let from_index = self
+            .account_index(&transaction.from_id)
+            .map_err(|error| error.to_string())?;
+        let to_index = self
+            .account_index(&transaction.to_id)
+            .map_err(|error| error.to_string())?;
+        if self.transaction_ids.contains_key(&transaction.id) {
+            return Err(format!("Duplicated transaction {}", transaction.id));
+        }
+
+        if update_balances {
+            self.ledger.transfer(
+                &transaction.id,
+                &transaction.from_id,
+                &transaction.to_id,
+                transaction.amount,
+                transaction.timestamp,
+            )?;
+            let from_balance = self.ledger.amount(&transaction.from_id);
+            let to_balance = self.ledger.amount(&transaction.to_id);
+
+            self.accounts[from_index].balance = from_balance;
+            self.accounts[to_index].balance = to_balance;
+            transaction.from_balance = Some(from_balance);
+            transaction.to_balance = Some(to_balance);
+        }
+
+        let position = self.transactions.len();
+        self.transaction_ids
+            .insert(transaction.id.clone(), position);
+        if let Some(key) = &transaction.idempotency_key {
+            let key = (transaction.from_id.clone(), key.clone());
+            self.idempotency_keys.insert(key.clone(), position);
+            self.idempotency_order.push_back((key, position));
+        }
+        for account_id in [&transaction.from_id, &transaction.to_id] {
+            self.account_transactions
+                .entry(account_id.clone())
+                .or_default()
+                .push(position);
+        }
+        self.transactions.push(transaction);
+        Ok(())
     }
 
     /// Create a UUID
     pub fn create_uuid() -> String {
-        let uuid = Uuid::new_v4().to_string().replace("-", "");
-        uuid
+        Uuid::new_v4().to_string().replace('-', "")
     }
 
     /// Create the actual timestamp since UNIX EPOCH
     pub fn create_timestamp() -> f64 {
-        let timestamp = SystemTime::now()
+        SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
-            .as_secs_f64();
-        timestamp
+            .as_secs_f64()
+    }
+
+    /// Static -> Create a new account using the username, which must be
+    /// unique, and the password required to login
+    pub fn create_account(
+        &mut self,
+        username: String,
+        password: String,
+    ) -> Result<String, AccountError> {
+        self.check_new_account(&username, &password)?;
+        let password_hash = self
+            .auth
+            .hash_password(&password)
+            .map_err(AccountError::Internal)?;
+        self.insert_account(username, password_hash)
+    }
+
+    /// Comprueba los datos de una cuenta nueva antes de calcular el hash de
+    /// su contraseña
+    pub(crate) fn check_new_account(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<(), AccountError> {
+        if self.usernames.contains_key(username) {
+            return Err(AccountError::UsernameTaken(username.to_string()));
+        }
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(AccountError::InvalidPassword);
+        }
+        Ok(())
     }
 
-    /// Static -> Create a new account using the username
-    pub fn create_account(&mut self, username: String) -> Result<String, String> {
+    /// Crea la cuenta con el hash ya calculado. El nombre se vuelve a
+    /// comprobar porque otra petición ha podido usarlo mientras tanto.
+    pub(crate) fn insert_account(
+        &mut self,
+        username: String,
+        password_hash: String,
+    ) -> Result<String, AccountError> {
+        if self.usernames.contains_key(&username) {
+            return Err(AccountError::UsernameTaken(username));
+        }
+
         let timestamp = App::create_timestamp();
 
         let account = Account {
             id: App::create_uuid(),
-            created_time: timestamp.clone(),
-            last_login: timestamp.clone(),
+            created_time: timestamp,
+            last_login: timestamp,
             username,
-            balance: 10.0,
+            balance: self.starting_balance,
+            status: AccountStatus::Active,
         };
 
-        let account_str = account.clone().to_string();
+        let data = account
+            .clone()
+            .to_string()
+            .map_err(AccountError::Internal)?;
+        self.commit(Event::AccountCreated {
+            account,
+            password_hash,
+        })
+        .map_err(|error| AccountError::Storage(error.to_string()))?;
+        Ok(data)
+    }
 
-        match account_str {
-            Ok(data) => {
-                self.accounts.push(account);
-                Ok(data)
-            }
-            Err(error) => Err(error),
+    /// Static -> Start a session for the account with the given credentials
+    pub fn login(&mut self, username: &str, password: &str) -> Result<Session, AuthError> {
+        let credentials = self.credentials_of(username);
+        let hash = credentials.as_ref().map(|(_, hash)| hash.as_str());
+        if !self.auth.check_password(hash, password) {
+            return Err(AuthError::InvalidCredentials);
         }
+        let (account_id, _) = credentials.unwrap();
+        self.start_session(account_id)
+    }
+
+    /// ID y hash de la contraseña de la cuenta de `username`
+    pub(crate) fn credentials_of(&self, username: &str) -> Option<(String, String)> {
+        let account = self.get_account_by_username(username).ok()?;
+        let hash = self.credentials.get(&account.id)?;
+        Some((account.id.clone(), hash.clone()))
+    }
+
+    /// Inicia la sesión de una cuenta cuya contraseña ya se ha comprobado
+    pub(crate) fn start_session(&mut self, account_id: String) -> Result<Session, AuthError> {
+        let closed = self
+            .get_account(&account_id)
+            .map_or(true, |account| account.status == AccountStatus::Closed);
+        if closed {
+            return Err(AuthError::InvalidCredentials);
+        }
+
+        let timestamp = App::create_timestamp();
+        self.commit(Event::LoggedIn {
+            account_id: account_id.clone(),
+            timestamp,
+        })
+        .map_err(|error| AuthError::Storage(error.to_string()))?;
+
+        // Se descartan las sesiones caducadas
+        self.sessions
+            .retain(|_, session| session.expires_at > timestamp);
+
+        let session = Session {
+            token: format!("{}{}", App::create_uuid(), App::create_uuid()),
+            account_id,
+            expires_at: timestamp + self.auth.session_ttl.as_secs_f64(),
+        };
+        self.sessions.insert(session.token.clone(), session.clone());
+        Ok(session)
+    }
+
+    /// Dynamic -> Get the account ID of the session of `token`
+    pub fn authenticate(&mut self, token: &str) -> Result<String, AuthError> {
+        let session = self.sessions.get(token).ok_or(AuthError::InvalidToken)?;
+        if session.expires_at <= App::create_timestamp() {
+            self.sessions.remove(token);
+            return Err(AuthError::SessionExpired);
+        }
+        Ok(session.account_id.clone())
     }
 
     /// Dynamic -> Get a specific account query by its account ID
-    pub fn get_account(&mut self, account_id: &str) -> Result<&mut Account, String> {
-        for account in self.accounts.iter_mut() {
-            if account_id == account.id {
-                return Ok(account);
-            }
+    pub fn get_account(&self, account_id: &str) -> Result<&Account, String> {
+        match self.account_ids.get(account_id) {
+            Some(position) => Ok(&self.accounts[*position]),
+            None => Err(format!("Account with ID {} not found", account_id)),
+        }
+    }
+
+    /// Dynamic -> Get a specific account query by its username
+    pub fn get_account_by_username(&self, username: &str) -> Result<&Account, String> {
+        match self.usernames.get(username) {
+            Some(position) => Ok(&self.accounts[*position]),
+            None => Err(format!("Account with username {} not found", username)),
         }
-        let error = format!("Account with ID {} not found", account_id);
-        Err(error)
     }
 
-    /// Static -> Create a new transaction but it is not check
+    /// Static -> Transfer `amount` between two accounts with a session token
+    /// of `from_id`. Every input is validated before touching any account, so
+    /// either both balances change or none does.
     pub fn create_transaction(
         &mut self,
+        token: &str,
         from_id: String,
         to_id: String,
         amount: String,
-    ) -> Result<Transaction, String> {
-        let transaction = Transaction {
+    ) -> Result<Transaction, TransferError> {
+        self.transfer(token, from_id, to_id, amount, None)
+    }
+
+    /// Static -> Transfer like `create_transaction`, but a repeated request
+    /// with the same `idempotency_key` returns the original transaction
+    /// instead of moving the funds again. Keys belong to the sending account
+    /// and are remembered for `idempotency_retention`.
+    pub fn create_idempotent_transaction(
+        &mut self,
+        token: &str,
+        from_id: String,
+        to_id: String,
+        amount: String,
+        idempotency_key: String,
+    ) -> Result<Transaction, TransferError> {
+        self.transfer(token, from_id, to_id, amount, Some(idempotency_key))
+    }
+
+    fn transfer(
+        &mut self,
+        token: &str,
+        from_id: String,
+        to_id: String,
+        amount: String,
+        idempotency_key: Option<String>,
+    ) -> Result<Transaction, TransferError> {
+        let account_id = self
+            .authenticate(token)
+            .map_err(TransferError::Unauthorized)?;
+        if account_id != from_id {
+            return Err(TransferError::Unauthorized(AuthError::Forbidden));
+        }
+
+        let amount = Amount::parse(&amount, self.decimals)
+            .and_then(Amount::positive)
+            .map_err(TransferError::InvalidAmount)?;
+
+        if let Some(key) = &idempotency_key {
+            if key.is_empty() || key.chars().count() > MAX_IDEMPOTENCY_KEY_LENGTH {
+                return Err(TransferError::InvalidIdempotencyKey);
+            }
+            self.purge_idempotency_keys(App::create_timestamp());
+
+            let replayed = self.idempotency_keys.get(&(from_id.clone(), key.clone()));
+            if let Some(position) = replayed {
+                let original = &self.transactions[*position];
+                if original.to_id != to_id || original.amount != amount {
+                    return Err(TransferError::IdempotencyKeyReused(key.clone()));
+                }
+                return Ok(original.clone());
+            }
+        }
+
+        let transaction = self.prepare_transfer(from_id, to_id, amount, idempotency_key)?;
+        self.commit(Event::TransactionCreated(transaction.clone()))
+            .map_err(|error| TransferError::Storage(error.to_string()))?;
+
+        Ok(self.stored(transaction))
+    }
+
+    /// Copia guardada de una transacción recién aplicada, con los saldos
+    /// resultantes
+    fn stored(&self, transaction: Transaction) -> Transaction {
+        match self.transaction_ids.get(&transaction.id) {
+            Some(position) => self.transactions[*position].clone(),
+            None => transaction,
+        }
+    }
+
+    /// Transacción de `amount` entre dos cuentas si ambas pueden hacerla,
+    /// sin guardarla
+    fn prepare_transfer(
+        &self,
+        from_id: String,
+        to_id: String,
+        amount: Amount,
+        idempotency_key: Option<String>,
+    ) -> Result<Transaction, TransferError> {
+        if from_id == to_id {
+            return Err(TransferError::SelfTransfer);
+        }
+
+        let from_index = self.account_index(&from_id)?;
+        let to_index = self.account_index(&to_id)?;
+        self.check_active(from_index)?;
+        self.check_active(to_index)?;
+
+        let from_balance = self.accounts[from_index].balance;
+        if from_balance.checked_sub(amount).is_err() {
+            return Err(TransferError::InsufficientFunds {
+                available: from_balance.to_decimal_string(self.decimals),
+            });
+        }
+        self.accounts[to_index]
+            .balance
+            .checked_add(amount)
+            .map_err(TransferError::Overflow)?;
+
+        Ok(Transaction {
             id: App::create_uuid(),
             from_id,
             to_id,
-            amount: amount.parse::<f64>().unwrap(),
+            amount,
             timestamp: App::create_timestamp(),
             node: self.addr.clone(),
-        };
+            idempotency_key,
+            batch_id: None,
+            from_balance: None,
+            to_balance: None,
+        })
+    }
+
+    /// Dynamic -> Transfer funds from one account to several with a session
+    /// token of the sending account. The transfers are stored together under
+    /// a shared batch ID: either all of them are made or none is.
+    pub fn create_batch_transaction(
+        &mut self,
+        token: &str,
+        from_id: String,
+        legs: Vec<TransferLeg>,
+    ) -> Result<Batch, TransferError> {
+        let account_id = self
+            .authenticate(token)
+            .map_err(TransferError::Unauthorized)?;
+        if account_id != from_id {
+            return Err(TransferError::Unauthorized(AuthError::Forbidden));
+        }
+        if legs.is_empty() || legs.len() > MAX_BATCH_LEGS {
+            return Err(TransferError::InvalidBatch(legs.len()));
+        }
+
+        let from_index = self.account_index(&from_id)?;
+        self.check_active(from_index)?;
+
+        // Se comprueban los saldos con el total que envía la cuenta origen y
+        // el que recibe cada destino, que puede aparecer en varias
+        // transferencias
+        let mut total = Amount::ZERO;
+        let mut received: HashMap<usize, Amount> = HashMap::new();
+        let mut amounts = Vec::with_capacity(legs.len());
+        for leg in &legs {
+            let amount = Amount::parse(&leg.amount, self.decimals)
+                .and_then(Amount::positive)
+                .map_err(TransferError::InvalidAmount)?;
+            if leg.to_id == from_id {
+                return Err(TransferError::SelfTransfer);
+            }
+            let to_index = self.account_index(&leg.to_id)?;
+            self.check_active(to_index)?;
+
+            total = total
+                .checked_add(amount)
+                .map_err(TransferError::InvalidAmount)?;
+            let to_received = received.entry(to_index).or_insert(Amount::ZERO);
+            *to_received = to_received
+                .checked_add(amount)
+                .map_err(TransferError::Overflow)?;
+            self.accounts[to_index]
+                .balance
+                .checked_add(*to_received)
+                .map_err(TransferError::Overflow)?;
+            amounts.push(amount);
+        }
 
-        let account_from = self.get_account(&transaction.from_id);
-
-        match account_from {
-            Ok(account) => {
-                if account.balance > transaction.amount {
-                    account.balance = account.balance - transaction.amount.clone();
-                } else {
-                    return Err(format!(
-                        "La cuenta origen no tiene fondos suficientes {}",
-                        account.balance
-                    ));
+        let from_balance = self.accounts[from_index].balance;
+        if from_balance.checked_sub(total).is_err() {
+            return Err(TransferError::InsufficientFunds {
+                available: from_balance.to_decimal_string(self.decimals),
+            });
+        }
+
+        let batch_id = App::create_uuid();
+        let timestamp = App::create_timestamp();
+        let transactions: Vec<Transaction> = legs
+            .into_iter()
+            .zip(amounts)
+            .map(|(leg, amount)| Transaction {
+                id: App::create_uuid(),
+                from_id: from_id.clone(),
+                to_id: leg.to_id,
+                amount,
+                timestamp,
+                node: self.addr.clone(),
+                idempotency_key: None,
+                batch_id: Some(batch_id.clone()),
+                from_balance: None,
+                to_balance: None,
+            })
+            .collect();
+        self.commit(Event::BatchCreated(transactions.clone()))
+            .map_err(|error| TransferError::Storage(error.to_string()))?;
+
+        Ok(Batch {
+            batch_id,
+            transactions: transactions
+                .into_iter()
+                .map(|transaction| self.stored(transaction))
+                .collect(),
+        })
+    }
+
+    /// Comprueba que la cuenta en `position` puede enviar y recibir fondos
+    fn check_active(&self, position: usize) -> Result<(), TransferError> {
+        let account = &self.accounts[position];
+        match account.status {
+            AccountStatus::Active => Ok(()),
+            AccountStatus::Frozen => Err(TransferError::AccountFrozen(account.id.clone())),
+            AccountStatus::Closed => Err(TransferError::AccountClosed(account.id.clone())),
+        }
+    }
+
+    /// Posición de la cuenta `account_id` si `token` es de una sesión suya y
+    /// la cuenta no está cerrada
+    fn authorize(&mut self, token: &str, account_id: &str) -> Result<usize, AccountError> {
+        let session_account = self
+            .authenticate(token)
+            .map_err(AccountError::Unauthorized)?;
+        if session_account != account_id {
+            return Err(AccountError::Unauthorized(AuthError::Forbidden));
+        }
+
+        let position = self
+            .account_index(account_id)
+            .map_err(|_| AccountError::AccountNotFound(account_id.to_string()))?;
+        if self.accounts[position].status == AccountStatus::Closed {
+            return Err(AccountError::AccountClosed(account_id.to_string()));
+        }
+        Ok(position)
+    }
+
+    /// Dynamic -> Change the username of an account with a session token of
+    /// the account. The new username must be unique.
+    pub fn rename_account(
+        &mut self,
+        token: &str,
+        account_id: &str,
+        username: String,
+    ) -> Result<Account, AccountError> {
+        let position = self.authorize(token, account_id)?;
+        if self.accounts[position].username == username {
+            return Ok(self.accounts[position].clone());
+        }
+        if self.usernames.contains_key(&username) {
+            return Err(AccountError::UsernameTaken(username));
+        }
+
+        self.commit(Event::AccountRenamed {
+            account_id: account_id.to_string(),
+            username,
+        })
+        .map_err(|error| AccountError::Storage(error.to_string()))?;
+        Ok(self.accounts[position].clone())
+    }
+
+    /// Dynamic -> Freeze an account, which cannot send or receive funds until
+    /// it is unfrozen
+    pub fn freeze_account(
+        &mut self,
+        token: &str,
+        account_id: &str,
+    ) -> Result<Account, AccountError> {
+        self.set_account_status(token, account_id, AccountStatus::Frozen)
+    }
+
+    /// Dynamic -> Unfreeze a frozen account
+    pub fn unfreeze_account(
+        &mut self,
+        token: &str,
+        account_id: &str,
+    ) -> Result<Account, AccountError> {
+        self.set_account_status(token, account_id, AccountStatus::Active)
+    }
+
+    fn set_account_status(
+        &mut self,
+        token: &str,
+        account_id: &str,
+        status: AccountStatus,
+    ) -> Result<Account, AccountError> {
+        let position = self.authorize(token, account_id)?;
+        if self.accounts[position].status != status {
+            self.commit(Event::AccountStatusChanged {
+                account_id: account_id.to_string(),
+                status,
+            })
+            .map_err(|error| AccountError::Storage(error.to_string()))?;
+        }
+        Ok(self.accounts[position].clone())
+    }
+
+    /// Dynamic -> Close an account for good. An account with a balance can
+    /// only be closed by transferring it to `sweep_to` in the same step. The
+    /// sessions of the account end and it cannot login again.
+    pub fn close_account(
+        &mut self,
+        token: &str,
+        account_id: &str,
+        sweep_to: Option<String>,
+    ) -> Result<ClosedAccount, AccountError> {
+        let position = self.authorize(token, account_id)?;
+        if self.accounts[position].status == AccountStatus::Frozen {
+            return Err(AccountError::AccountFrozen(account_id.to_string()));
+        }
+
+        let balance = self.accounts[position].balance;
+        let sweep = match sweep_to {
+            _ if balance.is_zero() => None,
+            None => {
+                return Err(AccountError::BalanceNotZero {
+                    balance: balance.to_decimal_string(self.decimals),
+                })
+            }
+            Some(to_id) => {
+                if to_id == account_id {
+                    return Err(AccountError::Sweep(TransferError::SelfTransfer));
                 }
+                let to_index = self.account_index(&to_id).map_err(AccountError::Sweep)?;
+                self.check_active(to_index).map_err(AccountError::Sweep)?;
+                self.accounts[to_index]
+                    .balance
+                    .checked_add(balance)
+                    .map_err(|error| AccountError::Sweep(TransferError::Overflow(error)))?;
+
+                Some(Transaction {
+                    id: App::create_uuid(),
+                    from_id: account_id.to_string(),
+                    to_id,
+                    amount: balance,
+                    timestamp: App::create_timestamp(),
+                    node: self.addr.clone(),
+                    idempotency_key: None,
+                    batch_id: None,
+                    from_balance: None,
+                    to_balance: None,
+                })
             }
-            Err(error) => return Err(error),
         };
 
-        let account_to = self.get_account(&transaction.to_id);
+        self.commit(Event::AccountClosed {
+            account_id: account_id.to_string(),
+            sweep: sweep.clone(),
+        })
+        .map_err(|error| AccountError::Storage(error.to_string()))?;
+        self.sessions
+            .retain(|_, session| session.account_id != account_id);
 
-        match account_to {
-            Ok(account) => {
-                account.balance = account.balance + transaction.amount.clone();
+        Ok(ClosedAccount {
+            account: self.accounts[position].clone(),
+            sweep: sweep.map(|transaction| self.stored(transaction)),
+        })
+    }
+
+    /// Olvida las claves de idempotencia usadas antes del periodo de retención
+    fn purge_idempotency_keys(&mut self, now: f64) {
+        let oldest = now - self.idempotency_retention.as_secs_f64();
+        while let Some((key, position)) = self.idempotency_order.front() {
+            if self.transactions[*position].timestamp >= oldest {
+                break;
+            }
+            // La clave se puede haber vuelto a usar después de caducar
+            if self.idempotency_keys.get(key) == Some(position) {
+                self.idempotency_keys.remove(key);
             }
-            Err(error) => return Err(error),
+            self.idempotency_order.pop_front();
         }
-        self.transactions.push(transaction.clone());
+    }
 
-        Ok(transaction)
+    fn account_index(&self, account_id: &str) -> Result<usize, TransferError> {
+        self.account_ids
+            .get(account_id)
+            .copied()
+            .ok_or_else(|| TransferError::AccountNotFound(account_id.to_string()))
     }
 
     /// Static -> Get all transactions stored in App
-    pub fn get_all_transactions(self) -> Vec<Transaction> {
+    pub fn get_all_transactions(&self) -> Vec<Transaction> {
         self.transactions.clone()
     }
 
     // Static -> Get an specific transaction query by ID
-    pub fn get_transaction(self, transaction_id: String) -> Result<Transaction, ()> {
-        for transaction in self.transactions.clone() {
-            if transaction_id == transaction.id {
-                return Ok(transaction);
-            } else {
-                continue;
-            }
+    pub fn get_transaction(&self, transaction_id: &str) -> Result<Transaction, String> {
+        match self.transaction_ids.get(transaction_id) {
+            Some(position) => Ok(self.transactions[*position].clone()),
+            None => Err(format!("Transaction with ID {} not found", transaction_id)),
         }
-        return Err(());
     }
 
-    // Static -> Get an specific transaction query by ID
-    pub fn get_transaction_by_account(self, account_id: String) -> Vec<Transaction> {
-        let mut transactions = Vec::new();
-        for transaction in self.transactions.clone() {
-            if account_id == transaction.from_id {
-                transactions.push(transaction)
-            } else {
-                continue;
-            }
+    /// Transactions sent or received by an account, in creation order
+    fn account_transactions<'a>(
+        &'a self,
+        account_id: &'a str,
+        direction: Direction,
+    ) -> impl Iterator<Item = &'a Transaction> + 'a {
+        self.account_transactions
+            .get(account_id)
+            .into_iter()
+            .flatten()
+            .map(|position| &self.transactions[*position])
+            .filter(move |transaction| direction.matches(transaction, account_id))
+    }
+
+    // Static -> Get the transactions of an account in the given direction
+    pub fn get_transaction_by_account(
+        &self,
+        account_id: &str,
+        direction: Direction,
+    ) -> Vec<Transaction> {
+        self.account_transactions(account_id, direction)
+            .cloned()
+            .collect()
+    }
+
+    /// Static -> Get a page of the transactions matching `query`, optionally
+    /// restricted to those of `account_id`
+    pub fn query_transactions(
+        &self,
+        account_id: Option<&str>,
+        query: &TransactionQuery,
+    ) -> TransactionPage {
+        let transactions: Box<dyn Iterator<Item = &Transaction>> = match account_id {
+            Some(account_id) => Box::new(self.account_transactions(account_id, query.direction)),
+            None => Box::new(self.transactions.iter()),
+        };
+        let mut transactions: Vec<&Transaction> = transactions
+            .filter(|transaction| {
+                query
+                    .since
+                    .is_none_or(|since| transaction.timestamp >= since)
+            })
+            .filter(|transaction| {
+                query
+                    .until
+                    .is_none_or(|until| transaction.timestamp <= until)
+            })
+            .collect();
+
+        transactions.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        if query.order == Order::Descending {
+            transactions.reverse();
+        }
+
+        let total = transactions.len();
+        let transactions = transactions
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.clamp(1, MAX_PAGE_SIZE))
+            .cloned()
+            .collect();
+
+        TransactionPage {
+            total,
+            offset: query.offset,
+            transactions,
         }
-        transactions
     }
 }
 
+/* Consultas de transacciones */
+
+/// Máximo número de transacciones devueltas en una página
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// Transacciones de una cuenta según su sentido
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Enviadas por la cuenta
+    Sent,
+    /// Recibidas por la cuenta
+    Received,
+    /// Enviadas o recibidas por la cuenta
+    #[default]
+    Both,
+}
+
+impl Direction {
+    pub fn matches(&self, transaction: &Transaction, account_id: &str) -> bool {
+        match self {
+            Direction::Sent => transaction.from_id == account_id,
+            Direction::Received => transaction.to_id == account_id,
+            Direction::Both => transaction.from_id == account_id || transaction.to_id == account_id,
+        }
+    }
+}
+
+/// Orden de las transacciones según su fecha
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Filtros, orden y paginación de una consulta de transacciones. Las fechas
+/// son timestamps UNIX en segundos e incluyen los extremos.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct TransactionQuery {
+    pub offset: usize,
+    pub limit: usize,
+    pub since: Option<f64>,
+    pub until: Option<f64>,
+    pub direction: Direction,
+    pub order: Order,
+}
+
+impl Default for TransactionQuery {
+    fn default() -> TransactionQuery {
+        TransactionQuery {
+            offset: 0,
+            limit: MAX_PAGE_SIZE,
+            since: None,
+            until: None,
+            direction: Direction::Both,
+            order: Order::Ascending,
+        }
+    }
+}
+
+/// Página de resultados de una consulta de transacciones. `total` es el
+/// número de transacciones que cumplen los filtros.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionPage {
+    pub total: usize,
+    pub offset: usize,
+    pub transactions: Vec<Transaction>,
+}
+
 /* Protocolo de comuncicación */
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -190,14 +1342,30 @@ pub struct Response {
     pub origin_addr: String,
     pub target_addr: String,
     pub data: String,
-    pub status: u16,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorBody>,
 }
 
 impl Response {
+    /// Datos de la respuesta si la petición ha tenido éxito o su error
+    pub fn into_result(self) -> Result<String, ErrorBody> {
+        match (self.status, self.error) {
+            (Status::Success, _) => Ok(self.data),
+            (_, Some(error)) => Err(error),
+            (status, None) => Err(ErrorBody::new(
+                ErrorCode::Internal,
+                format!("Request failed with status {}", status),
+            )),
+        }
+    }
+
     // Envía una respuesta a una petición
-    pub fn send(&self, stream: &mut TcpStream) {
+    pub fn send<W: io::Write>(&self, stream: &mut W) {
         let json = serde_json::to_string(&self).unwrap().into_bytes();
-        stream.write_all(&json).unwrap();
+        if let Err(error) = write_frame(stream, &json) {
+            tracing::warn!("Unable to send response: {}", error);
+        }
     }
 }
 
@@ -206,34 +1374,162 @@ pub struct Request {
     pub endpoint: String,
     pub origin_addr: String,
     pub target_addr: String,
-    pub data: String,
+    pub data: serde_json::Value,
 }
 
 impl Request {
-    /// Envia una petición y espera su respuesta
-    pub fn send(&self) -> Response {
+    /// Crea una petición para el endpoint y los datos de `body`
+    pub fn new(origin_addr: String, target_addr: String, body: RequestBody) -> Request {
+        Request {
+            endpoint: body.endpoint().to_string(),
+            origin_addr,
+            target_addr,
+            data: body.data(),
+        }
+    }
+
+    /// Interpreta el endpoint y los datos de la petición
+    pub fn body(&self) -> Result<RequestBody, serde_json::Error> {
+        serde_json::from_value(serde_json::json!({
+            "endpoint": self.endpoint,
+            "data": self.data,
+        }))
+    }
+
+    /// Envia una petición y espera su respuesta. `timeout` limita la
+    /// conexión y cada escritura y lectura.
+    pub fn send(&self, timeout: Duration) -> io::Result<Response> {
         //Se preocesa la petición
-        let json = serde_json::to_string(&self.clone()).unwrap().into_bytes();
+        let json = serde_json::to_string(&self.clone())?.into_bytes();
 
         // Creamos la conexión y enviamos la petición
-        let mut stream = TcpStream::connect(&self.target_addr).unwrap();
-        stream.write_all(&json).unwrap();
-        // Esperamos la respuesta del nodo
-        let mut buf = [0; 1024];
-        let n = stream.read(&mut buf).unwrap();
+        let mut stream = connect(&self.target_addr, timeout)?;
+        write_frame(&mut stream, &json)?;
+
+        // Esperamos la respuesta completa del nodo
+        let frame = read_frame(&mut stream, MAX_FRAME_SIZE)?;
 
         // Procesameos la respuesta del servidor y se devuelve un objeto respuesta
-        let str = String::from_utf8(buf[..n].to_vec()).unwrap();
-        let response: Response = serde_json::from_str(&str).unwrap();
-        response
+        let response: Response = serde_json::from_slice(&frame)?;
+        Ok(response)
+    }
+}
+
+/// Conecta con `addr` en menos de `timeout`, probando cada una de sus
+/// direcciones, y aplica el mismo tiempo máximo a las lecturas y escrituras
+pub(crate) fn connect(addr: &str, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for socket_addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_addr, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                return Ok(stream);
+            }
+            Err(error) => last_error = Some(error),
+        }
     }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} does not resolve to any address", addr),
+        )
+    }))
 }
 
 /* Estructuras de datos */
 
+/// Datos de la petición a un endpoint. `ENDPOINT` es el nombre con el que
+/// viaja en `Request.endpoint`.
+pub trait Payload: Serialize + serde::de::DeserializeOwned {
+    const ENDPOINT: &'static str;
+}
+
+/// Peticiones admitidas por el servidor junto con sus datos
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "endpoint", content = "data")]
+pub enum RequestBody {
+    CreateAccount(CreateAccountData),
+    Login(LoginData),
+    GetAccount(GetAccountData),
+    CreateTransaction(CreateTransactionData),
+    ListTransactions(ListTransactionsData),
+    GetTransaction(GetTransactionData),
+    GetAccountTransactions(GetAccountTransactionsData),
+    RenameAccount(RenameAccountData),
+    FreezeAccount(FreezeAccountData),
+    UnfreezeAccount(UnfreezeAccountData),
+    CloseAccount(CloseAccountData),
+    CreateBatchTransaction(CreateBatchTransactionData),
+    ScheduleTransfer(ScheduleTransferData),
+    ListScheduledTransfers(ListScheduledTransfersData),
+    CancelScheduledTransfer(CancelScheduledTransferData),
+    GetStatement(GetStatementData),
+}
+
+impl RequestBody {
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            RequestBody::CreateAccount(_) => CreateAccountData::ENDPOINT,
+            RequestBody::Login(_) => LoginData::ENDPOINT,
+            RequestBody::GetAccount(_) => GetAccountData::ENDPOINT,
+            RequestBody::CreateTransaction(_) => CreateTransactionData::ENDPOINT,
+            RequestBody::ListTransactions(_) => ListTransactionsData::ENDPOINT,
+            RequestBody::GetTransaction(_) => GetTransactionData::ENDPOINT,
+            RequestBody::GetAccountTransactions(_) => GetAccountTransactionsData::ENDPOINT,
+            RequestBody::RenameAccount(_) => RenameAccountData::ENDPOINT,
+            RequestBody::FreezeAccount(_) => FreezeAccountData::ENDPOINT,
+            RequestBody::UnfreezeAccount(_) => UnfreezeAccountData::ENDPOINT,
+            RequestBody::CloseAccount(_) => CloseAccountData::ENDPOINT,
+            RequestBody::CreateBatchTransaction(_) => CreateBatchTransactionData::ENDPOINT,
+            RequestBody::ScheduleTransfer(_) => ScheduleTransferData::ENDPOINT,
+            RequestBody::ListScheduledTransfers(_) => ListScheduledTransfersData::ENDPOINT,
+            RequestBody::CancelScheduledTransfer(_) => CancelScheduledTransferData::ENDPOINT,
+            RequestBody::GetStatement(_) => GetStatementData::ENDPOINT,
+        }
+    }
+
+    pub fn data(&self) -> serde_json::Value {
+        match self {
+            RequestBody::CreateAccount(data) => serde_json::to_value(data),
+            RequestBody::Login(data) => serde_json::to_value(data),
+            RequestBody::GetAccount(data) => serde_json::to_value(data),
+            RequestBody::CreateTransaction(data) => serde_json::to_value(data),
+            RequestBody::ListTransactions(data) => serde_json::to_value(data),
+            RequestBody::GetTransaction(data) => serde_json::to_value(data),
+            RequestBody::GetAccountTransactions(data) => serde_json::to_value(data),
+            RequestBody::RenameAccount(data) => serde_json::to_value(data),
+            RequestBody::FreezeAccount(data) => serde_json::to_value(data),
+            RequestBody::UnfreezeAccount(data) => serde_json::to_value(data),
+            RequestBody::CloseAccount(data) => serde_json::to_value(data),
+            RequestBody::CreateBatchTransaction(data) => serde_json::to_value(data),
+            RequestBody::ScheduleTransfer(data) => serde_json::to_value(data),
+            RequestBody::ListScheduledTransfers(data) => serde_json::to_value(data),
+            RequestBody::CancelScheduledTransfer(data) => serde_json::to_value(data),
+            RequestBody::GetStatement(data) => serde_json::to_value(data),
+        }
+        .unwrap()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CreateAccountData {
     pub username: String,
+    pub password: String,
+}
+
+impl Payload for CreateAccountData {
+    const ENDPOINT: &'static str = "CreateAccount";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoginData {
+    pub username: String,
+    pub password: String,
+}
+
+impl Payload for LoginData {
+    const ENDPOINT: &'static str = "Login";
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -241,11 +1537,165 @@ pub struct GetAccountData {
     pub account_id: String,
 }
 
+impl Payload for GetAccountData {
+    const ENDPOINT: &'static str = "GetAccount";
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CreateTransactionData {
+    /// Token de una sesión de la cuenta origen
+    pub token: String,
+    pub from_id: String,
+    pub to_id: String,
+    pub amount: String,
+    /// Clave para repetir la petición sin duplicar la transferencia
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+}
+
+impl Payload for CreateTransactionData {
+    const ENDPOINT: &'static str = "CreateTransaction";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateBatchTransactionData {
+    /// Token de una sesión de la cuenta origen
+    pub token: String,
+    pub from_id: String,
+    pub legs: Vec<TransferLeg>,
+}
+
+impl Payload for CreateBatchTransactionData {
+    const ENDPOINT: &'static str = "CreateBatchTransaction";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleTransferData {
+    /// Token de una sesión de la cuenta origen
+    pub token: String,
     pub from_id: String,
     pub to_id: String,
     pub amount: String,
+    /// UNIX timestamp de la primera ejecución
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execute_at: Option<f64>,
+    /// Segundos entre ejecuciones, si la transferencia se repite
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<u64>,
+}
+
+impl Payload for ScheduleTransferData {
+    const ENDPOINT: &'static str = "ScheduleTransfer";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListScheduledTransfersData {
+    pub token: String,
+    pub account_id: String,
+}
+
+impl Payload for ListScheduledTransfersData {
+    const ENDPOINT: &'static str = "ListScheduledTransfers";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CancelScheduledTransferData {
+    pub token: String,
+    pub schedule_id: String,
+}
+
+impl Payload for CancelScheduledTransferData {
+    const ENDPOINT: &'static str = "CancelScheduledTransfer";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetStatementData {
+    pub token: String,
+    pub account_id: String,
+    #[serde(flatten)]
+    pub query: StatementQuery,
+}
+
+impl Payload for GetStatementData {
+    const ENDPOINT: &'static str = "GetStatement";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ListTransactionsData {
+    #[serde(flatten)]
+    pub query: TransactionQuery,
+}
+
+impl Payload for ListTransactionsData {
+    const ENDPOINT: &'static str = "ListTransactions";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetTransactionData {
+    pub transaction_id: String,
+}
+
+impl Payload for GetTransactionData {
+    const ENDPOINT: &'static str = "GetTransaction";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetAccountTransactionsData {
+    pub account_id: String,
+    #[serde(flatten)]
+    pub query: TransactionQuery,
+}
+
+impl Payload for GetAccountTransactionsData {
+    const ENDPOINT: &'static str = "GetAccountTransactions";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RenameAccountData {
+    /// Token de una sesión de la cuenta
+    pub token: String,
+    pub account_id: String,
+    pub username: String,
+}
+
+impl Payload for RenameAccountData {
+    const ENDPOINT: &'static str = "RenameAccount";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FreezeAccountData {
+    /// Token de una sesión de la cuenta
+    pub token: String,
+    pub account_id: String,
+}
+
+impl Payload for FreezeAccountData {
+    const ENDPOINT: &'static str = "FreezeAccount";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnfreezeAccountData {
+    /// Token de una sesión de la cuenta
+    pub token: String,
+    pub account_id: String,
+}
+
+impl Payload for UnfreezeAccountData {
+    const ENDPOINT: &'static str = "UnfreezeAccount";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CloseAccountData {
+    /// Token de una sesión de la cuenta
+    pub token: String,
+    pub account_id: String,
+    /// Cuenta a la que se transfiere el saldo restante
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sweep_to: Option<String>,
+}
+
+impl Payload for CloseAccountData {
+    const ENDPOINT: &'static str = "CloseAccount";
 }
 
 /* Conexión concurrente basado en un Pool de hilos*/
@@ -293,10 +1743,19 @@ impl Drop for ThreadPool {
         drop(self.sender.take());
 
         for worker in &mut self.workers {
-            println!("Shutting down worker {}", worker.id);
+            tracing::debug!("Shutting down worker {}", worker.id);
 
+            // Un trabajo que entra en pánico termina su worker, pero no debe
+            // convertir el cierre del pool en otro pánico
             if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+                if let Err(panic) = thread.join() {
+                    let message = panic
+                        .downcast_ref::<&str>()
+                        .copied()
+                        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                        .unwrap_or("unknown panic");
+                    tracing::error!("Worker {} panicked: {}", worker.id, message);
+                }
             }
         }
     }
@@ -313,12 +1772,9 @@ impl Worker {
             let message = receiver.lock().unwrap().recv();
 
             match message {
-                Ok(job) => {
-                    println!("Thread {} spawned", id);
-                    job()
-                }
+                Ok(job) => job(),
                 Err(_) => {
-                    println!("Worker {id} disconnected; shutting down.");
+                    tracing::debug!("Worker {id} disconnected; shutting down.");
                     break;
                 }
             }
@@ -1,12 +1,13 @@
 use serde::{Deserialize, Serialize};
 
+use crate::framing::{read_frame, write_frame, MAX_FRAME_SIZE};
+use crate::{connect, DEFAULT_TIMEOUT};
 use std::{
     collections::HashSet,
-    io::{Read, Write},
     net::TcpStream,
     sync::{Arc,Mutex},
     thread,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use uuid::Uuid;
@@ -51,18 +52,19 @@ fn make_response(
         action,
         data: data.to_string(),
     };
-    let json = serde_json::to_string(&req).unwrap().into_bytes();
+    let json = serde_json::to_string(&req)?.into_bytes();
     // Envio del mensaje al nodo
-    stream.write_all(&json).unwrap();
+    write_frame(stream, &json)?;
 
     Ok(())
 }
 
 fn make_request(
-    to_addr: &String,
+    to_addr: &str,
     entity: Entity,
     action: Action,
     data: String,
+    timeout: Duration,
 ) -> Result<Request, Box<dyn std::error::Error>> {
     // Genera una petición para desde un nodo/cliente a otro nodo
     let req = Request {
@@ -70,36 +72,42 @@ fn make_request(
         action,
         data: data.to_string(),
     };
-    let json = serde_json::to_string(&req).unwrap().into_bytes();
+    let json = serde_json::to_string(&req)?.into_bytes();
 
-    // Envio del mensaje al nodo
-    let mut stream = TcpStream::connect(to_addr).unwrap();
-    stream.write_all(&json).unwrap();
+    // Envio del mensaje al nodo. Con el timeout un nodo que no responde no
+    // bloquea al que le llama
+    let mut stream = connect(to_addr, timeout)?;
+    write_frame(&mut stream, &json)?;
 
-    // Esperamos la respuesta del nodo
-    let mut buf = [0; 1024];
-    let n = stream.read(&mut buf).unwrap();
+    // Esperamos la respuesta completa del nodo
+    let frame = read_frame(&mut stream, MAX_FRAME_SIZE)?;
 
     // Procesameos la respuesta del nodo
-    let string = String::from_utf8(buf[..n].to_vec()).unwrap();
-    let response: Request = serde_json::from_str(&string).unwrap();
+    let response: Request = serde_json::from_slice(&frame)?;
     Ok(response)
 }
 
-pub fn distrbute_item(addr:String, nodes:HashSet<String>, data: String) {
+/// Lee una petición completa enviada por un nodo/cliente
+pub fn receive_request(stream: &mut TcpStream) -> Result<Request, Box<dyn std::error::Error>> {
+    let frame = read_frame(stream, MAX_FRAME_SIZE)?;
+    let request: Request = serde_json::from_slice(&frame)?;
+    Ok(request)
+}
+
+pub fn distrbute_item(addr:String, nodes:HashSet<String>, data: String, timeout: Duration) {
     //Distribuye por la red de nodos un item de manera secuencial.
 
     for node in nodes{
         if node == addr {
             continue;
         }
-        println!("Data sending to {}", &node);
-        match make_request(&node, Entity::NODE, Action::CREATE, data.clone()) {
+        tracing::debug!(%node, "Sending item");
+        match make_request(&node, Entity::NODE, Action::CREATE, data.clone(), timeout) {
             Ok(_) => {
-                println!("Data send to {}", &node);
+                tracing::info!(%node, "Item sent");
             }
             Err(error) => {
-                println!("{}", error)
+                tracing::warn!("{}", error)
             }
         }
     }
@@ -111,27 +119,40 @@ pub struct Node {
     pub register_addr: String,
     pub nodes: Arc<Mutex<HashSet<String>>>,
     pub storage: Vec<Item>,
+    timeout: Duration,
 }
 
 impl Node {
-    pub fn new(addr: &String, register_addr: &String) -> Self {
+    /// Nodo que escucha en `addr` y se registra en la red a través del nodo
+    /// de `register_addr`. Acepta `&str`, por lo que se puede llamar tanto
+    /// con literales como con `&String`.
+    pub fn new(addr: &str, register_addr: &str) -> Self {
         let node = Node {
-            addr: addr.clone(),
-            register_addr: register_addr.clone(),
+            addr: addr.to_string(),
+            register_addr: register_addr.to_string(),
             nodes: Arc::new(Mutex::new(HashSet::new())),
             storage: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
         };
 
-        node.nodes.lock().unwrap().insert(addr.clone());
+        node.nodes.lock().unwrap().insert(addr.to_string());
         node
     }
 
+    /// Tiempo máximo para conectar con otro nodo, enviarle una petición y
+    /// recibir su respuesta. Por defecto es [`DEFAULT_TIMEOUT`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Node {
+        self.timeout = timeout;
+        self
+    }
+
     pub fn register(&mut self) {
         match make_request(
             &self.register_addr,
             Entity::NODE,
             Action::REGISTER,
             self.addr.clone(),
+            self.timeout,
         ) {
             Ok(res) => {
                 let nodes: HashSet<String> = serde_json::from_str(&res.data).unwrap();
@@ -142,9 +163,9 @@ impl Node {
                         continue;
                     }
                 }
-                println!("Node register successfully");
+                tracing::info!(addr = %self.addr, "Node registered");
             }
-            Err(error) => println!("{}", error),
+            Err(error) => tracing::warn!("{}", error),
         }
     }
 
@@ -189,7 +210,7 @@ impl Node {
         match request.action {
             Action::CREATE => {
                 // Crea un item
-                println!("Creating a new item sent by a client");
+                tracing::info!("Creating a new item sent by a client");
                 let content = request.data.clone();
 
                 match self.create_item(content) {
@@ -204,17 +225,18 @@ impl Node {
                         match make_response(stream, Entity::NODE, Action::SUCCESS, data.clone()) {
                             Ok(_) => {}
                             Err(error) => {
-                                println!("{}", error);
+                                tracing::warn!("{}", error);
                             }
                         };
 
                         let nodes = self.nodes.lock().unwrap().clone();
                         let addr = self.addr.clone();
+                        let timeout = self.timeout;
 
-                        thread::spawn(move || distrbute_item(addr, nodes, data.clone()));
+                        thread::spawn(move || distrbute_item(addr, nodes, data.clone(), timeout));
                     }
                     Err(error) => {
-                        println!("{}", error);
+                        tracing::warn!("{}", error);
                     }
                 }
             }
@@ -223,7 +245,7 @@ impl Node {
                 // Recupera un Item concreto.
 
                 let id = request.data;
-                println!("Retrieving a item requested by a client => {}", &id);
+                tracing::info!(item_id = %id, "Retrieving an item requested by a client");
 
                 match self.retrieve_item(&id) {
 
@@ -234,12 +256,12 @@ impl Node {
                                 //
                             }
                             Err(error) => {
-                                println!("{}", error);
+                                tracing::warn!("{}", error);
                             }
                         };
                     }
                     Err(error) => {
-                        println!("{}", error);
+                        tracing::warn!("{}", error);
                     }
                 }
             }
@@ -265,27 +287,27 @@ impl Node {
 
                 match make_response(stream, Entity::NODE, Action::SUCCESS, data) {
                     Ok(_) => {
-                        println!("Node {} register successfuully", request.data);
+                        tracing::info!(node = %request.data, "Node registered");
                         //Nada
                     }
                     Err(error) => {
-                        println!("{}", error);
+                        tracing::warn!("{}", error);
                     }
                 }
             }
 
             Action::CREATE => {
                 // Crea el item
-                println!("Creating an item from another node");
+                tracing::info!("Creating an item from another node");
                 let item: Item = serde_json::from_str(&request.data).unwrap();
                 self.storage.push(item.clone());
 
                 match make_response(stream, Entity::NODE, Action::SUCCESS, request.data.clone()) {
                     Ok(_) => {
-                        println!("Item {} created successfuully", item.id);
+                        tracing::info!(item_id = %item.id, "Item created");
                     }
                     Err(error) => {
-                        println!("{}", error);
+                        tracing::warn!("{}", error);
                     }
                 }
             }
@@ -0,0 +1,71 @@
+//! Enrutado de las peticiones a la función que gestiona cada endpoint.
+
+use crate::status::{ErrorBody, ErrorCode};
+use crate::{App, Payload, Request};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Resultado de gestionar una petición: los datos de la respuesta o su error
+pub type Outcome = Result<String, ErrorBody>;
+
+type Route = Box<dyn Fn(&Mutex<App>, serde_json::Value) -> Outcome + Send + Sync>;
+
+/// Asocia cada endpoint con la función que lo gestiona
+#[derive(Default)]
+pub struct Router {
+    routes: HashMap<&'static str, Route>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Registra `handler` para el endpoint de sus datos, `P::ENDPOINT`. Se
+    /// ejecuta con la App bloqueada.
+    pub fn register<P, F>(&mut self, handler: F) -> &mut Router
+    where
+        P: Payload + 'static,
+        F: Fn(&mut App, P) -> Outcome + Send + Sync + 'static,
+    {
+        self.register_unlocked(move |app: &Mutex<App>, data: P| {
+            handler(&mut app.lock().unwrap(), data)
+        })
+    }
+
+    /// Como `register`, pero `handler` bloquea la App solo cuando la usa. Es
+    /// para los endpoints con trabajo lento, como el hash de las contraseñas,
+    /// que no debe hacer esperar al resto de peticiones.
+    pub fn register_unlocked<P, F>(&mut self, handler: F) -> &mut Router
+    where
+        P: Payload + 'static,
+        F: Fn(&Mutex<App>, P) -> Outcome + Send + Sync + 'static,
+    {
+        let route =
+            move |app: &Mutex<App>, data: serde_json::Value| match serde_json::from_value(data) {
+                Ok(data) => handler(app, data),
+                Err(error) => Err(ErrorBody::new(
+                    ErrorCode::InvalidPayload,
+                    format!("Request Data is not valid: {}", error),
+                )),
+            };
+        self.routes.insert(P::ENDPOINT, Box::new(route));
+        self
+    }
+
+    /// Ejecuta la función registrada para el endpoint de la petición
+    pub fn dispatch(&self, app: &Mutex<App>, request: Request) -> Outcome {
+        match self.routes.get(request.endpoint.as_str()) {
+            Some(route) => route(app, request.data),
+            None => Err(ErrorBody::new(
+                ErrorCode::UnknownEndpoint,
+                format!("{} endpoint not found", request.endpoint),
+            )),
+        }
+    }
+
+    /// Endpoints registrados
+    pub fn endpoints(&self) -> Vec<&'static str> {
+        self.routes.keys().copied().collect()
+    }
+}
@@ -0,0 +1,402 @@
+//! Transferencias programadas.
+//!
+//! Una cuenta puede programar una transferencia para un momento futuro o para
+//! que se repita cada cierto intervalo. El [`Scheduler`] es un hilo del
+//! servidor que ejecuta las que han vencido. El resultado de cada ejecución,
+//! la transacción creada o el motivo del fallo, se guarda como un único
+//! evento, de manera que tras reiniciar no se repite ninguna.
+
+use crate::auth::AuthError;
+use crate::storage::Event;
+use crate::{App, Transaction, TransferError};
+use coliseum_money::Amount;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Cada cuánto comprueba el scheduler si hay transferencias pendientes
+pub const DEFAULT_SCHEDULER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Fallos seguidos tras los que una transferencia periódica deja de intentarse
+pub const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+
+/// Estado de una transferencia programada
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleStatus {
+    /// Pendiente de su próxima ejecución
+    Active,
+    /// Transferencia única que ya se ha hecho
+    Completed,
+    /// Transferencia única que no se ha podido hacer, o periódica que ha
+    /// fallado [`MAX_CONSECUTIVE_FAILURES`] veces seguidas
+    Failed,
+    Cancelled,
+}
+
+/// Ejecución fallida de una transferencia programada
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScheduleFailure {
+    pub timestamp: f64,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledTransfer {
+    pub id: String,
+    pub from_id: String,
+    pub to_id: String,
+    pub amount: Amount,
+    pub created_time: f64,
+    /// Momento de la próxima ejecución
+    pub next_run: f64,
+    /// Segundos entre ejecuciones de una transferencia periódica
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<u64>,
+    pub status: ScheduleStatus,
+    /// Transacciones creadas por las ejecuciones
+    #[serde(default)]
+    pub transaction_ids: Vec<String>,
+    /// Últimos fallos, como mucho [`MAX_CONSECUTIVE_FAILURES`]
+    #[serde(default)]
+    pub failures: Vec<ScheduleFailure>,
+    /// Fallos desde la última ejecución correcta
+    #[serde(default)]
+    pub consecutive_failures: u32,
+}
+
+/// Resultado de ejecutar una transferencia programada
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum RunResult {
+    Executed(Transaction),
+    Failed(String),
+}
+
+/// Ejecución de una transferencia programada, tal como se guarda en el log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledRun {
+    pub schedule_id: String,
+    pub timestamp: f64,
+    pub result: RunResult,
+}
+
+impl App {
+    /// Dynamic -> Schedule a transfer with a session token of the sending
+    /// account. It runs once at `execute_at` or, with `interval_secs`, every
+    /// `interval_secs` starting at `execute_at` or one interval from now.
+    pub fn schedule_transfer(
+        &mut self,
+        token: &str,
+        from_id: String,
+        to_id: String,
+        amount: String,
+        execute_at: Option<f64>,
+        interval_secs: Option<u64>,
+    ) -> Result<ScheduledTransfer, TransferError> {
+        let account_id = self
+            .authenticate(token)
+            .map_err(TransferError::Unauthorized)?;
+        if account_id != from_id {
+            return Err(TransferError::Unauthorized(AuthError::Forbidden));
+        }
+
+        let amount = Amount::parse(&amount, self.decimals)
+            .and_then(Amount::positive)
+            .map_err(TransferError::InvalidAmount)?;
+        if from_id == to_id {
+            return Err(TransferError::SelfTransfer);
+        }
+        let from_index = self.account_index(&from_id)?;
+        let to_index = self.account_index(&to_id)?;
+        // El saldo se comprueba en cada ejecución, pero una cuenta congelada o
+        // cerrada haría fallar todas
+        self.check_active(from_index)?;
+        self.check_active(to_index)?;
+
+        let now = App::create_timestamp();
+        let next_run = match (execute_at, interval_secs) {
+            (_, Some(0)) => {
+                return Err(TransferError::InvalidSchedule(
+                    "interval_secs must be at least 1".to_string(),
+                ))
+            }
+            (Some(execute_at), _) if !execute_at.is_finite() || execute_at < now => {
+                return Err(TransferError::InvalidSchedule(
+                    "execute_at must be in the future".to_string(),
+                ))
+            }
+            (Some(execute_at), _) => execute_at,
+            (None, Some(interval)) => now + interval as f64,
+            (None, None) => {
+                return Err(TransferError::InvalidSchedule(
+                    "execute_at or interval_secs is required".to_string(),
+                ))
+            }
+        };
+
+        let schedule = ScheduledTransfer {
+            id: App::create_uuid(),
+            from_id,
+            to_id,
+            amount,
+            created_time: now,
+            next_run,
+            interval_secs,
+            status: ScheduleStatus::Active,
+            transaction_ids: Vec::new(),
+            failures: Vec::new(),
+            consecutive_failures: 0,
+        };
+        self.commit(Event::TransferScheduled(schedule.clone()))
+            .map_err(|error| TransferError::Storage(error.to_string()))?;
+        Ok(schedule)
+    }
+
+    /// Dynamic -> Scheduled transfers sent by an account, with a session
+    /// token of the account
+    pub fn list_scheduled_transfers(
+        &mut self,
+        token: &str,
+        account_id: &str,
+    ) -> Result<Vec<ScheduledTransfer>, TransferError> {
+        let session_account = self
+            .authenticate(token)
+            .map_err(TransferError::Unauthorized)?;
+        if session_account != account_id {
+            return Err(TransferError::Unauthorized(AuthError::Forbidden));
+        }
+
+        Ok(self
+            .scheduled_transfers
+            .iter()
+            .filter(|schedule| schedule.from_id == account_id)
+            .cloned()
+            .collect())
+    }
+
+    /// Dynamic -> Cancel a scheduled transfer with a session token of the
+    /// sending account. Transfers that are no longer active are left as
+    /// they are.
+    pub fn cancel_scheduled_transfer(
+        &mut self,
+        token: &str,
+        schedule_id: &str,
+    ) -> Result<ScheduledTransfer, TransferError> {
+        let account_id = self
+            .authenticate(token)
+            .map_err(TransferError::Unauthorized)?;
+        let position = self.schedule_index(schedule_id)?;
+        if self.scheduled_transfers[position].from_id != account_id {
+            return Err(TransferError::Unauthorized(AuthError::Forbidden));
+        }
+
+        if self.scheduled_transfers[position].status == ScheduleStatus::Active {
+            self.commit(Event::ScheduledTransferCancelled {
+                schedule_id: schedule_id.to_string(),
+            })
+            .map_err(|error| TransferError::Storage(error.to_string()))?;
+        }
+        Ok(self.scheduled_transfers[position].clone())
+    }
+
+    /// Ejecuta las transferencias activas que vencen antes de `now`, por orden
+    /// de vencimiento. Una transferencia periódica que lleva varios intervalos
+    /// sin ejecutarse, por ejemplo con el servidor parado, se ejecuta una vez.
+    pub fn run_scheduled_transfers(&mut self, now: f64) -> io::Result<Vec<ScheduledRun>> {
+        let mut runs = Vec::new();
+        for schedule_id in self.due_scheduled_transfers(now) {
+            runs.extend(self.run_scheduled_transfer(&schedule_id, now)?);
+        }
+        Ok(runs)
+    }
+
+    /// IDs de las transferencias activas que vencen antes de `now`, por orden
+    /// de vencimiento
+    pub fn due_scheduled_transfers(&self, now: f64) -> Vec<String> {
+        let mut due: Vec<&ScheduledTransfer> = self
+            .scheduled_transfers
+            .iter()
+            .filter(|schedule| {
+                schedule.status == ScheduleStatus::Active && schedule.next_run <= now
+            })
+            .collect();
+        due.sort_by(|a, b| a.next_run.total_cmp(&b.next_run));
+        due.into_iter()
+            .map(|schedule| schedule.id.clone())
+            .collect()
+    }
+
+    /// Ejecuta una transferencia programada si sigue activa y ha vencido antes
+    /// de `now`. Se valida como una transferencia de `create_transaction`.
+    pub fn run_scheduled_transfer(
+        &mut self,
+        schedule_id: &str,
+        now: f64,
+    ) -> io::Result<Option<ScheduledRun>> {
+        let Some(position) = self.scheduled_ids.get(schedule_id).copied() else {
+            return Ok(None);
+        };
+        let schedule = &self.scheduled_transfers[position];
+        if schedule.status != ScheduleStatus::Active || schedule.next_run > now {
+            return Ok(None);
+        }
+
+        let result = match self.prepare_transfer(
+            schedule.from_id.clone(),
+            schedule.to_id.clone(),
+            schedule.amount,
+            None,
+        ) {
+            Ok(transaction) => RunResult::Executed(transaction),
+            Err(error) => RunResult::Failed(error.to_string()),
+        };
+        let run = ScheduledRun {
+            schedule_id: schedule.id.clone(),
+            timestamp: now,
+            result,
+        };
+        self.commit(Event::ScheduledTransferRan(run.clone()))?;
+        Ok(Some(run))
+    }
+
+    /// Scheduled transfers in creation order
+    pub fn scheduled_transfers(&self) -> &[ScheduledTransfer] {
+        &self.scheduled_transfers
+    }
+
+    pub(crate) fn apply_schedule(&mut self, schedule: ScheduledTransfer) -> Result<(), String> {
+        if self.scheduled_ids.contains_key(&schedule.id) {
+            return Err(format!("Duplicated scheduled transfer {}", schedule.id));
+        }
+        self.scheduled_ids
+            .insert(schedule.id.clone(), self.scheduled_transfers.len());
+        self.scheduled_transfers.push(schedule);
+        Ok(())
+    }
+
+    pub(crate) fn apply_scheduled_run(
+        &mut self,
+        run: ScheduledRun,
+        update_balances: bool,
+    ) -> Result<(), String> {
+        let position = self
+            .schedule_index(&run.schedule_id)
+            .map_err(|error| error.to_string())?;
+        let succeeded = match run.result {
+            RunResult::Executed(transaction) => {
+                let transaction_id = transaction.id.clone();
+                self.apply_transaction(transaction, update_balances)?;
+                let schedule = &mut self.scheduled_transfers[position];
+                schedule.transaction_ids.push(transaction_id);
+                schedule.consecutive_failures = 0;
+                true
+            }
+            RunResult::Failed(error) => {
+                let schedule = &mut self.scheduled_transfers[position];
+                schedule.failures.push(ScheduleFailure {
+                    timestamp: run.timestamp,
+                    error,
+                });
+                schedule.consecutive_failures += 1;
+                if schedule.failures.len() > MAX_CONSECUTIVE_FAILURES as usize {
+                    let excess = schedule.failures.len() - MAX_CONSECUTIVE_FAILURES as usize;
+                    schedule.failures.drain(..excess);
+                }
+                false
+            }
+        };
+
+        let schedule = &mut self.scheduled_transfers[position];
+        match schedule.interval_secs {
+            Some(_) if schedule.consecutive_failures >= MAX_CONSECUTIVE_FAILURES => {
+                schedule.status = ScheduleStatus::Failed
+            }
+            // La siguiente ejecución es la primera posterior a esta
+            Some(interval) => {
+                let interval = interval as f64;
+                let missed = ((run.timestamp - schedule.next_run) / interval)
+                    .floor()
+                    .max(0.0);
+                schedule.next_run += (missed + 1.0) * interval;
+            }
+            None if succeeded => schedule.status = ScheduleStatus::Completed,
+            None => schedule.status = ScheduleStatus::Failed,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_schedule_cancelled(&mut self, schedule_id: &str) -> Result<(), String> {
+        let position = self
+            .schedule_index(schedule_id)
+            .map_err(|error| error.to_string())?;
+        self.scheduled_transfers[position].status = ScheduleStatus::Cancelled;
+        Ok(())
+    }
+
+    fn schedule_index(&self, schedule_id: &str) -> Result<usize, TransferError> {
+        self.scheduled_ids
+            .get(schedule_id)
+            .copied()
+            .ok_or_else(|| TransferError::ScheduleNotFound(schedule_id.to_string()))
+    }
+}
+
+/// Hilo que ejecuta las transferencias programadas de una App
+pub struct Scheduler {
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl Scheduler {
+    /// Arranca el hilo, que comprueba cada `interval` si hay transferencias
+    /// pendientes
+    pub fn spawn(app: Arc<Mutex<App>>, interval: Duration) -> Scheduler {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                run_due(&app);
+            }
+        });
+        Scheduler { stop, thread }
+    }
+
+    /// Detiene el hilo y espera a que termine la ejecución en curso
+    pub fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+    }
+}
+
+fn run_due(app: &Mutex<App>) {
+    let now = App::create_timestamp();
+    let due = app.lock().unwrap().due_scheduled_transfers(now);
+    for schedule_id in due {
+        // La App se bloquea para cada transferencia y no para todas, de manera
+        // que las peticiones no esperan a que se guarden todas
+        let run = app
+            .lock()
+            .unwrap()
+            .run_scheduled_transfer(&schedule_id, now);
+        match run {
+            Ok(Some(run)) => match run.result {
+                RunResult::Executed(transaction) => info!(
+                    schedule_id = %run.schedule_id,
+                    transaction_id = %transaction.id,
+                    "Scheduled transfer executed"
+                ),
+                RunResult::Failed(error) => warn!(
+                    schedule_id = %run.schedule_id,
+                    "Scheduled transfer failed: {}",
+                    error
+                ),
+            },
+            // Cancelada entre tanto
+            Ok(None) => {}
+            Err(error) => error!("Unable to store scheduled transfer: {}", error),
+        }
+    }
+}
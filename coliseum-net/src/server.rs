@@ -0,0 +1,486 @@
+use crate::auth::AuthError;
+use crate::framing::{is_frame_too_large, read_frame, MAX_FRAME_SIZE};
+use crate::router::{Outcome, Router};
+use crate::status::{ErrorBody, ErrorCode, Status};
+use crate::{
+    AccountError, App, CancelScheduledTransferData, CloseAccountData, CreateAccountData,
+    CreateBatchTransactionData, CreateTransactionData, FreezeAccountData, GetAccountData,
+    GetAccountTransactionsData, GetStatementData, GetTransactionData, ListScheduledTransfersData,
+    ListTransactionsData, LoginData, RenameAccountData, Request, Response, ScheduleTransferData,
+    ThreadPool, Transaction, TransactionPage, UnfreezeAccountData,
+};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, info_span, warn, Span};
+use uuid::Uuid;
+
+/// Tiempo máximo que se espera a que un cliente envíe su petición
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Crea una cuenta. El hash de la contraseña se calcula sin bloquear la App.
+fn create_account(app: &Mutex<App>, data: CreateAccountData) -> Outcome {
+    let auth = {
+        let app = app.lock().unwrap();
+        app.check_new_account(&data.username, &data.password)?;
+        app.auth.clone()
+    };
+    let password_hash = auth
+        .hash_password(&data.password)
+        .map_err(AccountError::Internal)?;
+
+    app.lock()
+        .unwrap()
+        .insert_account(data.username, password_hash)
+        .map_err(ErrorBody::from)
+}
+
+/// Inicia una sesión y devuelve su token. La contraseña se comprueba sin
+/// bloquear la App.
+fn login(app: &Mutex<App>, data: LoginData) -> Outcome {
+    let (auth, credentials) = {
+        let app = app.lock().unwrap();
+        (app.auth.clone(), app.credentials_of(&data.username))
+    };
+    let hash = credentials.as_ref().map(|(_, hash)| hash.as_str());
+    if !auth.check_password(hash, &data.password) {
+        return Err(AuthError::InvalidCredentials.into());
+    }
+
+    let (account_id, _) = credentials.unwrap();
+    match app.lock().unwrap().start_session(account_id) {
+        Ok(session) => Ok(serde_json::to_string(&session).unwrap()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Obtiene una cuenta
+fn get_account(app: &mut App, data: GetAccountData) -> Outcome {
+    match app.get_account(&data.account_id) {
+        Ok(account) => Ok(serde_json::to_string(account).unwrap()),
+        Err(error) => Err(ErrorBody::new(ErrorCode::AccountNotFound, error)),
+    }
+}
+
+/// Cambia el nombre de usuario de una cuenta
+fn rename_account(app: &mut App, data: RenameAccountData) -> Outcome {
+    match app.rename_account(&data.token, &data.account_id, data.username) {
+        Ok(account) => Ok(serde_json::to_string(&account).unwrap()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Congela una cuenta
+fn freeze_account(app: &mut App, data: FreezeAccountData) -> Outcome {
+    match app.freeze_account(&data.token, &data.account_id) {
+        Ok(account) => Ok(serde_json::to_string(&account).unwrap()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Reactiva una cuenta congelada
+fn unfreeze_account(app: &mut App, data: UnfreezeAccountData) -> Outcome {
+    match app.unfreeze_account(&data.token, &data.account_id) {
+        Ok(account) => Ok(serde_json::to_string(&account).unwrap()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Cierra una cuenta, transfiriendo antes su saldo si se indica a dónde
+fn close_account(app: &mut App, data: CloseAccountData) -> Outcome {
+    match app.close_account(&data.token, &data.account_id, data.sweep_to) {
+        Ok(mut closed) => {
+            closed.sweep = closed.sweep.map(Transaction::for_sender);
+            Ok(serde_json::to_string(&closed).unwrap())
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Crea una transacción, o devuelve la original si se repite su clave de
+/// idempotencia
+fn create_transaction(app: &mut App, data: CreateTransactionData) -> Outcome {
+    let transaction = match data.idempotency_key {
+        Some(key) => app.create_idempotent_transaction(
+            &data.token,
+            data.from_id,
+            data.to_id,
+            data.amount,
+            key,
+        ),
+        None => app.create_transaction(&data.token, data.from_id, data.to_id, data.amount),
+    };
+    match transaction {
+        Ok(transaction) => Ok(serde_json::to_string(&transaction.for_sender()).unwrap()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Crea todas las transacciones de un lote o ninguna
+fn create_batch_transaction(app: &mut App, data: CreateBatchTransactionData) -> Outcome {
+    match app.create_batch_transaction(&data.token, data.from_id, data.legs) {
+        Ok(mut batch) => {
+            batch.transactions = batch
+                .transactions
+                .into_iter()
+                .map(Transaction::for_sender)
+                .collect();
+            Ok(serde_json::to_string(&batch).unwrap())
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Programa una transferencia única o periódica
+fn schedule_transfer(app: &mut App, data: ScheduleTransferData) -> Outcome {
+    match app.schedule_transfer(
+        &data.token,
+        data.from_id,
+        data.to_id,
+        data.amount,
+        data.execute_at,
+        data.interval_secs,
+    ) {
+        Ok(schedule) => Ok(serde_json::to_string(&schedule).unwrap()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Lista las transferencias programadas de una cuenta
+fn list_scheduled_transfers(app: &mut App, data: ListScheduledTransfersData) -> Outcome {
+    match app.list_scheduled_transfers(&data.token, &data.account_id) {
+        Ok(schedules) => Ok(serde_json::to_string(&schedules).unwrap()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Cancela una transferencia programada
+fn cancel_scheduled_transfer(app: &mut App, data: CancelScheduledTransferData) -> Outcome {
+    match app.cancel_scheduled_transfer(&data.token, &data.schedule_id) {
+        Ok(schedule) => Ok(serde_json::to_string(&schedule).unwrap()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Página de transacciones sin los saldos de las cuentas, que solo se ven
+/// en los extractos
+fn public_page(mut page: TransactionPage) -> String {
+    page.transactions = page
+        .transactions
+        .into_iter()
+        .map(Transaction::without_balances)
+        .collect();
+    serde_json::to_string(&page).unwrap()
+}
+
+/// Lista las transacciones del nodo
+fn list_transactions(app: &mut App, data: ListTransactionsData) -> Outcome {
+    Ok(public_page(app.query_transactions(None, &data.query)))
+}
+
+/// Obtiene una transacción
+fn get_transaction(app: &mut App, data: GetTransactionData) -> Outcome {
+    match app.get_transaction(&data.transaction_id) {
+        Ok(transaction) => Ok(serde_json::to_string(&transaction.without_balances()).unwrap()),
+        Err(error) => Err(ErrorBody::new(ErrorCode::TransactionNotFound, error)),
+    }
+}
+
+/// Lista las transacciones enviadas o recibidas por una cuenta
+fn get_account_transactions(app: &mut App, data: GetAccountTransactionsData) -> Outcome {
+    if let Err(error) = app.get_account(&data.account_id) {
+        return Err(ErrorBody::new(ErrorCode::AccountNotFound, error));
+    }
+    Ok(public_page(
+        app.query_transactions(Some(&data.account_id), &data.query),
+    ))
+}
+
+/// Extracto de una cuenta entre dos fechas
+fn get_statement(app: &mut App, data: GetStatementData) -> Outcome {
+    match app.get_statement(&data.token, &data.account_id, data.query) {
+        Ok(statement) => Ok(serde_json::to_string(&statement).unwrap()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Router con los endpoints del servidor, al que se pueden añadir otros
+pub fn routes() -> Router {
+    let mut router = Router::new();
+    router
+        .register_unlocked(create_account)
+        .register_unlocked(login)
+        .register(get_account)
+        .register(create_transaction)
+        .register(create_batch_transaction)
+        .register(list_transactions)
+        .register(get_transaction)
+        .register(get_account_transactions)
+        .register(get_statement)
+        .register(rename_account)
+        .register(freeze_account)
+        .register(unfreeze_account)
+        .register(close_account)
+        .register(schedule_transfer)
+        .register(list_scheduled_transfers)
+        .register(cancel_scheduled_transfer);
+    router
+}
+
+/// Atiende las peticiones del protocolo contra una App. No depende del
+/// transporte, de manera que lo comparten el servidor bloqueante y el asíncrono.
+#[derive(Clone)]
+pub struct Handler {
+    pub addr: String,
+    pub app: Arc<Mutex<App>>,
+    /// Tamaño máximo de las peticiones, en bytes
+    pub max_frame_size: usize,
+    router: Arc<Router>,
+}
+
+impl Handler {
+    pub fn new(addr: String, app: App) -> Handler {
+        Handler::with_router(addr, app, routes())
+    }
+
+    pub fn with_router(addr: String, app: App, router: Router) -> Handler {
+        Handler {
+            addr,
+            app: Arc::new(Mutex::new(app)),
+            max_frame_size: MAX_FRAME_SIZE,
+            router: Arc::new(router),
+        }
+    }
+
+    fn response(&self, peer: &str, outcome: Outcome) -> Response {
+        let (data, status, error) = match outcome {
+            Ok(data) => (data, Status::Success, None),
+            Err(error) => (String::new(), error.code.status(), Some(error)),
+        };
+
+        Response {
+            origin_addr: self.addr.clone(),
+            target_addr: peer.to_string(),
+            data,
+            status,
+            error,
+        }
+    }
+
+    /// Gestiona una petición que no se ha podido leer completa. Solo se
+    /// responde si es demasiado grande. En otro caso el cliente ha cerrado la
+    /// conexión antes de enviarla completa o la conexión ha fallado, por
+    /// ejemplo en el handshake TLS.
+    pub fn handle_invalid_frame(&self, error: io::Error, peer: &str) -> Option<Response> {
+        let started = Instant::now();
+        let _span = request_span(peer).entered();
+
+        if is_frame_too_large(&error) {
+            let error = ErrorBody::new(ErrorCode::PayloadTooLarge, error.to_string());
+            let response = self.response(peer, Err(error));
+            log_response(UNKNOWN_ENDPOINT, &response, started);
+            Some(response)
+        } else if error.kind() == io::ErrorKind::UnexpectedEof {
+            debug!("Connection closed before a whole request: {}", error);
+            None
+        } else {
+            warn!("Connection error: {}", error);
+            None
+        }
+    }
+
+    /// Gestiona la petición según el endpoint y devuelve su respuesta
+    pub fn handle_request(&self, frame: &[u8], peer: &str) -> Response {
+        let started = Instant::now();
+        let _span = request_span(peer).entered();
+        let request: Result<Request, serde_json::Error> = serde_json::from_slice(frame);
+
+        match request {
+            Err(error) => {
+                debug!("Request does not satisfy the protocol: {}", error);
+
+                let error = ErrorBody::new(ErrorCode::InvalidRequest, "Request is not valid");
+                let response = self.response(peer, Err(error));
+                log_response(UNKNOWN_ENDPOINT, &response, started);
+                response
+            }
+
+            Ok(request) => {
+                let endpoint = request.endpoint.clone();
+                debug!(origin = %request.origin_addr, "Dispatching {}", endpoint);
+
+                // El servidor actua según el endpoint dentro de la Request
+                let outcome = self.router.dispatch(&self.app, request);
+                let response = self.response(peer, outcome);
+                log_response(&endpoint, &response, started);
+                response
+            }
+        }
+    }
+}
+
+/// Endpoint con el que se registran las peticiones que no se han podido leer
+const UNKNOWN_ENDPOINT: &str = "-";
+
+/// Span de una petición. Todos los mensajes que se registran mientras se
+/// atiende incluyen su ID y la dirección del cliente.
+fn request_span(peer: &str) -> Span {
+    let request_id = Uuid::new_v4().simple().to_string();
+    info_span!("request", request_id = %request_id, peer = %peer)
+}
+
+/// Registra el resultado de una petición y el tiempo que ha tardado
+fn log_response(endpoint: &str, response: &Response, started: Instant) {
+    let status = response.status.code();
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    match &response.error {
+        None => info!(endpoint, status, latency_ms, "Request handled"),
+        Some(error) if response.status == Status::InternalError => error!(
+            endpoint,
+            status,
+            latency_ms,
+            error_code = ?error.code,
+            "Request failed: {}",
+            error
+        ),
+        Some(error) => info!(
+            endpoint,
+            status,
+            latency_ms,
+            error_code = ?error.code,
+            "Request rejected: {}",
+            error
+        ),
+    }
+}
+
+/// Servidor TCP bloqueante que atiende las conexiones en un pool de hilos
+#[derive(Clone)]
+pub struct Server {
+    handler: Handler,
+    workers: usize,
+    shutdown: Arc<AtomicBool>,
+    local_addr: Arc<Mutex<Option<SocketAddr>>>,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
+}
+
+impl Server {
+    pub fn new(handler: Handler, workers: usize) -> Server {
+        Server {
+            handler,
+            workers,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            local_addr: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Cifra las conexiones con TLS, ver [`crate::tls::server_config`]
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: Arc<rustls::ServerConfig>) -> Server {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Lee la petición de una conexión y envía su respuesta
+    fn handle_stream(&self, stream: TcpStream) {
+        let peer = match stream.peer_addr() {
+            Ok(peer) => peer.to_string(),
+            Err(error) => {
+                warn!("{}", error);
+                return;
+            }
+        };
+        if let Err(error) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+            warn!("{}", error);
+            return;
+        }
+
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.tls {
+            self.handle_tls_stream(config.clone(), stream, &peer);
+            return;
+        }
+
+        let mut stream = stream;
+        self.exchange(&mut stream, &peer);
+    }
+
+    /// Completa el handshake TLS y atiende la petición cifrada
+    #[cfg(feature = "tls")]
+    fn handle_tls_stream(&self, config: Arc<rustls::ServerConfig>, stream: TcpStream, peer: &str) {
+        let connection = match rustls::ServerConnection::new(config) {
+            Ok(connection) => connection,
+            Err(error) => {
+                error!("{}", error);
+                return;
+            }
+        };
+        let mut stream = rustls::StreamOwned::new(connection, stream);
+
+        // Los errores del handshake no son peticiones inválidas
+        while stream.conn.is_handshaking() {
+            if let Err(error) = stream.conn.complete_io(&mut stream.sock) {
+                warn!(%peer, "TLS handshake failed: {}", error);
+                return;
+            }
+        }
+
+        self.exchange(&mut stream, peer);
+        stream.conn.send_close_notify();
+        let _ = stream.conn.complete_io(&mut stream.sock);
+    }
+
+    fn exchange<S: Read + Write>(&self, stream: &mut S, peer: &str) {
+        let response = match read_frame(stream, self.handler.max_frame_size) {
+            Ok(frame) => Some(self.handler.handle_request(&frame, peer)),
+            Err(error) => self.handler.handle_invalid_frame(error, peer),
+        };
+        if let Some(response) = response {
+            response.send(stream);
+        }
+    }
+
+    /// Detiene el servidor. Las conexiones ya aceptadas se terminan de atender.
+    pub fn stop(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Se despierta al listener, que está bloqueado esperando conexiones
+        if let Some(local_addr) = *self.local_addr.lock().unwrap() {
+            let _ = TcpStream::connect(local_addr);
+        }
+    }
+
+    /// Atiende las conexiones de `listener` hasta que se llame a `stop`
+    pub fn run(&self, listener: TcpListener) {
+        *self.local_addr.lock().unwrap() = listener.local_addr().ok();
+        info!(
+            "Listening on: {} with {} workers",
+            &self.handler.addr, self.workers
+        );
+
+        let pool = ThreadPool::new(self.workers);
+        for stream in listener.incoming() {
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+
+            // Procesamiento concurrente de las conexiones
+            match stream {
+                Ok(stream) => {
+                    let server = self.clone();
+                    pool.execute(move || server.handle_stream(stream));
+                }
+                Err(error) => warn!("{}", error),
+            }
+        }
+
+        // Al destruir el pool se esperan las peticiones en curso
+        info!("Shutting down, waiting for in-flight requests");
+        drop(pool);
+    }
+}
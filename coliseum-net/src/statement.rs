@@ -0,0 +1,207 @@
+//! Extractos de cuenta.
+//!
+//! Cada transacción guarda el saldo que dejó en la cuenta origen y en la
+//! destino, de manera que el saldo de una cuenta en cualquier momento se
+//! obtiene de su última transacción anterior sin recorrer el historial.
+
+use crate::{AccountError, App, Direction, Transaction, MAX_PAGE_SIZE};
+use coliseum_money::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Sentido de un movimiento desde el punto de vista de la cuenta
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    /// La cuenta recibe fondos
+    Credit,
+    /// La cuenta envía fondos
+    Debit,
+}
+
+/// Movimiento de un extracto
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatementEntry {
+    pub transaction_id: String,
+    pub timestamp: f64,
+    pub kind: EntryKind,
+    /// Cuenta que envía o recibe los fondos
+    pub counterparty_id: String,
+    pub amount: Amount,
+    /// Saldo de la cuenta después del movimiento
+    pub balance: Amount,
+}
+
+impl StatementEntry {
+    fn new(transaction: &Transaction, account_id: &str) -> StatementEntry {
+        let (kind, counterparty_id, balance) = if transaction.from_id == account_id {
+            (
+                EntryKind::Debit,
+                &transaction.to_id,
+                transaction.from_balance,
+            )
+        } else {
+            (
+                EntryKind::Credit,
+                &transaction.from_id,
+                transaction.to_balance,
+            )
+        };
+        StatementEntry {
+            transaction_id: transaction.id.clone(),
+            timestamp: transaction.timestamp,
+            kind,
+            counterparty_id: counterparty_id.clone(),
+            amount: transaction.amount,
+            balance: balance.unwrap_or_default(),
+        }
+    }
+
+    /// Saldo de la cuenta antes del movimiento
+    fn balance_before(&self) -> Amount {
+        let balance = match self.kind {
+            EntryKind::Credit => self.balance.checked_sub(self.amount),
+            EntryKind::Debit => self.balance.checked_add(self.amount),
+        };
+        balance.unwrap_or(self.balance)
+    }
+}
+
+/// Periodo y paginación de un extracto. Las fechas son timestamps UNIX en
+/// segundos e incluyen los extremos.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct StatementQuery {
+    pub since: Option<f64>,
+    pub until: Option<f64>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for StatementQuery {
+    fn default() -> StatementQuery {
+        StatementQuery {
+            since: None,
+            until: None,
+            offset: 0,
+            limit: MAX_PAGE_SIZE,
+        }
+    }
+}
+
+/// Página de los movimientos de una cuenta entre dos fechas, con el saldo
+/// antes y después de la página. `total` es el número de movimientos del
+/// periodo.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub account_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<f64>,
+    pub total: usize,
+    pub offset: usize,
+    pub opening_balance: Amount,
+    pub closing_balance: Amount,
+    pub entries: Vec<StatementEntry>,
+}
+
+impl App {
+    /// Dynamic -> Statement of an account between `query.since` and
+    /// `query.until`, with a session token of the account. Without `since` the
+    /// statement starts with the first transaction of the account and without
+    /// `until` it ends with the last one. It returns at most `query.limit`
+    /// entries, up to `MAX_PAGE_SIZE`, from `query.offset`.
+    pub fn get_statement(
+        &mut self,
+        token: &str,
+        account_id: &str,
+        query: StatementQuery,
+    ) -> Result<Statement, AccountError> {
+        let position = self.authorize(token, account_id)?;
+        let account = &self.accounts[position];
+        let StatementQuery {
+            since,
+            until,
+            offset,
+            limit,
+        } = query;
+        let transactions: Vec<&Transaction> = self
+            .account_transactions(account_id, Direction::Both)
+            .collect();
+
+        // El saldo inicial es el anterior a la primera transacción del
+        // periodo, o el actual si no hay transacciones desde `since`
+        let first = transactions
+            .iter()
+            .position(|transaction| since.is_none_or(|since| transaction.timestamp >= since))
+            .unwrap_or(transactions.len());
+        let period_opening = transactions
+            .get(first)
+            .map_or(account.balance, |transaction| {
+                StatementEntry::new(transaction, account_id).balance_before()
+            });
+
+        let period: Vec<StatementEntry> = transactions[first..]
+            .iter()
+            .filter(|transaction| until.is_none_or(|until| transaction.timestamp <= until))
+            .map(|transaction| StatementEntry::new(transaction, account_id))
+            .collect();
+        // La página empieza con el saldo que dejó el movimiento anterior
+        let opening_balance = match offset.min(period.len()) {
+            0 => period_opening,
+            skipped => period[skipped - 1].balance,
+        };
+        let total = period.len();
+        let entries: Vec<StatementEntry> = period
+            .into_iter()
+            .skip(offset)
+            .take(limit.clamp(1, MAX_PAGE_SIZE))
+            .collect();
+        let closing_balance = entries
+            .last()
+            .map_or(opening_balance, |entry| entry.balance);
+
+        Ok(Statement {
+            account_id: account_id.to_string(),
+            since,
+            until,
+            total,
+            offset,
+            opening_balance,
+            closing_balance,
+            entries,
+        })
+    }
+
+    /// Calcula los saldos de las transacciones guardadas sin ellos, a partir
+    /// del saldo actual de cada cuenta y hacia atrás
+    pub(crate) fn fill_running_balances(&mut self) {
+        let complete = self.transactions.iter().all(|transaction| {
+            transaction.from_balance.is_some() && transaction.to_balance.is_some()
+        });
+        if complete {
+            return;
+        }
+
+        for account in &self.accounts {
+            let Some(positions) = self.account_transactions.get(&account.id) else {
+                continue;
+            };
+            let mut balance = account.balance;
+            for position in positions.iter().rev() {
+                let transaction = &mut self.transactions[*position];
+                let before = if transaction.from_id == account.id {
+                    transaction.from_balance.get_or_insert(balance);
+                    balance.checked_add(transaction.amount)
+                } else {
+                    transaction.to_balance.get_or_insert(balance);
+                    balance.checked_sub(transaction.amount)
+                };
+                match before {
+                    Ok(before) => balance = before,
+                    Err(_) => break,
+                }
+            }
+        }
+    }
+}
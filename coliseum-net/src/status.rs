@@ -0,0 +1,229 @@
+//! Códigos de estado y errores del protocolo.
+//!
+//! El estado de una respuesta viaja como un código numérico al estilo HTTP.
+//! Si la petición falla la respuesta incluye además un [`ErrorBody`] con un
+//! código de error que los clientes pueden interpretar sin leer el mensaje.
+
+use crate::auth::AuthError;
+use crate::{AccountError, TransferError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Estado de una respuesta
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(into = "u16", try_from = "u16")]
+pub enum Status {
+    Success,
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    InsufficientFunds,
+    Conflict,
+    InternalError,
+}
+
+impl Status {
+    pub fn code(&self) -> u16 {
+        match self {
+            Status::Success => 200,
+            Status::BadRequest => 400,
+            Status::Unauthorized => 401,
+            Status::InsufficientFunds => 402,
+            Status::Forbidden => 403,
+            Status::NotFound => 404,
+            Status::Conflict => 409,
+            Status::InternalError => 500,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        *self == Status::Success
+    }
+}
+
+impl From<Status> for u16 {
+    fn from(status: Status) -> u16 {
+        status.code()
+    }
+}
+
+impl TryFrom<u16> for Status {
+    type Error = String;
+
+    fn try_from(code: u16) -> Result<Status, String> {
+        match code {
+            200 => Ok(Status::Success),
+            400 => Ok(Status::BadRequest),
+            401 => Ok(Status::Unauthorized),
+            402 => Ok(Status::InsufficientFunds),
+            403 => Ok(Status::Forbidden),
+            404 => Ok(Status::NotFound),
+            409 => Ok(Status::Conflict),
+            500 => Ok(Status::InternalError),
+            _ => Err(format!("Unknown status code {}", code)),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// Motivo concreto por el que ha fallado una petición
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// La petición no cumple el protocolo
+    InvalidRequest,
+    /// La petición es mayor que el tamaño máximo admitido
+    PayloadTooLarge,
+    /// El endpoint no existe
+    UnknownEndpoint,
+    /// Los datos de la petición no corresponden con su endpoint
+    InvalidPayload,
+    InvalidAmount,
+    SelfTransfer,
+    AccountNotFound,
+    /// La cuenta está congelada y no puede mover fondos
+    AccountFrozen,
+    /// La cuenta está cerrada
+    AccountClosed,
+    /// La cuenta no se puede cerrar porque tiene saldo
+    BalanceNotZero,
+    TransactionNotFound,
+    /// Ya existe una cuenta con el nombre de usuario
+    UsernameTaken,
+    /// La contraseña no cumple los requisitos
+    InvalidPassword,
+    /// El usuario o la contraseña no son correctos
+    InvalidCredentials,
+    /// Falta el token de sesión o no es válido
+    InvalidToken,
+    SessionExpired,
+    /// La sesión no permite operar con la cuenta
+    Forbidden,
+    InsufficientFunds,
+    BalanceOverflow,
+    /// La clave de idempotencia no es válida
+    InvalidIdempotencyKey,
+    /// La clave de idempotencia ya se usó en una petición distinta
+    IdempotencyKeyReused,
+    /// El lote no tiene transferencias o tiene demasiadas
+    InvalidBatch,
+    /// La fecha o el intervalo de una transferencia programada no son válidos
+    InvalidSchedule,
+    ScheduleNotFound,
+    Internal,
+}
+
+impl ErrorCode {
+    /// Estado de las respuestas con este error
+    pub fn status(&self) -> Status {
+        match self {
+            ErrorCode::InvalidRequest
+            | ErrorCode::PayloadTooLarge
+            | ErrorCode::UnknownEndpoint
+            | ErrorCode::InvalidPayload
+            | ErrorCode::InvalidAmount
+            | ErrorCode::SelfTransfer
+            | ErrorCode::InvalidPassword
+            | ErrorCode::InvalidIdempotencyKey
+            | ErrorCode::InvalidBatch
+            | ErrorCode::InvalidSchedule => Status::BadRequest,
+            ErrorCode::InvalidCredentials | ErrorCode::InvalidToken | ErrorCode::SessionExpired => {
+                Status::Unauthorized
+            }
+            ErrorCode::Forbidden => Status::Forbidden,
+            ErrorCode::AccountNotFound
+            | ErrorCode::TransactionNotFound
+            | ErrorCode::ScheduleNotFound => Status::NotFound,
+            ErrorCode::InsufficientFunds => Status::InsufficientFunds,
+            ErrorCode::UsernameTaken
+            | ErrorCode::AccountFrozen
+            | ErrorCode::AccountClosed
+            | ErrorCode::BalanceNotZero
+            | ErrorCode::BalanceOverflow
+            | ErrorCode::IdempotencyKeyReused => Status::Conflict,
+            ErrorCode::Internal => Status::InternalError,
+        }
+    }
+}
+
+/// Error de una respuesta: su código y una descripción para las personas
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorBody {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ErrorBody {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> ErrorBody {
+        ErrorBody {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ErrorBody {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ErrorBody {}
+
+impl From<TransferError> for ErrorBody {
+    fn from(error: TransferError) -> ErrorBody {
+        let code = match error {
+            TransferError::InvalidAmount(_) => ErrorCode::InvalidAmount,
+            TransferError::SelfTransfer => ErrorCode::SelfTransfer,
+            TransferError::AccountNotFound(_) => ErrorCode::AccountNotFound,
+            TransferError::AccountFrozen(_) => ErrorCode::AccountFrozen,
+            TransferError::AccountClosed(_) => ErrorCode::AccountClosed,
+            TransferError::InsufficientFunds { .. } => ErrorCode::InsufficientFunds,
+            TransferError::Overflow(_) => ErrorCode::BalanceOverflow,
+            TransferError::Unauthorized(error) => return error.into(),
+            TransferError::InvalidIdempotencyKey => ErrorCode::InvalidIdempotencyKey,
+            TransferError::IdempotencyKeyReused(_) => ErrorCode::IdempotencyKeyReused,
+            TransferError::InvalidBatch(_) => ErrorCode::InvalidBatch,
+            TransferError::InvalidSchedule(_) => ErrorCode::InvalidSchedule,
+            TransferError::ScheduleNotFound(_) => ErrorCode::ScheduleNotFound,
+            TransferError::Storage(_) => ErrorCode::Internal,
+        };
+        ErrorBody::new(code, error.to_string())
+    }
+}
+
+impl From<AccountError> for ErrorBody {
+    fn from(error: AccountError) -> ErrorBody {
+        let code = match error {
+            AccountError::UsernameTaken(_) => ErrorCode::UsernameTaken,
+            AccountError::InvalidPassword => ErrorCode::InvalidPassword,
+            AccountError::AccountNotFound(_) => ErrorCode::AccountNotFound,
+            AccountError::Unauthorized(error) => return error.into(),
+            AccountError::AccountFrozen(_) => ErrorCode::AccountFrozen,
+            AccountError::AccountClosed(_) => ErrorCode::AccountClosed,
+            AccountError::BalanceNotZero { .. } => ErrorCode::BalanceNotZero,
+            AccountError::Sweep(error) => return error.into(),
+            AccountError::Storage(_) | AccountError::Internal(_) => ErrorCode::Internal,
+        };
+        ErrorBody::new(code, error.to_string())
+    }
+}
+
+impl From<AuthError> for ErrorBody {
+    fn from(error: AuthError) -> ErrorBody {
+        let code = match error {
+            AuthError::InvalidCredentials => ErrorCode::InvalidCredentials,
+            AuthError::InvalidToken => ErrorCode::InvalidToken,
+            AuthError::SessionExpired => ErrorCode::SessionExpired,
+            AuthError::Forbidden => ErrorCode::Forbidden,
+            AuthError::Storage(_) => ErrorCode::Internal,
+        };
+        ErrorBody::new(code, error.to_string())
+    }
+}
@@ -0,0 +1,320 @@
+//! Almacenamiento de los datos de la App.
+//!
+//! Cada cambio de la App se guarda como un [`Event`] antes de aplicarse. Al
+//! arrancar, la App recupera su estado a partir de la última instantánea
+//! guardada y de los eventos posteriores a ella.
+
+use crate::ledger::JournalEntry;
+use crate::scheduler::{ScheduledRun, ScheduledTransfer};
+use crate::{Account, AccountStatus, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Cambio en el estado de la App
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Event {
+    AccountCreated {
+        account: Account,
+        password_hash: String,
+    },
+    LoggedIn {
+        account_id: String,
+        timestamp: f64,
+    },
+    AccountRenamed {
+        account_id: String,
+        username: String,
+    },
+    AccountStatusChanged {
+        account_id: String,
+        status: AccountStatus,
+    },
+    /// La cuenta se cierra después de aplicar `sweep`, si tiene
+    AccountClosed {
+        account_id: String,
+        sweep: Option<Transaction>,
+    },
+    TransactionCreated(Transaction),
+    /// Transacciones de un lote, que se aplican todas a la vez
+    BatchCreated(Vec<Transaction>),
+    TransferScheduled(ScheduledTransfer),
+    /// Ejecución de una transferencia programada, con la transacción que ha
+    /// creado si no ha fallado
+    ScheduledTransferRan(ScheduledRun),
+    ScheduledTransferCancelled {
+        schedule_id: String,
+    },
+}
+
+/// Estado completo de la App que se guarda en una instantánea
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct State {
+    pub accounts: Vec<Account>,
+    pub transactions: Vec<Transaction>,
+    /// ID de cuenta -> hash de su contraseña
+    pub credentials: HashMap<String, String>,
+    pub scheduled_transfers: Vec<ScheduledTransfer>,
+    /// Apuntes del libro mayor posteriores a `ledger_balances`
+    pub journal: Vec<JournalEntry>,
+    /// ID de cuenta -> saldo en el libro mayor antes de los apuntes de
+    /// `journal`, en unidades. Las instantáneas guardan aquí los saldos en
+    /// lugar de todos los apuntes.
+    pub ledger_balances: HashMap<String, i128>,
+}
+
+/// Backend en el que la App guarda sus datos
+pub trait Storage: Send + fmt::Debug {
+    /// Devuelve la última instantánea y los eventos guardados después de ella
+    fn load(&mut self) -> io::Result<(State, Vec<Event>)>;
+
+    /// Guarda un evento. Cuando termina sin error el evento se recupera
+    /// aunque el proceso se detenga inmediatamente después.
+    fn append(&mut self, event: &Event) -> io::Result<()>;
+
+    /// Guarda `state` como instantánea, que sustituye a los eventos previos
+    fn snapshot(&mut self, state: &State) -> io::Result<()>;
+
+    /// Indica si conviene guardar una instantánea
+    fn needs_snapshot(&self) -> bool {
+        false
+    }
+}
+
+/* Almacenamiento en memoria */
+
+#[derive(Debug, Default)]
+struct MemoryLog {
+    state: State,
+    events: Vec<Event>,
+}
+
+/// Almacenamiento en memoria, pensado para los tests. Sus copias comparten
+/// los datos, de manera que se puede reabrir una App con ellos.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    log: Arc<Mutex<MemoryLog>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    /// Número de eventos guardados después de la última instantánea
+    pub fn pending_events(&self) -> usize {
+        self.log.lock().unwrap().events.len()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&mut self) -> io::Result<(State, Vec<Event>)> {
+        let log = self.log.lock().unwrap();
+        Ok((log.state.clone(), log.events.clone()))
+    }
+
+    fn append(&mut self, event: &Event) -> io::Result<()> {
+        self.log.lock().unwrap().events.push(event.clone());
+        Ok(())
+    }
+
+    fn snapshot(&mut self, state: &State) -> io::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        log.state = state.clone();
+        log.events.clear();
+        Ok(())
+    }
+}
+
+/* Almacenamiento en disco */
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const SNAPSHOT_TMP_FILE: &str = "snapshot.json.tmp";
+const WAL_FILE: &str = "wal.log";
+
+/// Número de eventos tras el que se guarda una nueva instantánea
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 1000;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    /// Número del último evento incluido en la instantánea
+    sequence: u64,
+    state: State,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    sequence: u64,
+    event: Event,
+}
+
+/// Almacenamiento en un directorio con un write-ahead log (`wal.log`), con un
+/// evento JSON por línea, y una instantánea periódica (`snapshot.json`).
+///
+/// Cada evento se sincroniza con el disco antes de aplicarse. Una línea
+/// incompleta al final del log, que deja una escritura interrumpida, se
+/// descarta al recuperar. La instantánea se escribe en un fichero temporal
+/// que después se renombra, y los eventos que ya incluye se ignoran aunque
+/// el log no se haya llegado a vaciar.
+#[derive(Debug)]
+pub struct DiskStorage {
+    dir: PathBuf,
+    wal: File,
+    wal_len: u64,
+    sequence: u64,
+    snapshot_sequence: u64,
+    snapshot_interval: u64,
+}
+
+impl DiskStorage {
+    /// Abre el almacenamiento en `dir`, creando el directorio si no existe
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<DiskStorage> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let wal = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.join(WAL_FILE))?;
+        let wal_len = wal.metadata()?.len();
+
+        Ok(DiskStorage {
+            dir,
+            wal,
+            wal_len,
+            sequence: 0,
+            snapshot_sequence: 0,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+        })
+    }
+
+    /// Guarda una instantánea cada `interval` eventos
+    pub fn with_snapshot_interval(mut self, interval: u64) -> DiskStorage {
+        self.snapshot_interval = interval.max(1);
+        self
+    }
+
+    fn read_snapshot(&self) -> io::Result<Option<Snapshot>> {
+        match fs::read(self.dir.join(SNAPSHOT_FILE)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Lee los eventos completos del log y descarta una línea final incompleta
+    fn read_wal(&mut self) -> io::Result<Vec<Record>> {
+        let data = fs::read(self.dir.join(WAL_FILE))?;
+        let mut records = Vec::new();
+        let mut offset = 0;
+
+        let mut lines = data.split_inclusive(|byte| *byte == b'\n').peekable();
+        while let Some(line) = lines.next() {
+            let record = match line.ends_with(b"\n") {
+                true => serde_json::from_slice::<Record>(line).ok(),
+                false => None,
+            };
+
+            match record {
+                Some(record) => {
+                    records.push(record);
+                    offset += line.len();
+                }
+                // Solo la última línea puede estar incompleta
+                None if lines.peek().is_none() => {
+                    tracing::warn!("Discarding incomplete WAL record at byte {}", offset);
+                    break;
+                }
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Corrupted WAL record at byte {}", offset),
+                    ))
+                }
+            }
+        }
+
+        if (offset as u64) < self.wal_len {
+            self.truncate_wal(offset as u64)?;
+        }
+        Ok(records)
+    }
+
+    fn truncate_wal(&mut self, len: u64) -> io::Result<()> {
+        self.wal.set_len(len)?;
+        self.wal.sync_all()?;
+        self.wal_len = len;
+        Ok(())
+    }
+}
+
+impl Storage for DiskStorage {
+    fn load(&mut self) -> io::Result<(State, Vec<Event>)> {
+        let (sequence, state) = match self.read_snapshot()? {
+            Some(snapshot) => (snapshot.sequence, snapshot.state),
+            None => (0, State::default()),
+        };
+        self.snapshot_sequence = sequence;
+        self.sequence = sequence;
+
+        let mut events = Vec::new();
+        for record in self.read_wal()? {
+            // Eventos ya incluidos en la instantánea
+            if record.sequence <= self.sequence {
+                continue;
+            }
+            self.sequence = record.sequence;
+            events.push(record.event);
+        }
+
+        Ok((state, events))
+    }
+
+    fn append(&mut self, event: &Event) -> io::Result<()> {
+        let record = Record {
+            sequence: self.sequence + 1,
+            event: event.clone(),
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let written = self.wal.write_all(&line).and_then(|_| self.wal.sync_data());
+        if let Err(error) = written {
+            // Se elimina lo que se haya llegado a escribir del evento
+            let _ = self.truncate_wal(self.wal_len);
+            return Err(error);
+        }
+
+        self.wal_len += line.len() as u64;
+        self.sequence = record.sequence;
+        Ok(())
+    }
+
+    fn snapshot(&mut self, state: &State) -> io::Result<()> {
+        let snapshot = Snapshot {
+            sequence: self.sequence,
+            state: state.clone(),
+        };
+
+        let tmp_path = self.dir.join(SNAPSHOT_TMP_FILE);
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&serde_json::to_vec(&snapshot)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, self.dir.join(SNAPSHOT_FILE))?;
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+
+        self.snapshot_sequence = self.sequence;
+        self.truncate_wal(0)
+    }
+
+    fn needs_snapshot(&self) -> bool {
+        self.sequence - self.snapshot_sequence >= self.snapshot_interval
+    }
+}
@@ -0,0 +1,101 @@
+//! Cifrado TLS (rustls) de las conexiones entre clientes y servidor.
+//!
+//! El servidor se configura con su certificado y clave privada en PEM y el
+//! cliente con el bundle de CAs, también en PEM, con el que verifica el
+//! certificado del servidor. Se usa el proveedor criptográfico de `ring`.
+
+use crate::framing::{read_frame, write_frame, MAX_FRAME_SIZE};
+use crate::{connect, Request, Response};
+use rustls::crypto::ring;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, StreamOwned};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn invalid_data(error: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+fn load_certificates(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .map_err(invalid_data)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(invalid_data)?;
+
+    if certificates.is_empty() {
+        return Err(invalid_data(format!(
+            "No certificates found in {}",
+            path.display()
+        )));
+    }
+    Ok(certificates)
+}
+
+/// Configuración TLS del servidor a partir de su certificado, seguido de los
+/// intermedios si los hay, y de su clave privada
+pub fn server_config(cert_path: &Path, key_path: &Path) -> io::Result<Arc<ServerConfig>> {
+    let certificates = load_certificates(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(invalid_data)?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(invalid_data)?
+        .with_no_client_auth()
+        .with_single_cert(certificates, key)
+        .map_err(invalid_data)?;
+    Ok(Arc::new(config))
+}
+
+/// Configuración TLS del cliente, que solo acepta servidores con un
+/// certificado firmado por alguna de las CAs de `ca_path`
+pub fn client_config(ca_path: &Path) -> io::Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+    for certificate in load_certificates(ca_path)? {
+        roots.add(certificate).map_err(invalid_data)?;
+    }
+
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(invalid_data)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// Nombre con el que se verifica el certificado de `addr`: el host sin el
+/// puerto, sea un nombre DNS o una IP
+fn server_name(addr: &str) -> io::Result<ServerName<'static>> {
+    let host = match addr.rsplit_once(':') {
+        Some((host, _)) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => addr,
+    };
+    ServerName::try_from(host.to_string()).map_err(|error| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid server name {}: {}", host, error),
+        )
+    })
+}
+
+impl Request {
+    /// Envia una petición cifrada con TLS y espera su respuesta, con el
+    /// mismo `timeout` que [`Request::send`]
+    pub fn send_tls(&self, config: Arc<ClientConfig>, timeout: Duration) -> io::Result<Response> {
+        let json = serde_json::to_vec(self)?;
+
+        let connection =
+            ClientConnection::new(config, server_name(&self.target_addr)?).map_err(invalid_data)?;
+        let mut stream = StreamOwned::new(connection, connect(&self.target_addr, timeout)?);
+        write_frame(&mut stream, &json)?;
+
+        let frame = read_frame(&mut stream, MAX_FRAME_SIZE)?;
+        let response: Response = serde_json::from_slice(&frame)?;
+
+        stream.conn.send_close_notify();
+        let _ = stream.conn.complete_io(&mut stream.sock);
+        Ok(response)
+    }
+}
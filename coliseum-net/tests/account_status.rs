@@ -0,0 +1,176 @@
+mod common;
+
+use coliseum_money::Amount;
+use common::{create_account, login, PASSWORD};
+use lib::auth::AuthError;
+use lib::status::ErrorCode;
+use lib::storage::MemoryStorage;
+use lib::{AccountError, AccountStatus, TransferError};
+
+#[test]
+fn renamed_accounts_keep_usernames_unique() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+
+    assert_eq!(
+        app.rename_account(&token, &alice, "bob".to_string())
+            .unwrap_err(),
+        AccountError::UsernameTaken("bob".to_string())
+    );
+    let account = app
+        .rename_account(&token, &alice, "carol".to_string())
+        .unwrap();
+
+    assert_eq!(account.username, "carol");
+    assert!(app.get_account_by_username("alice").is_err());
+    assert_eq!(app.get_account_by_username("carol").unwrap().id, alice);
+    assert!(app.login("carol", PASSWORD).is_ok());
+    assert!(app
+        .create_account("alice".to_string(), PASSWORD.to_string())
+        .is_ok());
+}
+
+#[test]
+fn frozen_accounts_cannot_send_or_receive() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let alice_token = login(&mut app, "alice");
+    let bob_token = login(&mut app, "bob");
+
+    let account = app.freeze_account(&alice_token, &alice).unwrap();
+    assert_eq!(account.status, AccountStatus::Frozen);
+    assert_eq!(
+        app.create_transaction(&alice_token, alice.clone(), bob.clone(), "1".to_string())
+            .unwrap_err(),
+        TransferError::AccountFrozen(alice.clone())
+    );
+    assert_eq!(
+        app.create_transaction(&bob_token, bob.clone(), alice.clone(), "1".to_string())
+            .unwrap_err(),
+        TransferError::AccountFrozen(alice.clone())
+    );
+    assert_eq!(
+        app.freeze_account(&bob_token, &alice).unwrap_err(),
+        AccountError::Unauthorized(AuthError::Forbidden)
+    );
+
+    app.unfreeze_account(&alice_token, &alice).unwrap();
+    assert!(app
+        .create_transaction(&bob_token, bob, alice, "1".to_string())
+        .is_ok());
+}
+
+#[test]
+fn closing_requires_an_empty_balance_or_a_sweep() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let alice_token = login(&mut app, "alice");
+    let bob_token = login(&mut app, "bob");
+
+    assert_eq!(
+        app.close_account(&alice_token, &alice, None).unwrap_err(),
+        AccountError::BalanceNotZero {
+            balance: "10.00".to_string()
+        }
+    );
+    assert_eq!(
+        app.close_account(&alice_token, &alice, Some("missing".to_string()))
+            .unwrap_err(),
+        AccountError::Sweep(TransferError::AccountNotFound("missing".to_string()))
+    );
+
+    let closed = app
+        .close_account(&alice_token, &alice, Some(bob.clone()))
+        .unwrap();
+
+    assert_eq!(closed.account.status, AccountStatus::Closed);
+    assert_eq!(closed.account.balance, Amount::ZERO);
+    assert_eq!(closed.sweep.unwrap().amount, Amount::from_units(1000));
+    assert_eq!(common::balance(&app, &bob), Amount::from_units(2000));
+    assert!(app.authenticate(&alice_token).is_err());
+    assert_eq!(
+        app.login("alice", PASSWORD).unwrap_err(),
+        AuthError::InvalidCredentials
+    );
+    assert_eq!(
+        app.create_transaction(&bob_token, bob, alice.clone(), "1".to_string())
+            .unwrap_err(),
+        TransferError::AccountClosed(alice)
+    );
+}
+
+#[test]
+fn account_changes_survive_a_restart() {
+    let storage = MemoryStorage::new();
+    let mut app = common::open(storage.clone());
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let carol = create_account(&mut app, "carol");
+    let alice_token = login(&mut app, "alice");
+    let bob_token = login(&mut app, "bob");
+    app.rename_account(&alice_token, &alice, "alicia".to_string())
+        .unwrap();
+    app.freeze_account(&bob_token, &bob).unwrap();
+    app.close_account(&alice_token, &alice, Some(carol.clone()))
+        .unwrap();
+
+    let app = common::open(storage);
+
+    let account = app.get_account_by_username("alicia").unwrap();
+    assert_eq!(account.status, AccountStatus::Closed);
+    assert_eq!(account.balance, Amount::ZERO);
+    assert_eq!(app.get_account(&bob).unwrap().status, AccountStatus::Frozen);
+    assert_eq!(common::balance(&app, &carol), Amount::from_units(2000));
+    assert_eq!(app.transactions().len(), 1);
+}
+
+#[test]
+fn clients_can_manage_their_accounts() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+
+    let (client, server, thread) = common::serve(app);
+
+    let error = |response: std::io::Result<lib::Response>| {
+        response.unwrap().into_result().unwrap_err().code
+    };
+    assert_eq!(
+        error(client.rename_account(&token, &alice, "bob")),
+        ErrorCode::UsernameTaken
+    );
+    client
+        .freeze_account(&token, &alice)
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert_eq!(
+        error(client.create_transaction(&token, &alice, &bob, "1")),
+        ErrorCode::AccountFrozen
+    );
+    assert_eq!(
+        error(client.close_account(&token, &alice, None)),
+        ErrorCode::AccountFrozen
+    );
+    client
+        .unfreeze_account(&token, &alice)
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert_eq!(
+        error(client.close_account(&token, &alice, None)),
+        ErrorCode::BalanceNotZero
+    );
+    client
+        .close_account(&token, &alice, Some(&bob))
+        .unwrap()
+        .into_result()
+        .unwrap();
+
+    common::stop(server, thread);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{create_account, login, ADDR, PASSWORD};
+use lib::server::Handler;
+use lib::status::{ErrorCode, Status};
+use lib::storage::MemoryStorage;
+use lib::{AccountError, CreateAccountData, Direction, Request, RequestBody};
+
+#[test]
+fn usernames_are_unique() {
+    let mut app = common::app();
+    create_account(&mut app, "alice");
+
+    let error = app
+        .create_account("alice".to_string(), PASSWORD.to_string())
+        .unwrap_err();
+
+    assert_eq!(error, AccountError::UsernameTaken("alice".to_string()));
+    assert_eq!(app.accounts().len(), 1);
+}
+
+#[test]
+fn taken_usernames_are_a_conflict() {
+    let handler = Handler::new(ADDR.to_string(), common::app());
+    let request = Request::new(
+        "localhost".to_string(),
+        ADDR.to_string(),
+        RequestBody::CreateAccount(CreateAccountData {
+            username: "alice".to_string(),
+            password: PASSWORD.to_string(),
+        }),
+    );
+    let frame = serde_json::to_vec(&request).unwrap();
+
+    assert_eq!(
+        handler.handle_request(&frame, "localhost").status,
+        Status::Success
+    );
+    let response = handler.handle_request(&frame, "localhost");
+
+    assert_eq!(response.status, Status::Conflict);
+    assert_eq!(response.error.unwrap().code, ErrorCode::UsernameTaken);
+}
+
+#[test]
+fn indexes_are_rebuilt_when_opening_the_app() {
+    let storage = MemoryStorage::new();
+    let mut app = common::open(storage.clone());
+    create_account(&mut app, "alice");
+    create_account(&mut app, "bob");
+    let alice = app.get_account_by_username("alice").unwrap().id.clone();
+    let bob = app.get_account_by_username("bob").unwrap().id.clone();
+    let token = login(&mut app, "alice");
+    let transaction = app
+        .create_transaction(&token, alice.clone(), bob.clone(), "1".to_string())
+        .unwrap();
+
+    let mut app = common::open(storage);
+
+    assert_eq!(app.get_account(&alice).unwrap().username, "alice");
+    assert_eq!(
+        app.get_transaction(&transaction.id).unwrap().id,
+        transaction.id
+    );
+    assert_eq!(
+        app.get_transaction_by_account(&bob, Direction::Received)
+            .len(),
+        1
+    );
+    assert!(app
+        .create_account("bob".to_string(), PASSWORD.to_string())
+        .is_err());
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use lib::async_net::serve;
+use lib::server::Handler;
+use lib::status::Status;
+use lib::{CreateAccountData, Request, RequestBody};
+
+fn create_account_request(target_addr: String, username: String) -> Request {
+    Request::new(
+        "localhost".to_string(),
+        target_addr,
+        RequestBody::CreateAccount(CreateAccountData {
+            username,
+            password: common::PASSWORD.to_string(),
+        }),
+    )
+}
+
+#[tokio::test]
+async fn async_client_talks_to_blocking_server() {
+    let (client, server, thread) = common::serve(common::app());
+    let addr = client.server_addr;
+
+    // Más de 1 KiB, que antes se truncaba
+    let username = "a".repeat(4096);
+    let response = create_account_request(addr, username.clone())
+        .send_async(lib::DEFAULT_TIMEOUT)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status, Status::Success);
+    assert!(response.data.contains(&username));
+
+    common::stop(server, thread);
+}
+
+#[tokio::test]
+async fn blocking_client_talks_to_async_server() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let handler = Handler::new(addr.clone(), common::app());
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(serve(listener, handler.clone(), async {
+        let _ = stopped.await;
+    }));
+
+    let request = create_account_request(addr, "Usuario1".to_string());
+    let response = tokio::task::spawn_blocking(move || request.send(lib::DEFAULT_TIMEOUT))
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(response.status, Status::Success);
+    assert_eq!(handler.app.lock().unwrap().accounts().len(), 1);
+
+    stop.send(()).unwrap();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn async_frames_match_blocking_frames() {
+    let mut blocking = Vec::new();
+    lib::framing::write_frame(&mut blocking, b"payload").unwrap();
+    let mut nonblocking = Vec::new();
+    lib::async_net::write_frame(&mut nonblocking, b"payload")
+        .await
+        .unwrap();
+    assert_eq!(blocking, nonblocking);
+
+    let mut reader = blocking.as_slice();
+    let blocking_error = lib::framing::read_frame(&mut reader, 3).unwrap_err();
+    let mut reader = nonblocking.as_slice();
+    let async_error = lib::async_net::read_frame(&mut reader, 3)
+        .await
+        .unwrap_err();
+    assert_eq!(async_error.kind(), blocking_error.kind());
+    assert_eq!(async_error.to_string(), blocking_error.to_string());
+}
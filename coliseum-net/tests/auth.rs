@@ -0,0 +1,169 @@
+mod common;
+
+use common::{create_account, login, PASSWORD};
+use lib::auth::{AuthError, Session};
+use lib::status::{ErrorCode, Status};
+use lib::{AccountError, TransferError};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn passwords_must_be_long_enough() {
+    let mut app = common::app();
+
+    let error = app
+        .create_account("alice".to_string(), "short".to_string())
+        .unwrap_err();
+
+    assert_eq!(error, AccountError::InvalidPassword);
+    assert!(app.accounts().is_empty());
+}
+
+#[test]
+fn login_checks_the_password_and_updates_last_login() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let created = app.get_account(&alice).unwrap().last_login;
+
+    assert_eq!(
+        app.login("alice", "wrong password").unwrap_err(),
+        AuthError::InvalidCredentials
+    );
+    assert_eq!(
+        app.login("nobody", PASSWORD).unwrap_err(),
+        AuthError::InvalidCredentials
+    );
+
+    thread::sleep(Duration::from_millis(5));
+    let session = app.login("alice", PASSWORD).unwrap();
+
+    assert_eq!(session.account_id, alice);
+    assert_eq!(app.authenticate(&session.token), Ok(alice.clone()));
+    assert!(app.get_account(&alice).unwrap().last_login > created);
+}
+
+#[test]
+fn accounts_must_not_leak_password_hashes() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+
+    let data = serde_json::to_string(app.get_account(&alice).unwrap()).unwrap();
+
+    assert!(!data.contains("argon2"));
+}
+
+#[test]
+fn transfers_require_a_session_of_the_sender() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let bob_token = login(&mut app, "bob");
+
+    let result = app.create_transaction("stolen", alice.clone(), bob.clone(), "1".to_string());
+    assert_eq!(
+        result.unwrap_err(),
+        TransferError::Unauthorized(AuthError::InvalidToken)
+    );
+
+    let result = app.create_transaction(&bob_token, alice.clone(), bob.clone(), "1".to_string());
+    assert_eq!(
+        result.unwrap_err(),
+        TransferError::Unauthorized(AuthError::Forbidden)
+    );
+
+    assert!(app.transactions().is_empty());
+    assert!(app
+        .create_transaction(&bob_token, bob, alice, "1".to_string())
+        .is_ok());
+}
+
+#[test]
+fn sessions_expire() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    app.auth.session_ttl = Duration::from_millis(10);
+    let token = login(&mut app, "alice");
+
+    thread::sleep(Duration::from_millis(20));
+    let result = app.create_transaction(&token, alice, bob, "1".to_string());
+
+    assert_eq!(
+        result.unwrap_err(),
+        TransferError::Unauthorized(AuthError::SessionExpired)
+    );
+    assert_eq!(app.authenticate(&token), Err(AuthError::InvalidToken));
+}
+
+#[test]
+fn clients_login_before_transferring() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+
+    let (client, server, thread) = common::serve(app);
+
+    let response = client.login("alice", "wrong password").unwrap();
+    assert_eq!(response.status, Status::Unauthorized);
+    assert_eq!(response.error.unwrap().code, ErrorCode::InvalidCredentials);
+
+    let response = client.create_transaction("", &alice, &bob, "1").unwrap();
+    assert_eq!(response.status, Status::Unauthorized);
+
+    let response = client.login("bob", PASSWORD).unwrap();
+    let session: Session = serde_json::from_str(&response.into_result().unwrap()).unwrap();
+    let response = client
+        .create_transaction(&session.token, &alice, &bob, "1")
+        .unwrap();
+    assert_eq!(response.status, Status::Forbidden);
+    assert_eq!(response.error.unwrap().code, ErrorCode::Forbidden);
+
+    let response = client.login("alice", PASSWORD).unwrap();
+    let session: Session = serde_json::from_str(&response.into_result().unwrap()).unwrap();
+    let response = client
+        .create_transaction(&session.token, &alice, &bob, "1")
+        .unwrap();
+    assert_eq!(response.status, Status::Success);
+
+    common::stop(server, thread);
+}
+
+#[test]
+fn unknown_usernames_fail_like_wrong_passwords() {
+    let mut app = common::app();
+    create_account(&mut app, "alice");
+
+    assert_eq!(
+        app.login("nobody", PASSWORD).unwrap_err(),
+        AuthError::InvalidCredentials
+    );
+    assert_eq!(
+        app.login("alice", "wrong password").unwrap_err(),
+        AuthError::InvalidCredentials
+    );
+}
+
+#[test]
+fn passwords_are_hashed_without_blocking_other_requests() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    // Un hash lento de verdad, para que la petición siga en curso
+    app.auth.memory_cost = 64 * 1024;
+    app.auth.iterations = 8;
+    let (client, server, thread) = common::serve(app);
+
+    let signup = {
+        let client = client.clone();
+        thread::spawn(move || client.create_account("bob", PASSWORD).unwrap())
+    };
+    thread::sleep(Duration::from_millis(50));
+    let response = client.get_account(&alice).unwrap();
+
+    assert_eq!(response.status, Status::Success);
+    assert!(!signup.is_finished());
+    assert_eq!(signup.join().unwrap().status, Status::Success);
+    let response = client.create_account("bob", PASSWORD).unwrap();
+    assert_eq!(response.error.unwrap().code, ErrorCode::UsernameTaken);
+
+    common::stop(server, thread);
+}
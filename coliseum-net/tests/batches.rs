@@ -0,0 +1,138 @@
+mod common;
+
+use coliseum_money::Amount;
+use common::{balance, create_account, login};
+use lib::status::ErrorCode;
+use lib::storage::MemoryStorage;
+use lib::{Batch, Direction, TransferError, TransferLeg, MAX_BATCH_LEGS};
+
+fn leg(to_id: &str, amount: &str) -> TransferLeg {
+    TransferLeg {
+        to_id: to_id.to_string(),
+        amount: amount.to_string(),
+    }
+}
+
+#[test]
+fn batches_share_an_id() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let carol = create_account(&mut app, "carol");
+    let token = login(&mut app, "alice");
+
+    let batch = app
+        .create_batch_transaction(
+            &token,
+            alice.clone(),
+            vec![leg(&bob, "2"), leg(&carol, "3.50"), leg(&bob, "1")],
+        )
+        .unwrap();
+
+    assert_eq!(batch.transactions.len(), 3);
+    assert!(batch
+        .transactions
+        .iter()
+        .all(|transaction| transaction.batch_id.as_ref() == Some(&batch.batch_id)));
+    assert_eq!(balance(&app, &alice), Amount::from_units(350));
+    assert_eq!(balance(&app, &bob), Amount::from_units(1300));
+    assert_eq!(balance(&app, &carol), Amount::from_units(1350));
+    assert_eq!(
+        app.get_transaction_by_account(&bob, Direction::Received)
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn failed_legs_cancel_the_whole_batch() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let carol = create_account(&mut app, "carol");
+    let token = login(&mut app, "alice");
+    let mut batch = |legs: Vec<TransferLeg>| {
+        app.create_batch_transaction(&token, alice.clone(), legs)
+            .unwrap_err()
+    };
+
+    // Cada transferencia cabe en el saldo, pero no todas juntas
+    assert_eq!(
+        batch(vec![leg(&bob, "6"), leg(&carol, "6")]),
+        TransferError::InsufficientFunds {
+            available: "10.00".to_string()
+        }
+    );
+    assert_eq!(
+        batch(vec![leg(&bob, "1"), leg("missing", "1")]),
+        TransferError::AccountNotFound("missing".to_string())
+    );
+    assert_eq!(
+        batch(vec![leg(&bob, "1"), leg(&alice, "1")]),
+        TransferError::SelfTransfer
+    );
+    assert!(matches!(
+        batch(vec![leg(&bob, "1"), leg(&carol, "0")]),
+        TransferError::InvalidAmount(_)
+    ));
+    assert_eq!(batch(vec![]), TransferError::InvalidBatch(0));
+    assert_eq!(
+        batch(vec![leg(&bob, "0.01"); MAX_BATCH_LEGS + 1]),
+        TransferError::InvalidBatch(MAX_BATCH_LEGS + 1)
+    );
+
+    assert!(app.transactions().is_empty());
+    assert_eq!(balance(&app, &alice), Amount::from_units(1000));
+    assert_eq!(balance(&app, &bob), Amount::from_units(1000));
+}
+
+#[test]
+fn batches_survive_a_restart() {
+    let storage = MemoryStorage::new();
+    let mut app = common::open(storage.clone());
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let carol = create_account(&mut app, "carol");
+    let token = login(&mut app, "alice");
+    let batch = app
+        .create_batch_transaction(
+            &token,
+            alice.clone(),
+            vec![leg(&bob, "1"), leg(&carol, "2")],
+        )
+        .unwrap();
+
+    let app = common::open(storage);
+
+    assert_eq!(balance(&app, &alice), Amount::from_units(700));
+    assert_eq!(balance(&app, &carol), Amount::from_units(1200));
+    let transaction = app.get_transaction(&batch.transactions[1].id).unwrap();
+    assert_eq!(transaction.batch_id, Some(batch.batch_id));
+}
+
+#[test]
+fn clients_can_send_batches() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+
+    let (client, server, thread) = common::serve(app);
+
+    let data = client
+        .create_batch_transaction(&token, &alice, vec![leg(&bob, "1"), leg(&bob, "2")])
+        .unwrap()
+        .into_result()
+        .unwrap();
+    let batch: Batch = serde_json::from_str(&data).unwrap();
+    assert_eq!(batch.transactions.len(), 2);
+
+    let error = client
+        .create_batch_transaction(&token, &alice, vec![])
+        .unwrap()
+        .into_result()
+        .unwrap_err();
+    assert_eq!(error.code, ErrorCode::InvalidBatch);
+
+    common::stop(server, thread);
+}
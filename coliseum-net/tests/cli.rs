@@ -0,0 +1,210 @@
+mod common;
+
+use common::create_account;
+use lib::server::Server;
+use serde_json::Value;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+/// Arranca un servidor con las cuentas `alice` y `bob` y devuelve su
+/// dirección y los IDs de las cuentas
+fn start_server() -> (Server, String, thread::JoinHandle<()>, [String; 2]) {
+    let mut app = common::app();
+    let ids = [
+        create_account(&mut app, "alice"),
+        create_account(&mut app, "bob"),
+    ];
+    let (client, server, thread) = common::serve(app);
+    (server, client.server_addr, thread, ids)
+}
+
+fn client(addr: &str) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_client"));
+    command
+        .env_remove("COLISEUM_TOKEN")
+        .env_remove("COLISEUM_PASSWORD")
+        .env_remove("COLISEUM_TLS_CA")
+        .args(["--server", addr]);
+    command
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn commands_print_the_response_data() {
+    let (server, addr, thread, [alice, _]) = start_server();
+
+    let output = client(&addr)
+        .args(["--output", "json", "get-account", &alice])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let account: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(account["username"], "alice");
+
+    let output = client(&addr)
+        .args(["get-account", &alice])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("balance       10.00"));
+
+    common::stop(server, thread);
+}
+
+#[test]
+fn errors_exit_with_a_non_zero_code() {
+    let (server, addr, thread, [alice, bob]) = start_server();
+
+    let output = client(&addr)
+        .args(["get-account", "missing"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("AccountNotFound"));
+
+    let output = client(&addr)
+        .args(["transfer", &alice, &bob, "1", "--token", "invalid"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    common::stop(server, thread);
+
+    let output = client(&addr)
+        .args(["get-account", &alice])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn login_reads_the_password_from_stdin() {
+    let (server, addr, thread, _) = start_server();
+
+    let mut child = client(&addr)
+        .args(["--output", "json", "login", "alice"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    writeln!(child.stdin.take().unwrap(), "{}", common::PASSWORD).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let session: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert!(session["token"].is_string());
+
+    // Sin contraseña en la entrada no se envía la petición
+    let output = client(&addr)
+        .args(["login", "alice"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+
+    common::stop(server, thread);
+}
+
+#[test]
+fn statements_can_be_exported_as_csv() {
+    let (server, addr, thread, [alice, bob]) = start_server();
+    let token = {
+        let output = client(&addr)
+            .args(["--output", "json", "login", "alice"])
+            .args(["--password", common::PASSWORD])
+            .output()
+            .unwrap();
+        let session: Value = serde_json::from_str(&stdout(&output)).unwrap();
+        session["token"].as_str().unwrap().to_string()
+    };
+    client(&addr)
+        .args(["transfer", &alice, &bob, "1.5", "--token", &token])
+        .output()
+        .unwrap();
+
+    let output = client(&addr)
+        .args(["--output", "csv", "statement", &alice, "--token", &token])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let csv = stdout(&output);
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert!(lines.contains(&"opening_balance,10.00"));
+    assert!(lines.contains(&"closing_balance,8.50"));
+    let header = lines
+        .iter()
+        .position(|line| line.starts_with("amount,"))
+        .unwrap();
+    assert!(lines[header].contains("balance"));
+    assert!(lines[header + 1].starts_with("1.50,8.50,"));
+    assert!(lines[header + 1].contains(&bob));
+
+    common::stop(server, thread);
+}
+
+#[test]
+fn transactions_show_running_balances_as_amounts() {
+    let (server, addr, thread, [alice, bob]) = start_server();
+    let token = {
+        let output = client(&addr)
+            .args(["--output", "json", "login", "alice"])
+            .args(["--password", common::PASSWORD])
+            .output()
+            .unwrap();
+        let session: Value = serde_json::from_str(&stdout(&output)).unwrap();
+        session["token"].as_str().unwrap().to_string()
+    };
+    let output = client(&addr)
+        .args(["transfer", &alice, &bob, "1.5", "--token", &token])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Quien envía los fondos solo ve su propio saldo
+    let table = stdout(&output);
+    assert!(table.contains("from_balance  8.50"), "{}", table);
+    assert!(!table.contains("to_balance"), "{}", table);
+
+    common::stop(server, thread);
+}
+
+#[test]
+fn repl_reuses_the_login_token() {
+    let (server, addr, thread, [alice, bob]) = start_server();
+
+    let mut child = client(&addr)
+        .args(["--output", "json", "repl"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let script = format!(
+        "login alice --password '{}'\ntransfer {} {} 2.5\nget-account {}\nexit\n",
+        common::PASSWORD,
+        alice,
+        bob,
+        bob
+    );
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout(&output).contains("\"balance\": 1250"));
+
+    common::stop(server, thread);
+}
@@ -0,0 +1,74 @@
+//! Helpers shared by the coliseum-net tests.
+#![allow(dead_code)]
+
+use coliseum_money::Amount;
+use lib::client::Client;
+use lib::server::{Handler, Server};
+use lib::storage::Storage;
+use lib::{Account, App};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+pub const ADDR: &str = "127.0.0.1:5000";
+pub const PASSWORD: &str = "contraseña";
+
+/// Cheap password hashing, the defaults are far too slow for tests
+fn fast_auth(app: &mut App) {
+    app.auth.memory_cost = 8;
+    app.auth.iterations = 1;
+}
+
+/// In-memory App with cheap password hashing
+pub fn app() -> App {
+    let mut app = App::new(ADDR.to_string());
+    fast_auth(&mut app);
+    app
+}
+
+/// Opens an App over `storage` with cheap password hashing
+pub fn open(storage: impl Storage + 'static) -> App {
+    let mut app = App::open(ADDR.to_string(), storage).unwrap();
+    fast_auth(&mut app);
+    app
+}
+
+/// Creates an account with `PASSWORD` and returns its ID
+pub fn create_account(app: &mut App, username: &str) -> String {
+    let data = app
+        .create_account(username.to_string(), PASSWORD.to_string())
+        .unwrap();
+    let account: Account = serde_json::from_str(&data).unwrap();
+    account.id
+}
+
+/// Session token of an account created with `PASSWORD`
+pub fn login(app: &mut App, username: &str) -> String {
+    app.login(username, PASSWORD).unwrap().token
+}
+
+/// Current balance of an account
+pub fn balance(app: &App, account_id: &str) -> Amount {
+    app.get_account(account_id).unwrap().balance
+}
+
+/// Runs `app` on a local port and returns a client for it, the server and
+/// its thread, to be ended with `stop`
+pub fn serve(app: App) -> (Client, Server, JoinHandle<()>) {
+    serve_handler(Handler::new(ADDR.to_string(), app))
+}
+
+/// Like `serve` with an already configured handler
+pub fn serve_handler(handler: Handler) -> (Client, Server, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = Server::new(handler, 2);
+    let running = server.clone();
+    let thread = thread::spawn(move || running.run(listener));
+    (Client::new("localhost", &addr), server, thread)
+}
+
+/// Stops a server started with `serve` and waits for it
+pub fn stop(server: Server, thread: JoinHandle<()>) {
+    server.stop();
+    thread.join().unwrap();
+}
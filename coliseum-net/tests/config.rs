@@ -0,0 +1,129 @@
+mod common;
+
+use coliseum_money::Amount;
+use lib::config::{ConfigError, LogLevel, ServerConfig};
+use lib::server::Handler;
+use lib::status::ErrorCode;
+use lib::Account;
+use std::fs;
+use std::path::PathBuf;
+
+fn write_config(dir: &tempfile::TempDir, contents: &str) -> PathBuf {
+    let path = dir.path().join("coliseum.toml");
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn files_override_the_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_config(
+        &dir,
+        r#"
+        addr = "127.0.0.1:6000"
+        starting_balance = "25.50"
+        log_level = "debug"
+        "#,
+    );
+
+    let config = ServerConfig::from_file(&path).unwrap();
+
+    assert_eq!(config.addr, "127.0.0.1:6000");
+    assert_eq!(config.log_level, LogLevel::Debug);
+    assert_eq!(config.starting_balance().unwrap(), Amount::from_units(2550));
+    assert_eq!(config.workers, ServerConfig::default().workers);
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_config(&dir, "adress = \"127.0.0.1:6000\"\n");
+
+    let error = ServerConfig::from_file(&path).unwrap_err();
+
+    assert!(matches!(error, ConfigError::Parse(..)));
+    assert!(error.to_string().contains("adress"));
+    assert!(matches!(
+        ServerConfig::from_file(&dir.path().join("missing.toml")),
+        Err(ConfigError::Read(..))
+    ));
+}
+
+#[test]
+fn invalid_values_name_their_field() {
+    let field = |config: ServerConfig| match config.validate() {
+        Err(ConfigError::Invalid { field, .. }) => field,
+        result => panic!("Unexpected validation result {:?}", result),
+    };
+    let default = ServerConfig::default;
+
+    assert_eq!(
+        field(ServerConfig {
+            addr: "not an address".to_string(),
+            ..default()
+        }),
+        "addr"
+    );
+    assert_eq!(
+        field(ServerConfig {
+            workers: 0,
+            ..default()
+        }),
+        "workers"
+    );
+    assert_eq!(
+        field(ServerConfig {
+            starting_balance: "-1".to_string(),
+            ..default()
+        }),
+        "starting_balance"
+    );
+    assert_eq!(
+        field(ServerConfig {
+            max_frame_size: 0,
+            ..default()
+        }),
+        "max_frame_size"
+    );
+    assert_eq!(
+        field(ServerConfig {
+            tls_cert: Some(PathBuf::from("server.pem")),
+            ..default()
+        }),
+        "tls_key"
+    );
+}
+
+#[test]
+fn servers_use_the_configured_values() {
+    let config = ServerConfig {
+        starting_balance: "3".to_string(),
+        max_frame_size: 512,
+        ..ServerConfig::default()
+    };
+
+    let mut app = common::app();
+    app.starting_balance = config.starting_balance().unwrap();
+    let mut handler = Handler::new(common::ADDR.to_string(), app);
+    handler.max_frame_size = config.max_frame_size;
+
+    let (client, server, thread) = common::serve_handler(handler);
+
+    let data = client
+        .create_account("alice", common::PASSWORD)
+        .unwrap()
+        .into_result()
+        .unwrap();
+    let account: Account = serde_json::from_str(&data).unwrap();
+    assert_eq!(account.balance, Amount::from_units(300));
+
+    let error = client
+        .create_account(&"x".repeat(512), common::PASSWORD)
+        .unwrap()
+        .into_result()
+        .unwrap_err();
+    assert_eq!(error.code, ErrorCode::PayloadTooLarge);
+
+    common::stop(server, thread);
+}
@@ -0,0 +1,57 @@
+use lib::framing::{is_frame_too_large, read_frame, write_frame, MAX_FRAME_SIZE};
+use std::io::{self, Cursor, Read};
+
+/// Reader that returns at most one byte per read, like a slow TCP stream.
+struct Trickle(Cursor<Vec<u8>>);
+
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+#[test]
+fn frames_larger_than_a_single_read_are_read_whole() {
+    let payload = "x".repeat(64 * 1024).into_bytes();
+    let mut buffer = Vec::new();
+    write_frame(&mut buffer, &payload).unwrap();
+
+    let mut reader = Trickle(Cursor::new(buffer));
+    assert_eq!(read_frame(&mut reader, MAX_FRAME_SIZE).unwrap(), payload);
+}
+
+#[test]
+fn consecutive_frames_are_kept_apart() {
+    let mut buffer = Vec::new();
+    write_frame(&mut buffer, b"first").unwrap();
+    write_frame(&mut buffer, b"second").unwrap();
+
+    let mut reader = Cursor::new(buffer);
+    assert_eq!(read_frame(&mut reader, MAX_FRAME_SIZE).unwrap(), b"first");
+    assert_eq!(read_frame(&mut reader, MAX_FRAME_SIZE).unwrap(), b"second");
+}
+
+#[test]
+fn frames_over_the_maximum_size_are_rejected() {
+    let mut buffer = Vec::new();
+    write_frame(&mut buffer, &[0; 100]).unwrap();
+
+    let error = read_frame(&mut Cursor::new(buffer), 99).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(is_frame_too_large(&error));
+    assert!(!is_frame_too_large(&io::Error::new(
+        io::ErrorKind::InvalidData,
+        "other"
+    )));
+}
+
+#[test]
+fn truncated_frames_are_an_error() {
+    let mut buffer = Vec::new();
+    write_frame(&mut buffer, b"truncated").unwrap();
+    buffer.truncate(8);
+
+    let error = read_frame(&mut Cursor::new(buffer), MAX_FRAME_SIZE).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use coliseum_money::Amount;
+use common::{create_account, login};
+use lib::client::Client;
+use lib::framing::{read_frame, write_frame, MAX_FRAME_SIZE};
+use lib::status::{ErrorCode, Status};
+use lib::storage::MemoryStorage;
+use lib::{TransferError, MAX_IDEMPOTENCY_KEY_LENGTH};
+use std::io::Read;
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Reenvía a `server_addr` la primera petición que recibe y se queda con la
+/// respuesta, como una conexión que se corta tras llegar al servidor
+fn drop_first_response(server_addr: String) -> (String, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let thread = thread::spawn(move || {
+        let (mut client, _) = listener.accept().unwrap();
+        let request = read_frame(&mut client, MAX_FRAME_SIZE).unwrap();
+        let mut server = TcpStream::connect(server_addr).unwrap();
+        write_frame(&mut server, &request).unwrap();
+        read_frame(&mut server, MAX_FRAME_SIZE).unwrap();
+        // Hasta que el cliente se cansa de esperar y cierra
+        let _ = client.read_to_end(&mut Vec::new());
+    });
+    (addr, thread)
+}
+
+#[test]
+fn replays_return_the_original_transaction() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    let transfer = |app: &mut lib::App, amount: &str, key: &str| {
+        app.create_idempotent_transaction(
+            &token,
+            alice.clone(),
+            bob.clone(),
+            amount.to_string(),
+            key.to_string(),
+        )
+    };
+
+    let original = transfer(&mut app, "1", "retry-1").unwrap();
+    let replayed = transfer(&mut app, "1.00", "retry-1").unwrap();
+
+    assert_eq!(replayed.id, original.id);
+    assert_eq!(original.idempotency_key.as_deref(), Some("retry-1"));
+    assert_eq!(app.transactions().len(), 1);
+    assert_eq!(common::balance(&app, &bob), Amount::from_units(1100));
+
+    // La misma clave con otros datos no es un reintento
+    assert_eq!(
+        transfer(&mut app, "2", "retry-1").unwrap_err(),
+        TransferError::IdempotencyKeyReused("retry-1".to_string())
+    );
+    assert_eq!(
+        transfer(&mut app, "1", "").unwrap_err(),
+        TransferError::InvalidIdempotencyKey
+    );
+    assert_eq!(
+        transfer(&mut app, "1", &"k".repeat(MAX_IDEMPOTENCY_KEY_LENGTH + 1)).unwrap_err(),
+        TransferError::InvalidIdempotencyKey
+    );
+}
+
+#[test]
+fn keys_belong_to_the_sending_account() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let alice_token = login(&mut app, "alice");
+    let bob_token = login(&mut app, "bob");
+
+    app.create_idempotent_transaction(
+        &alice_token,
+        alice.clone(),
+        bob.clone(),
+        "1".to_string(),
+        "key".to_string(),
+    )
+    .unwrap();
+    app.create_idempotent_transaction(&bob_token, bob, alice, "1".to_string(), "key".to_string())
+        .unwrap();
+
+    assert_eq!(app.transactions().len(), 2);
+}
+
+#[test]
+fn keys_expire_after_the_retention_window() {
+    let mut app = common::app();
+    app.idempotency_retention = Duration::ZERO;
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+
+    for _ in 0..2 {
+        app.create_idempotent_transaction(
+            &token,
+            alice.clone(),
+            bob.clone(),
+            "1".to_string(),
+            "key".to_string(),
+        )
+        .unwrap();
+    }
+
+    assert_eq!(app.transactions().len(), 2);
+}
+
+#[test]
+fn keys_survive_a_restart() {
+    let storage = MemoryStorage::new();
+    let mut app = common::open(storage.clone());
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    let original = app
+        .create_idempotent_transaction(
+            &token,
+            alice.clone(),
+            bob.clone(),
+            "1".to_string(),
+            "key".to_string(),
+        )
+        .unwrap();
+
+    let mut app = common::open(storage);
+    let token = login(&mut app, "alice");
+    let replayed = app
+        .create_idempotent_transaction(&token, alice, bob, "1".to_string(), "key".to_string())
+        .unwrap();
+
+    assert_eq!(replayed.id, original.id);
+    assert_eq!(app.transactions().len(), 1);
+}
+
+#[test]
+fn clients_can_retry_transfers() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+
+    let (client, server, thread) = common::serve(app);
+
+    let send = |amount: &str| {
+        client
+            .create_idempotent_transaction(&token, &alice, &bob, amount, "retry")
+            .unwrap()
+            .into_result()
+    };
+    assert_eq!(send("1").unwrap(), send("1").unwrap());
+    assert_eq!(send("5").unwrap_err().code, ErrorCode::IdempotencyKeyReused);
+
+    common::stop(server, thread);
+}
+
+#[test]
+fn lost_responses_are_retried_with_the_same_key() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    let (client, server, thread) = common::serve(app);
+    let (proxy_addr, proxy) = drop_first_response(client.server_addr.clone());
+
+    let lost = Client::new("localhost", &proxy_addr)
+        .with_timeout(Duration::from_millis(200))
+        .create_idempotent_transaction(&token, &alice, &bob, "1", "lost");
+    assert!(lost.is_err());
+    proxy.join().unwrap();
+
+    let retried = client
+        .create_idempotent_transaction(&token, &alice, &bob, "1", "lost")
+        .unwrap();
+    assert_eq!(retried.status, Status::Success);
+    let data = client.get_account(&bob).unwrap().into_result().unwrap();
+    let account: lib::Account = serde_json::from_str(&data).unwrap();
+    assert_eq!(account.balance, Amount::from_units(1100));
+
+    common::stop(server, thread);
+}
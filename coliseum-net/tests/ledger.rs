@@ -0,0 +1,189 @@
+mod common;
+
+use coliseum_money::Amount;
+use common::{create_account, login};
+use lib::ledger::{Ledger, LedgerError, Side, ISSUANCE_ACCOUNT};
+use lib::storage::{DiskStorage, MemoryStorage, Storage};
+use lib::{App, TransferLeg};
+use std::io;
+
+#[test]
+fn transfers_post_balanced_entries() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+
+    let transaction = app
+        .create_transaction(&token, alice.clone(), bob.clone(), "2.50".to_string())
+        .unwrap();
+
+    let entries: Vec<(&str, Side, u64)> = app
+        .ledger()
+        .entries()
+        .iter()
+        .filter(|entry| entry.reference == transaction.id)
+        .map(|entry| (entry.account_id.as_str(), entry.side, entry.amount.units()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (alice.as_str(), Side::Debit, 250),
+            (bob.as_str(), Side::Credit, 250)
+        ]
+    );
+    assert_eq!(app.ledger().amount(&alice), Amount::from_units(750));
+    assert_eq!(common::balance(&app, &bob), app.ledger().amount(&bob));
+}
+
+#[test]
+fn starting_balances_come_from_the_issuance_account() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    create_account(&mut app, "bob");
+
+    let grant = &app.ledger().entries()[..2];
+    assert_eq!(grant[0].account_id, ISSUANCE_ACCOUNT);
+    assert_eq!(grant[0].side, Side::Debit);
+    assert_eq!(grant[1].account_id, alice);
+    assert_eq!(grant[1].amount, Amount::from_units(1000));
+    assert_eq!(app.ledger().balance(ISSUANCE_ACCOUNT), -2000);
+    // La emisión no es una transacción entre cuentas
+    assert!(app.transactions().is_empty());
+
+    let supply = app.check_ledger().unwrap();
+    assert_eq!(supply.issued, 2000);
+    assert_eq!(supply.circulating, 2000);
+}
+
+#[test]
+fn the_journal_survives_a_restart() {
+    let storage = MemoryStorage::new();
+    let mut app = common::open(storage.clone());
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let carol = create_account(&mut app, "carol");
+    let token = login(&mut app, "alice");
+    let legs = vec![
+        TransferLeg {
+            to_id: bob.clone(),
+            amount: "1".to_string(),
+        },
+        TransferLeg {
+            to_id: carol.clone(),
+            amount: "2".to_string(),
+        },
+    ];
+    app.create_batch_transaction(&token, alice.clone(), legs)
+        .unwrap();
+    let bob_token = login(&mut app, "bob");
+    app.close_account(&bob_token, &bob, Some(carol.clone()))
+        .unwrap();
+    let entries = app.ledger().entries().to_vec();
+
+    // Del log y de una instantánea sale el mismo diario
+    let reopened = common::open(storage.clone());
+    assert_eq!(reopened.ledger().entries(), entries.as_slice());
+    let mut snapshot = storage.clone();
+    snapshot.snapshot(&reopened.state()).unwrap();
+    let reopened = common::open(snapshot);
+
+    assert_eq!(reopened.ledger().entries(), entries.as_slice());
+    assert_eq!(reopened.ledger().amount(&carol), Amount::from_units(2300));
+    assert_eq!(reopened.check_ledger().unwrap().issued, 3000);
+}
+
+#[test]
+fn snapshots_keep_balances_instead_of_the_journal() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = DiskStorage::open(dir.path())
+        .unwrap()
+        .with_snapshot_interval(4);
+    let mut app = common::open(storage);
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    for _ in 0..10 {
+        app.create_transaction(&token, alice.clone(), bob.clone(), "0.10".to_string())
+            .unwrap();
+    }
+
+    // Solo quedan los apuntes posteriores a la última instantánea
+    assert!(app.ledger().entries().len() <= 8);
+    assert_eq!(app.ledger().opening()[ISSUANCE_ACCOUNT], -2000);
+    let snapshot: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.path().join("snapshot.json")).unwrap()).unwrap();
+    assert_eq!(snapshot["state"]["journal"], serde_json::json!([]));
+
+    let reopened = common::open(DiskStorage::open(dir.path()).unwrap());
+    assert_eq!(reopened.ledger().amount(&alice), Amount::from_units(900));
+    assert_eq!(reopened.ledger().amount(&bob), Amount::from_units(1100));
+    assert_eq!(reopened.check_ledger().unwrap().issued, 2000);
+}
+
+#[test]
+fn snapshots_without_a_journal_are_rebuilt() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    app.create_transaction(&token, alice.clone(), bob.clone(), "4".to_string())
+        .unwrap();
+    let mut state = app.state();
+    state.journal.clear();
+    let mut storage = MemoryStorage::new();
+    storage.snapshot(&state).unwrap();
+
+    let app = common::open(storage);
+
+    assert_eq!(app.ledger().entries().len(), 6);
+    assert_eq!(app.ledger().entries()[1].amount, Amount::from_units(1000));
+    assert_eq!(app.ledger().amount(&alice), Amount::from_units(600));
+    assert_eq!(app.check_ledger().unwrap().issued, 2000);
+}
+
+#[test]
+fn tampered_balances_are_detected_on_open() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    create_account(&mut app, "bob");
+    let mut state = app.state();
+    state.accounts[0].balance = Amount::from_units(5000);
+    let mut storage = MemoryStorage::new();
+    storage.snapshot(&state).unwrap();
+
+    let error = App::open(common::ADDR.to_string(), storage).unwrap_err();
+
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        error.to_string(),
+        LedgerError::BalanceMismatch(alice).to_string()
+    );
+}
+
+#[test]
+fn transfers_are_checked_without_posting_them() {
+    let mut ledger = Ledger::default();
+    ledger
+        .issue("alice", Amount::from_units(1000), 0.0)
+        .unwrap();
+    let entries = ledger.entries().to_vec();
+
+    // La segunda deja a alice en negativo aunque la primera sea válida
+    let error = ledger
+        .check_transfers([
+            ("alice", "bob", Amount::from_units(800)),
+            ("alice", "carol", Amount::from_units(800)),
+        ])
+        .unwrap_err();
+
+    assert!(error.contains("overdrawn"));
+    assert_eq!(ledger.entries(), entries.as_slice());
+    assert_eq!(ledger.balance("alice"), 1000);
+    assert!(ledger
+        .check_transfers([
+            ("alice", "bob", Amount::from_units(800)),
+            ("bob", "carol", Amount::from_units(800)),
+        ])
+        .is_ok());
+}
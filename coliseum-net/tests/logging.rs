@@ -0,0 +1,117 @@
+mod common;
+
+use lib::framing::read_frame;
+use lib::server::Handler;
+use lib::{GetAccountData, Request, RequestBody};
+use serde_json::Value;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Destino de los logs que se puede leer desde el test
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Buffer {
+    type Writer = Buffer;
+
+    fn make_writer(&'a self) -> Buffer {
+        self.clone()
+    }
+}
+
+/// Líneas JSON registradas mientras se ejecuta `f`, con el mismo formato
+/// que el binario `server`
+fn capture(f: impl FnOnce()) -> Vec<Value> {
+    let buffer = Buffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_span_list(false)
+        .with_writer(buffer.clone())
+        .finish();
+    tracing::subscriber::with_default(subscriber, f);
+
+    let logs = buffer.0.lock().unwrap();
+    String::from_utf8_lossy(&logs)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn get_account(account_id: &str) -> Vec<u8> {
+    let body = RequestBody::GetAccount(GetAccountData {
+        account_id: account_id.to_string(),
+    });
+    let request = Request::new("localhost".to_string(), common::ADDR.to_string(), body);
+    serde_json::to_vec(&request).unwrap()
+}
+
+#[test]
+fn requests_are_logged_with_their_outcome() {
+    let mut app = common::app();
+    let id = common::create_account(&mut app, "alice");
+    let handler = Handler::new(common::ADDR.to_string(), app);
+
+    let logs = capture(|| {
+        handler.handle_request(&get_account(&id), "10.0.0.1:4000");
+        handler.handle_request(&get_account("missing"), "10.0.0.2:4000");
+    });
+
+    assert_eq!(logs.len(), 2);
+    assert_eq!(logs[0]["endpoint"], "GetAccount");
+    assert_eq!(logs[0]["status"], 200);
+    assert!(logs[0]["latency_ms"].as_f64().unwrap() >= 0.0);
+    assert_eq!(logs[0]["span"]["peer"], "10.0.0.1:4000");
+    assert_eq!(logs[1]["status"], 404);
+    assert_eq!(logs[1]["error_code"], "AccountNotFound");
+    assert_eq!(logs[1]["span"]["peer"], "10.0.0.2:4000");
+
+    // Cada petición tiene su propio ID
+    let request_id = |log: &Value| log["span"]["request_id"].as_str().unwrap().to_string();
+    assert_eq!(request_id(&logs[0]).len(), 32);
+    assert_ne!(request_id(&logs[0]), request_id(&logs[1]));
+}
+
+#[test]
+fn unreadable_requests_are_logged() {
+    let handler = Handler::new(common::ADDR.to_string(), common::app());
+
+    let logs = capture(|| {
+        handler.handle_request(b"not json", "10.0.0.1:4000");
+        let too_large = read_frame(&mut &[0, 0, 1, 0][..], 10).unwrap_err();
+        assert!(handler
+            .handle_invalid_frame(too_large, "10.0.0.1:4000")
+            .is_some());
+        let closed = io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed");
+        assert!(handler
+            .handle_invalid_frame(closed, "10.0.0.1:4000")
+            .is_none());
+        // Los errores de TLS también son InvalidData, pero no se responden
+        let handshake = io::Error::new(io::ErrorKind::InvalidData, "invalid handshake");
+        assert!(handler
+            .handle_invalid_frame(handshake, "10.0.0.1:4000")
+            .is_none());
+    });
+
+    assert_eq!(logs.len(), 3);
+    assert_eq!(logs[0]["endpoint"], "-");
+    assert_eq!(logs[0]["error_code"], "InvalidRequest");
+    assert_eq!(logs[1]["status"], 400);
+    assert_eq!(logs[1]["error_code"], "PayloadTooLarge");
+    assert_eq!(logs[2]["level"], "WARN");
+    assert!(logs[2]["message"]
+        .as_str()
+        .unwrap()
+        .contains("invalid handshake"));
+}
@@ -0,0 +1,19 @@
+use lib::net::Node;
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+#[test]
+fn register_gives_up_on_a_node_that_never_answers() {
+    // Accepts the connection but never writes a response.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let register_addr = listener.local_addr().unwrap().to_string();
+
+    let mut node =
+        Node::new("127.0.0.1:0", &register_addr).with_timeout(Duration::from_millis(200));
+    let start = Instant::now();
+    node.register();
+
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(node.nodes.lock().unwrap().len(), 1);
+    drop(listener);
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use lib::router::Outcome;
+use lib::server::{routes, Handler};
+use lib::status::{ErrorCode, Status};
+use lib::{App, CreateAccountData, Payload, Request, RequestBody, Response};
+use serde::{Deserialize, Serialize};
+
+fn handler() -> Handler {
+    Handler::new("127.0.0.1:5000".to_string(), common::app())
+}
+
+fn frame(request: &Request) -> Vec<u8> {
+    serde_json::to_vec(request).unwrap()
+}
+
+#[test]
+fn typed_requests_reach_their_handler() {
+    let handler = handler();
+    let request = Request::new(
+        "localhost".to_string(),
+        "127.0.0.1:5000".to_string(),
+        RequestBody::CreateAccount(CreateAccountData {
+            username: "Usuario1".to_string(),
+            password: common::PASSWORD.to_string(),
+        }),
+    );
+
+    let response = handler.handle_request(&frame(&request), "localhost");
+
+    assert_eq!(response.status, Status::Success);
+    assert_eq!(
+        handler.app.lock().unwrap().accounts()[0].username,
+        "Usuario1"
+    );
+    assert!(matches!(
+        request.body().unwrap(),
+        RequestBody::CreateAccount(_)
+    ));
+}
+
+#[test]
+fn unknown_endpoints_are_rejected() {
+    let request = Request {
+        endpoint: "DeleteEverything".to_string(),
+        origin_addr: "localhost".to_string(),
+        target_addr: "127.0.0.1:5000".to_string(),
+        data: serde_json::json!({}),
+    };
+
+    let response = handler().handle_request(&frame(&request), "localhost");
+
+    assert_eq!(response.status, Status::BadRequest);
+    let error = response.error.unwrap();
+    assert_eq!(error.code, ErrorCode::UnknownEndpoint);
+    assert_eq!(error.message, "DeleteEverything endpoint not found");
+}
+
+#[test]
+fn malformed_payloads_are_rejected() {
+    let request = Request {
+        endpoint: "GetAccount".to_string(),
+        origin_addr: "localhost".to_string(),
+        target_addr: "127.0.0.1:5000".to_string(),
+        data: serde_json::json!({ "id": 1 }),
+    };
+
+    let response = handler().handle_request(&frame(&request), "localhost");
+
+    assert_eq!(response.status, Status::BadRequest);
+    let error = response.error.unwrap();
+    assert_eq!(error.code, ErrorCode::InvalidPayload);
+    assert!(error.message.starts_with("Request Data is not valid"));
+}
+
+#[derive(Serialize, Deserialize)]
+struct CountAccountsData {}
+
+impl Payload for CountAccountsData {
+    const ENDPOINT: &'static str = "CountAccounts";
+}
+
+fn count_accounts(app: &mut App, _: CountAccountsData) -> Outcome {
+    Ok(app.accounts().len().to_string())
+}
+
+#[test]
+fn new_endpoints_are_registered_as_functions() {
+    let mut router = routes();
+    router.register(count_accounts);
+    let handler = Handler::with_router("127.0.0.1:5000".to_string(), common::app(), router);
+    let request = Request {
+        endpoint: "CountAccounts".to_string(),
+        origin_addr: "localhost".to_string(),
+        target_addr: "127.0.0.1:5000".to_string(),
+        data: serde_json::json!({}),
+    };
+
+    let response = handler.handle_request(&frame(&request), "localhost");
+
+    assert_eq!(response.status, Status::Success);
+    assert_eq!(response.data, "0");
+}
+
+#[test]
+fn unparsable_requests_are_rejected() {
+    let response = handler().handle_request(b"not json", "localhost");
+
+    assert_eq!(response.status, Status::BadRequest);
+    assert_eq!(response.error.unwrap().code, ErrorCode::InvalidRequest);
+}
+
+#[test]
+fn responses_carry_numeric_statuses_and_error_bodies() {
+    let response = handler().handle_request(b"not json", "localhost");
+    let json: serde_json::Value = serde_json::to_value(&response).unwrap();
+
+    assert_eq!(json["status"], 400);
+    assert_eq!(json["error"]["code"], "invalid_request");
+
+    let response: Response = serde_json::from_value(json).unwrap();
+    let error = response.into_result().unwrap_err();
+    assert_eq!(error.message, "Request is not valid");
+}
@@ -0,0 +1,309 @@
+mod common;
+
+use coliseum_money::Amount;
+use common::{balance, create_account, login};
+use lib::auth::AuthError;
+use lib::scheduler::{
+    RunResult, ScheduleStatus, ScheduledTransfer, Scheduler, MAX_CONSECUTIVE_FAILURES,
+};
+use lib::server::Handler;
+use lib::status::ErrorCode;
+use lib::storage::MemoryStorage;
+use lib::{App, TransferError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn one_off_transfers_run_once_when_due() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    let at = App::create_timestamp() + 60.0;
+    app.schedule_transfer(
+        &token,
+        alice.clone(),
+        bob.clone(),
+        "2".to_string(),
+        Some(at),
+        None,
+    )
+    .unwrap();
+
+    assert!(app.run_scheduled_transfers(at - 1.0).unwrap().is_empty());
+    let runs = app.run_scheduled_transfers(at).unwrap();
+    assert_eq!(runs.len(), 1);
+    assert!(matches!(runs[0].result, RunResult::Executed(_)));
+    assert!(app.run_scheduled_transfers(at + 60.0).unwrap().is_empty());
+
+    let schedule = &app.list_scheduled_transfers(&token, &alice).unwrap()[0];
+    assert_eq!(schedule.status, ScheduleStatus::Completed);
+    assert_eq!(schedule.transaction_ids.len(), 1);
+    assert_eq!(balance(&app, &bob), Amount::from_units(1200));
+    assert_eq!(
+        app.get_transaction(&schedule.transaction_ids[0])
+            .unwrap()
+            .amount,
+        Amount::from_units(200)
+    );
+}
+
+#[test]
+fn recurring_transfers_record_their_failures() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    let schedule = app
+        .schedule_transfer(
+            &token,
+            alice.clone(),
+            bob.clone(),
+            "4".to_string(),
+            None,
+            Some(60),
+        )
+        .unwrap();
+    let first = schedule.next_run;
+
+    // 10 - 4 - 4 no deja saldo para la tercera
+    for run in 0..3 {
+        app.run_scheduled_transfers(first + run as f64 * 60.0)
+            .unwrap();
+    }
+    // Tras varios intervalos sin ejecutarse solo se hace una transferencia
+    let runs = app.run_scheduled_transfers(first + 600.0).unwrap();
+    assert_eq!(runs.len(), 1);
+
+    let schedule = &app.scheduled_transfers()[0];
+    assert_eq!(schedule.status, ScheduleStatus::Active);
+    assert_eq!(schedule.transaction_ids.len(), 2);
+    assert_eq!(schedule.failures.len(), 2);
+    assert_eq!(schedule.failures[0].timestamp, first + 120.0);
+    assert!(schedule.failures[0].error.contains("fondos suficientes"));
+    assert_eq!(schedule.next_run, first + 660.0);
+    assert_eq!(balance(&app, &alice), Amount::from_units(200));
+
+    // Tras MAX_CONSECUTIVE_FAILURES fallos seguidos deja de intentarse
+    let mut next_run = schedule.next_run;
+    for _ in 2..MAX_CONSECUTIVE_FAILURES {
+        app.run_scheduled_transfers(next_run).unwrap();
+        next_run = app.scheduled_transfers()[0].next_run;
+    }
+    let schedule = &app.scheduled_transfers()[0];
+    assert_eq!(schedule.status, ScheduleStatus::Failed);
+    assert_eq!(schedule.consecutive_failures, MAX_CONSECUTIVE_FAILURES);
+    assert_eq!(schedule.failures.len(), MAX_CONSECUTIVE_FAILURES as usize);
+    assert!(app
+        .run_scheduled_transfers(next_run + 3600.0)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn only_the_last_failures_are_kept() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let alice_token = login(&mut app, "alice");
+    let bob_token = login(&mut app, "bob");
+    let schedule = app
+        .schedule_transfer(
+            &alice_token,
+            alice.clone(),
+            bob.clone(),
+            "4".to_string(),
+            None,
+            Some(60),
+        )
+        .unwrap();
+    let run = |app: &mut App| {
+        let next_run = app.scheduled_transfers()[0].next_run;
+        app.run_scheduled_transfers(next_run).unwrap();
+    };
+    // Deja a alice sin saldo para la tercera
+    run(&mut app);
+    run(&mut app);
+
+    // Fallos que nunca llegan al máximo seguidos, con una ejecución correcta
+    // entre ellos
+    for _ in 0..3 {
+        for _ in 1..MAX_CONSECUTIVE_FAILURES {
+            run(&mut app);
+        }
+        app.create_transaction(&bob_token, bob.clone(), alice.clone(), "4".to_string())
+            .unwrap();
+        run(&mut app);
+    }
+
+    let schedule = app
+        .scheduled_transfers()
+        .iter()
+        .find(|other| other.id == schedule.id)
+        .unwrap();
+    assert_eq!(schedule.status, ScheduleStatus::Active);
+    assert_eq!(schedule.transaction_ids.len(), 5);
+    assert_eq!(schedule.consecutive_failures, 0);
+    assert_eq!(schedule.failures.len(), MAX_CONSECUTIVE_FAILURES as usize);
+}
+
+#[test]
+fn schedules_need_active_accounts() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let carol = create_account(&mut app, "carol");
+    let alice_token = login(&mut app, "alice");
+    let carol_token = login(&mut app, "carol");
+    app.close_account(&carol_token, &carol, Some(bob.clone()))
+        .unwrap();
+    let schedule = |app: &mut App, to_id: &str| {
+        app.schedule_transfer(
+            &alice_token,
+            alice.clone(),
+            to_id.to_string(),
+            "1".to_string(),
+            None,
+            Some(60),
+        )
+    };
+
+    assert_eq!(
+        schedule(&mut app, &carol).unwrap_err(),
+        TransferError::AccountClosed(carol.clone())
+    );
+    app.freeze_account(&alice_token, &alice).unwrap();
+    assert_eq!(
+        schedule(&mut app, &bob).unwrap_err(),
+        TransferError::AccountFrozen(alice.clone())
+    );
+}
+
+#[test]
+fn schedules_are_validated_and_cancelled_by_their_owner() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let alice_token = login(&mut app, "alice");
+    let bob_token = login(&mut app, "bob");
+    let mut schedule = |execute_at: Option<f64>, interval_secs: Option<u64>| {
+        app.schedule_transfer(
+            &alice_token,
+            alice.clone(),
+            bob.clone(),
+            "1".to_string(),
+            execute_at,
+            interval_secs,
+        )
+    };
+
+    assert!(matches!(
+        schedule(None, None),
+        Err(TransferError::InvalidSchedule(_))
+    ));
+    assert!(matches!(
+        schedule(Some(App::create_timestamp() - 60.0), None),
+        Err(TransferError::InvalidSchedule(_))
+    ));
+    assert!(matches!(
+        schedule(None, Some(0)),
+        Err(TransferError::InvalidSchedule(_))
+    ));
+    let id = schedule(None, Some(60)).unwrap().id;
+
+    assert_eq!(
+        app.cancel_scheduled_transfer(&bob_token, &id).unwrap_err(),
+        TransferError::Unauthorized(AuthError::Forbidden)
+    );
+    assert_eq!(
+        app.cancel_scheduled_transfer(&alice_token, "missing")
+            .unwrap_err(),
+        TransferError::ScheduleNotFound("missing".to_string())
+    );
+    let cancelled = app.cancel_scheduled_transfer(&alice_token, &id).unwrap();
+    assert_eq!(cancelled.status, ScheduleStatus::Cancelled);
+    assert!(app
+        .run_scheduled_transfers(App::create_timestamp() + 3600.0)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn schedules_survive_a_restart() {
+    let storage = MemoryStorage::new();
+    let mut app = common::open(storage.clone());
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    let schedule = app
+        .schedule_transfer(
+            &token,
+            alice.clone(),
+            bob.clone(),
+            "1".to_string(),
+            None,
+            Some(60),
+        )
+        .unwrap();
+    app.run_scheduled_transfers(schedule.next_run).unwrap();
+
+    let mut app = common::open(storage);
+
+    let reopened = &app.scheduled_transfers()[0];
+    assert_eq!(reopened.next_run, schedule.next_run + 60.0);
+    assert_eq!(reopened.transaction_ids.len(), 1);
+    assert_eq!(balance(&app, &bob), Amount::from_units(1100));
+    // La ejecución ya guardada no se repite
+    assert!(app
+        .run_scheduled_transfers(schedule.next_run)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn the_scheduler_runs_due_transfers() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+
+    let handler = Handler::new(common::ADDR.to_string(), app);
+    let app = handler.app.clone();
+    let scheduler = Scheduler::spawn(app.clone(), Duration::from_millis(10));
+    let (client, server, thread) = common::serve_handler(handler);
+
+    let at = App::create_timestamp() + 0.05;
+    client
+        .schedule_transfer(&token, &alice, &bob, "1", Some(at), None)
+        .unwrap()
+        .into_result()
+        .unwrap();
+    let started = Instant::now();
+    let schedules = loop {
+        let data = client
+            .list_scheduled_transfers(&token, &alice)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let schedules: Vec<ScheduledTransfer> = serde_json::from_str(&data).unwrap();
+        if schedules[0].status != ScheduleStatus::Active || started.elapsed().as_secs() > 5 {
+            break schedules;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    assert_eq!(schedules[0].status, ScheduleStatus::Completed);
+    assert_eq!(
+        balance(&app.lock().unwrap(), &bob),
+        Amount::from_units(1100)
+    );
+
+    let error = client
+        .cancel_scheduled_transfer(&token, "missing")
+        .unwrap()
+        .into_result()
+        .unwrap_err();
+    assert_eq!(error.code, ErrorCode::ScheduleNotFound);
+
+    scheduler.stop();
+    common::stop(server, thread);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::create_account;
+use lib::framing::{encode_header, read_frame, MAX_FRAME_SIZE};
+use lib::status::Status;
+use lib::{GetAccountData, Request, RequestBody, Response, ThreadPool};
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[test]
+fn slow_requests_do_not_block_the_others_and_finish_on_stop() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let (client, server, thread) = common::serve(app);
+
+    // Una petición a medio enviar ocupa uno de los dos workers
+    let request = Request::new(
+        "localhost".to_string(),
+        client.server_addr.clone(),
+        RequestBody::GetAccount(GetAccountData {
+            account_id: alice.clone(),
+        }),
+    );
+    let payload = serde_json::to_vec(&request).unwrap();
+    let mut slow = TcpStream::connect(&client.server_addr).unwrap();
+    slow.write_all(&encode_header(payload.len()).unwrap())
+        .unwrap();
+    slow.write_all(&payload[..10]).unwrap();
+
+    let response = client.get_account(&alice).unwrap();
+    assert_eq!(response.status, Status::Success);
+
+    // Tras pararlo el servidor todavía responde a la petición en curso
+    server.stop();
+    slow.write_all(&payload[10..]).unwrap();
+    let frame = read_frame(&mut slow, MAX_FRAME_SIZE).unwrap();
+    let response: Response = serde_json::from_slice(&frame).unwrap();
+    assert_eq!(response.status, Status::Success);
+    assert!(response.data.contains(&alice));
+    thread.join().unwrap();
+
+    assert!(client.get_account(&alice).is_err());
+}
+
+#[test]
+fn panicking_jobs_do_not_break_the_pool_shutdown() {
+    let done = Arc::new(AtomicUsize::new(0));
+    let pool = ThreadPool::new(2);
+
+    pool.execute(|| panic!("job failed"));
+    for _ in 0..4 {
+        let done = done.clone();
+        pool.execute(move || {
+            done.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    drop(pool);
+
+    assert_eq!(done.load(Ordering::SeqCst), 4);
+}
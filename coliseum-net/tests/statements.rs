@@ -0,0 +1,263 @@
+mod common;
+
+use coliseum_money::Amount;
+use common::{create_account, login};
+use lib::auth::{AuthConfig, AuthError};
+use lib::statement::{EntryKind, Statement, StatementQuery};
+use lib::status::ErrorCode;
+use lib::storage::{MemoryStorage, State, Storage};
+use lib::{
+    Account, AccountError, AccountStatus, App, Transaction, TransactionPage, TransactionQuery,
+    MAX_PAGE_SIZE,
+};
+
+fn account(id: &str, balance: u64) -> Account {
+    Account {
+        id: id.to_string(),
+        created_time: 0.0,
+        last_login: 0.0,
+        username: id.to_string(),
+        balance: Amount::from_units(balance),
+        status: AccountStatus::Active,
+    }
+}
+
+fn transaction(id: &str, from_id: &str, to_id: &str, amount: u64, timestamp: f64) -> Transaction {
+    Transaction {
+        id: id.to_string(),
+        from_id: from_id.to_string(),
+        to_id: to_id.to_string(),
+        amount: Amount::from_units(amount),
+        timestamp,
+        node: common::ADDR.to_string(),
+        idempotency_key: None,
+        batch_id: None,
+        from_balance: None,
+        to_balance: None,
+    }
+}
+
+/// App abierta de una instantánea con fechas conocidas y sin saldos por
+/// transacción, como las guardadas antes de que existieran
+fn app_with_history() -> App {
+    let auth = AuthConfig {
+        memory_cost: 8,
+        iterations: 1,
+        ..AuthConfig::default()
+    };
+    let hash = auth.hash_password(common::PASSWORD).unwrap();
+    let mut storage = MemoryStorage::new();
+    storage
+        .snapshot(&State {
+            // alice empezó con 10 y bob con 10
+            accounts: vec![account("alice", 700), account("bob", 1300)],
+            transactions: vec![
+                transaction("t1", "alice", "bob", 500, 100.0),
+                transaction("t2", "bob", "alice", 300, 200.0),
+                transaction("t3", "alice", "bob", 100, 300.0),
+            ],
+            credentials: [("alice", &hash), ("bob", &hash)]
+                .map(|(id, hash)| (id.to_string(), hash.clone()))
+                .into(),
+            ..State::default()
+        })
+        .unwrap();
+    common::open(storage)
+}
+
+/// Extracto de una cuenta de `app_with_history` entre dos fechas
+fn statement(app: &mut App, account_id: &str, since: Option<f64>, until: Option<f64>) -> Statement {
+    let token = login(app, account_id);
+    let query = StatementQuery {
+        since,
+        until,
+        ..StatementQuery::default()
+    };
+    app.get_statement(&token, account_id, query).unwrap()
+}
+
+#[test]
+fn transactions_record_the_resulting_balances() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+
+    let transaction = app
+        .create_transaction(&token, alice, bob, "2.50".to_string())
+        .unwrap();
+
+    assert_eq!(transaction.from_balance, Some(Amount::from_units(750)));
+    assert_eq!(transaction.to_balance, Some(Amount::from_units(1250)));
+    let stored = app.get_transaction(&transaction.id).unwrap();
+    assert_eq!(stored.from_balance, transaction.from_balance);
+}
+
+#[test]
+fn transaction_queries_do_not_reveal_balances() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    let (client, server, thread) = common::serve(app);
+
+    let data = client
+        .create_transaction(&token, &alice, &bob, "1")
+        .unwrap()
+        .into_result()
+        .unwrap();
+    let sent: Transaction = serde_json::from_str(&data).unwrap();
+    assert_eq!(sent.from_balance, Some(Amount::from_units(900)));
+    assert_eq!(sent.to_balance, None);
+
+    let data = client
+        .get_account_transactions(&bob, TransactionQuery::default())
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert!(!data.contains("balance"), "{}", data);
+    let page: TransactionPage = serde_json::from_str(&data).unwrap();
+    assert_eq!(page.transactions[0].id, sent.id);
+    let data = client
+        .get_transaction(&sent.id)
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert!(!data.contains("balance"), "{}", data);
+    let data = client
+        .list_transactions(TransactionQuery::default())
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert!(!data.contains("balance"), "{}", data);
+
+    common::stop(server, thread);
+}
+
+#[test]
+fn statements_cover_a_time_range() {
+    let mut app = app_with_history();
+
+    let alice = statement(&mut app, "alice", Some(150.0), Some(300.0));
+
+    assert_eq!(alice.opening_balance, Amount::from_units(500));
+    assert_eq!(alice.closing_balance, Amount::from_units(700));
+    let entries: Vec<(&str, EntryKind, u64)> = alice
+        .entries
+        .iter()
+        .map(|entry| {
+            (
+                entry.transaction_id.as_str(),
+                entry.kind,
+                entry.balance.units(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            ("t2", EntryKind::Credit, 800),
+            ("t3", EntryKind::Debit, 700)
+        ]
+    );
+    assert_eq!(alice.entries[0].counterparty_id, "bob");
+
+    // Sin límites el extracto empieza con el saldo inicial de la cuenta
+    let bob = statement(&mut app, "bob", None, None);
+    assert_eq!(bob.opening_balance, Amount::from_units(1000));
+    assert_eq!(bob.closing_balance, Amount::from_units(1300));
+    assert_eq!(bob.entries.len(), 3);
+}
+
+#[test]
+fn periods_without_transactions_keep_the_balance() {
+    let mut app = app_with_history();
+
+    let before = statement(&mut app, "alice", None, Some(50.0));
+    let between = statement(&mut app, "alice", Some(110.0), Some(190.0));
+    let after = statement(&mut app, "alice", Some(400.0), None);
+
+    assert_eq!(before.opening_balance, Amount::from_units(1000));
+    assert_eq!(before.closing_balance, Amount::from_units(1000));
+    assert_eq!(between.opening_balance, Amount::from_units(500));
+    assert_eq!(between.closing_balance, Amount::from_units(500));
+    assert_eq!(after.closing_balance, Amount::from_units(700));
+    assert!(before.entries.is_empty() && between.entries.is_empty() && after.entries.is_empty());
+}
+
+#[test]
+fn statements_are_paged_with_the_balance_before_each_page() {
+    let mut app = app_with_history();
+    let token = login(&mut app, "alice");
+    let mut page = |offset: usize, limit: usize| {
+        let query = StatementQuery {
+            offset,
+            limit,
+            ..StatementQuery::default()
+        };
+        app.get_statement(&token, "alice", query).unwrap()
+    };
+
+    let second = page(1, 1);
+    assert_eq!(second.total, 3);
+    assert_eq!(second.offset, 1);
+    assert_eq!(second.entries.len(), 1);
+    assert_eq!(second.entries[0].transaction_id, "t2");
+    assert_eq!(second.opening_balance, Amount::from_units(500));
+    assert_eq!(second.closing_balance, Amount::from_units(800));
+
+    // Más allá del final la página está vacía y conserva el último saldo
+    let past_the_end = page(10, 1);
+    assert!(past_the_end.entries.is_empty());
+    assert_eq!(past_the_end.opening_balance, Amount::from_units(700));
+    assert_eq!(past_the_end.closing_balance, Amount::from_units(700));
+    assert_eq!(page(0, MAX_PAGE_SIZE + 1).entries.len(), 3);
+    assert_eq!(page(0, 0).entries.len(), 1);
+}
+
+#[test]
+fn statements_require_a_session_of_the_account() {
+    let mut app = app_with_history();
+    let token = login(&mut app, "alice");
+
+    assert_eq!(
+        app.get_statement(&token, "bob", StatementQuery::default())
+            .unwrap_err(),
+        AccountError::Unauthorized(AuthError::Forbidden)
+    );
+    assert_eq!(
+        app.get_statement("invalid", "alice", StatementQuery::default())
+            .unwrap_err(),
+        AccountError::Unauthorized(AuthError::InvalidToken)
+    );
+}
+
+#[test]
+fn clients_can_request_statements() {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let token = login(&mut app, "alice");
+    app.create_transaction(&token, alice.clone(), bob.clone(), "1".to_string())
+        .unwrap();
+
+    let (client, server, thread) = common::serve(app);
+
+    let data = client
+        .get_statement(&token, &alice, StatementQuery::default())
+        .unwrap()
+        .into_result()
+        .unwrap();
+    let statement: Statement = serde_json::from_str(&data).unwrap();
+    assert_eq!(statement.closing_balance, Amount::from_units(900));
+    assert_eq!(statement.entries[0].kind, EntryKind::Debit);
+
+    let error = client
+        .get_statement(&token, &bob, StatementQuery::default())
+        .unwrap()
+        .into_result()
+        .unwrap_err();
+    assert_eq!(error.code, ErrorCode::Forbidden);
+
+    common::stop(server, thread);
+}
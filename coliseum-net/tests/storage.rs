@@ -0,0 +1,176 @@
+mod common;
+
+use coliseum_money::Amount;
+use common::{create_account, login};
+use lib::storage::{DiskStorage, Event, MemoryStorage, Storage};
+use lib::{App, Transaction};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Crea dos cuentas, inicia sesión con la primera y mueve `transfers` veces 1 unidad entre ellas
+fn populate(app: &mut App, transfers: usize) {
+    let alice = create_account(app, "alice");
+    let bob = create_account(app, "bob");
+    let token = login(app, "alice");
+    for _ in 0..transfers {
+        app.create_transaction(&token, alice.clone(), bob.clone(), "1".to_string())
+            .unwrap();
+    }
+}
+
+fn summary(app: &App) -> (Vec<String>, Vec<u64>, Vec<String>) {
+    (
+        app.accounts()
+            .iter()
+            .map(|account| account.id.clone())
+            .collect(),
+        app.accounts()
+            .iter()
+            .map(|account| account.balance.units())
+            .collect(),
+        app.transactions()
+            .iter()
+            .map(|transaction| transaction.id.clone())
+            .collect(),
+    )
+}
+
+fn open_disk(dir: &Path, snapshot_interval: u64) -> App {
+    let storage = DiskStorage::open(dir)
+        .unwrap()
+        .with_snapshot_interval(snapshot_interval);
+    common::open(storage)
+}
+
+#[test]
+fn memory_storage_recovers_the_app() {
+    let storage = MemoryStorage::new();
+    let mut app = common::open(storage.clone());
+    populate(&mut app, 3);
+
+    let reopened = common::open(storage.clone());
+
+    assert_eq!(storage.pending_events(), 6);
+    assert_eq!(summary(&reopened), summary(&app));
+}
+
+#[test]
+fn disk_storage_recovers_the_app_after_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut app = open_disk(dir.path(), 1000);
+    populate(&mut app, 3);
+    let expected = summary(&app);
+    drop(app);
+
+    let mut reopened = open_disk(dir.path(), 1000);
+
+    assert_eq!(summary(&reopened), expected);
+    assert_eq!(expected.1, [700, 1300]);
+    // Las contraseñas también se recuperan
+    login(&mut reopened, "bob");
+}
+
+#[test]
+fn snapshots_replace_the_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut app = open_disk(dir.path(), 4);
+    populate(&mut app, 4);
+    let expected = summary(&app);
+    drop(app);
+
+    // 7 eventos: la instantánea incluye los 4 primeros
+    let wal = fs::read_to_string(dir.path().join("wal.log")).unwrap();
+    assert!(dir.path().join("snapshot.json").exists());
+    assert_eq!(wal.lines().count(), 3);
+
+    assert_eq!(summary(&open_disk(dir.path(), 4)), expected);
+}
+
+#[test]
+fn incomplete_trailing_records_are_discarded() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut app = open_disk(dir.path(), 1000);
+    populate(&mut app, 2);
+    let expected = summary(&app);
+    drop(app);
+
+    // Escritura interrumpida a mitad de un evento
+    let mut wal = OpenOptions::new()
+        .append(true)
+        .open(dir.path().join("wal.log"))
+        .unwrap();
+    wal.write_all(br#"{"sequence":5,"event":{"Transac"#)
+        .unwrap();
+    drop(wal);
+
+    let mut reopened = open_disk(dir.path(), 1000);
+    assert_eq!(summary(&reopened), expected);
+
+    // El log sigue siendo válido para los nuevos eventos
+    create_account(&mut reopened, "carol");
+    drop(reopened);
+    assert_eq!(open_disk(dir.path(), 1000).accounts().len(), 3);
+}
+
+#[test]
+fn events_already_in_the_snapshot_are_not_applied_twice() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut app = open_disk(dir.path(), 1000);
+    populate(&mut app, 2);
+    drop(app);
+    let wal = fs::read(dir.path().join("wal.log")).unwrap();
+
+    let mut app = open_disk(dir.path(), 1);
+    create_account(&mut app, "carol");
+    let expected = summary(&app);
+    drop(app);
+
+    // Caída después de guardar la instantánea y antes de vaciar el log
+    fs::write(dir.path().join("wal.log"), wal).unwrap();
+
+    assert_eq!(summary(&open_disk(dir.path(), 1000)), expected);
+}
+
+#[test]
+fn corrupted_records_are_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut app = open_disk(dir.path(), 1000);
+    populate(&mut app, 1);
+    drop(app);
+
+    let wal = fs::read_to_string(dir.path().join("wal.log")).unwrap();
+    fs::write(dir.path().join("wal.log"), wal.replacen("{", "#", 1)).unwrap();
+
+    let storage = DiskStorage::open(dir.path()).unwrap();
+    assert!(App::open(common::ADDR.to_string(), storage).is_err());
+}
+
+#[test]
+fn batches_that_do_not_apply_are_rejected_whole() {
+    let storage = MemoryStorage::new();
+    let mut app = common::open(storage.clone());
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    drop(app);
+
+    // La primera transferencia es válida y la segunda deja a alice sin saldo
+    let leg = |id: &str| Transaction {
+        id: id.to_string(),
+        from_id: alice.clone(),
+        to_id: bob.clone(),
+        amount: Amount::from_units(800),
+        timestamp: 0.0,
+        node: common::ADDR.to_string(),
+        idempotency_key: None,
+        batch_id: Some("batch".to_string()),
+        from_balance: None,
+        to_balance: None,
+    };
+    let mut log = storage.clone();
+    log.append(&Event::BatchCreated(vec![leg("t1"), leg("t2")]))
+        .unwrap();
+
+    let error = App::open(common::ADDR.to_string(), storage).unwrap_err();
+    assert!(error.to_string().contains("overdrawn"));
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{create_account, login};
+use lib::App;
+use proptest::prelude::*;
+
+#[derive(Debug, Clone)]
+enum Operation {
+    CreateAccount,
+    Transfer(usize, usize, String),
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        Just(Operation::CreateAccount),
+        (0..6usize, 0..6usize, "-?[0-9]{1,2}(\\.[0-9]{1,3})?")
+            .prop_map(|(from, to, amount)| Operation::Transfer(from, to, amount)),
+    ]
+}
+
+fn total_supply(app: &App) -> u64 {
+    app.accounts()
+        .iter()
+        .map(|account| account.balance.units())
+        .sum()
+}
+
+proptest! {
+    #[test]
+    fn transfers_conserve_total_supply(operations in prop::collection::vec(operation(), 1..80)) {
+        let mut app = common::app();
+        let mut issued = 0;
+        let mut tokens = Vec::new();
+
+        for operation in operations {
+            match operation {
+                Operation::CreateAccount => {
+                    let username = format!("user{}", app.accounts().len());
+                    create_account(&mut app, &username);
+                    tokens.push(login(&mut app, &username));
+                    issued += app.accounts().last().unwrap().balance.units();
+                }
+                Operation::Transfer(from, to, amount) => {
+                    let ids: Vec<String> = app.accounts().iter().map(|account| account.id.clone()).collect();
+                    let from_id = ids.get(from).cloned().unwrap_or_else(App::create_uuid);
+                    let to_id = ids.get(to).cloned().unwrap_or_else(App::create_uuid);
+                    let token = tokens.get(from).cloned().unwrap_or_default();
+                    let _ = app.create_transaction(&token, from_id, to_id, amount);
+                }
+            }
+            prop_assert_eq!(total_supply(&app), issued);
+            let supply = app.check_ledger().unwrap();
+            prop_assert_eq!(supply.issued, issued as u128);
+        }
+    }
+
+    #[test]
+    fn negative_and_zero_amounts_are_rejected(amount in "-[0-9]{1,3}|0|0\\.0{1,2}") {
+        let mut app = common::app();
+        let from_id = create_account(&mut app, "from");
+        let to_id = create_account(&mut app, "to");
+        let token = login(&mut app, "from");
+
+        prop_assert!(app.create_transaction(&token, from_id, to_id, amount).is_err());
+        prop_assert!(app.transactions().is_empty());
+    }
+}
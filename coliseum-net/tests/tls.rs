@@ -0,0 +1,122 @@
+#![cfg(feature = "tls")]
+
+mod common;
+
+use common::{create_account, ADDR};
+use lib::client::Client;
+use lib::server::{Handler, Server};
+use lib::status::Status;
+use lib::tls::{client_config, server_config};
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+use std::fs;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// CA autofirmada y certificado de `127.0.0.1` y `localhost` firmado por ella
+struct Certificates {
+    dir: tempfile::TempDir,
+}
+
+impl Certificates {
+    fn new() -> Certificates {
+        let dir = tempfile::tempdir().unwrap();
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let key = KeyPair::generate().unwrap();
+        let params =
+            CertificateParams::new(vec!["127.0.0.1".to_string(), "localhost".to_string()]).unwrap();
+        let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+
+        fs::write(dir.path().join("ca.pem"), ca.pem()).unwrap();
+        fs::write(dir.path().join("server.pem"), cert.pem()).unwrap();
+        fs::write(dir.path().join("server.key"), key.serialize_pem()).unwrap();
+        Certificates { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+}
+
+/// Arranca un servidor TLS y devuelve su dirección
+fn start_server(certificates: &Certificates) -> (Server, String, thread::JoinHandle<()>) {
+    let config = server_config(
+        &certificates.path("server.pem"),
+        &certificates.path("server.key"),
+    )
+    .unwrap();
+
+    let mut app = common::app();
+    create_account(&mut app, "alice");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = Server::new(Handler::new(ADDR.to_string(), app), 2).with_tls(config);
+    let running = server.clone();
+    let thread = thread::spawn(move || running.run(listener));
+    (server, addr, thread)
+}
+
+#[test]
+fn clients_talk_to_servers_over_tls() {
+    let certificates = Certificates::new();
+    let (server, addr, thread) = start_server(&certificates);
+    let client = Client::new("localhost", &addr)
+        .with_tls(client_config(&certificates.path("ca.pem")).unwrap());
+
+    let response = client.login("alice", common::PASSWORD).unwrap();
+
+    assert_eq!(response.status, Status::Success);
+    server.stop();
+    thread.join().unwrap();
+}
+
+#[test]
+fn clients_reject_servers_signed_by_other_cas() {
+    let certificates = Certificates::new();
+    let other = Certificates::new();
+    let (server, addr, thread) = start_server(&certificates);
+    let client =
+        Client::new("localhost", &addr).with_tls(client_config(&other.path("ca.pem")).unwrap());
+
+    assert!(client.login("alice", common::PASSWORD).is_err());
+
+    server.stop();
+    thread.join().unwrap();
+}
+
+#[test]
+fn plaintext_requests_get_no_response() {
+    let certificates = Certificates::new();
+    let (server, addr, thread) = start_server(&certificates);
+
+    assert!(Client::new("localhost", &addr)
+        .login("alice", common::PASSWORD)
+        .is_err());
+
+    // El servidor sigue atendiendo peticiones cifradas
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream.write_all(b"not a TLS handshake").unwrap();
+    drop(stream);
+    let client = Client::new("localhost", &addr)
+        .with_tls(client_config(&certificates.path("ca.pem")).unwrap());
+    assert!(client.login("alice", common::PASSWORD).is_ok());
+
+    server.stop();
+    thread.join().unwrap();
+}
+
+#[test]
+fn missing_certificates_are_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing: &Path = &dir.path().join("missing.pem");
+    fs::write(dir.path().join("empty.pem"), "").unwrap();
+
+    assert!(server_config(missing, missing).is_err());
+    assert!(client_config(&dir.path().join("empty.pem")).is_err());
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::{create_account, login};
+use lib::status::Status;
+use lib::storage::{MemoryStorage, Storage};
+use lib::{App, Direction, Order, TransactionPage, TransactionQuery};
+
+/// App con tres transacciones entre `alice` y `bob` en los instantes 10, 20 y 30
+fn app_with_transactions() -> (App, String, String) {
+    let mut app = common::app();
+    let alice = create_account(&mut app, "alice");
+    let bob = create_account(&mut app, "bob");
+    let alice_token = login(&mut app, "alice");
+    let bob_token = login(&mut app, "bob");
+
+    app.create_transaction(&alice_token, alice.clone(), bob.clone(), "1".to_string())
+        .unwrap();
+    app.create_transaction(&bob_token, bob.clone(), alice.clone(), "2".to_string())
+        .unwrap();
+    app.create_transaction(&alice_token, alice.clone(), bob.clone(), "3".to_string())
+        .unwrap();
+
+    let mut state = app.state();
+    for (transaction, timestamp) in state.transactions.iter_mut().zip([10.0, 20.0, 30.0]) {
+        transaction.timestamp = timestamp;
+    }
+    let mut storage = MemoryStorage::new();
+    storage.snapshot(&state).unwrap();
+
+    (common::open(storage), alice, bob)
+}
+
+fn timestamps(page: &TransactionPage) -> Vec<f64> {
+    page.transactions
+        .iter()
+        .map(|transaction| transaction.timestamp)
+        .collect()
+}
+
+#[test]
+fn account_transactions_include_both_directions() {
+    let (app, alice, _) = app_with_transactions();
+
+    assert_eq!(
+        app.get_transaction_by_account(&alice, Direction::Sent)
+            .len(),
+        2
+    );
+    assert_eq!(
+        app.get_transaction_by_account(&alice, Direction::Received)
+            .len(),
+        1
+    );
+    assert_eq!(
+        app.get_transaction_by_account(&alice, Direction::Both)
+            .len(),
+        3
+    );
+}
+
+#[test]
+fn queries_filter_sort_and_paginate() {
+    let (app, alice, _) = app_with_transactions();
+
+    let query = TransactionQuery {
+        since: Some(15.0),
+        until: Some(30.0),
+        ..TransactionQuery::default()
+    };
+    assert_eq!(
+        timestamps(&app.query_transactions(None, &query)),
+        [20.0, 30.0]
+    );
+
+    let query = TransactionQuery {
+        order: Order::Descending,
+        offset: 1,
+        limit: 1,
+        ..TransactionQuery::default()
+    };
+    let page = app.query_transactions(None, &query);
+    assert_eq!(page.total, 3);
+    assert_eq!(timestamps(&page), [20.0]);
+
+    let query = TransactionQuery {
+        direction: Direction::Sent,
+        ..TransactionQuery::default()
+    };
+    assert_eq!(
+        timestamps(&app.query_transactions(Some(&alice), &query)),
+        [10.0, 30.0]
+    );
+}
+
+#[test]
+fn client_queries_a_running_server() {
+    let (app, alice, bob) = app_with_transactions();
+    let transaction_id = app.transactions()[1].id.clone();
+
+    let (client, server, thread) = common::serve(app);
+
+    let response = client
+        .list_transactions(TransactionQuery::default())
+        .unwrap();
+    let page: TransactionPage = serde_json::from_str(&response.data).unwrap();
+    assert_eq!(page.total, 3);
+
+    let response = client.get_transaction(&transaction_id).unwrap();
+    assert_eq!(response.status, Status::Success);
+    assert!(response.data.contains(&transaction_id));
+
+    let query = TransactionQuery {
+        direction: Direction::Received,
+        ..TransactionQuery::default()
+    };
+    let response = client.get_account_transactions(&bob, query).unwrap();
+    let page: TransactionPage = serde_json::from_str(&response.data).unwrap();
+    assert_eq!(page.total, 2);
+    assert!(page
+        .transactions
+        .iter()
+        .all(|transaction| transaction.from_id == alice));
+
+    assert_eq!(
+        client.get_transaction("missing").unwrap().status,
+        Status::NotFound
+    );
+    assert_eq!(
+        client
+            .get_account_transactions("missing", TransactionQuery::default())
+            .unwrap()
+            .status,
+        Status::NotFound
+    );
+
+    common::stop(server, thread);
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use coliseum_money::{Amount, AmountError};
+use common::{create_account, login};
+use lib::server::Handler;
+use lib::status::{ErrorCode, Status};
+use lib::storage::{MemoryStorage, Storage};
+use lib::{App, CreateTransactionData, Request, RequestBody, TransferError};
+
+/// App con las cuentas `from` y `to` y un token de sesión de `from`
+fn app_with_accounts() -> (App, String, String, String) {
+    let mut app = common::app();
+    let from_id = create_account(&mut app, "from");
+    let to_id = create_account(&mut app, "to");
+    let token = login(&mut app, "from");
+    (app, from_id, to_id, token)
+}
+
+fn balances(app: &App) -> Vec<Amount> {
+    app.accounts()
+        .iter()
+        .map(|account| account.balance)
+        .collect()
+}
+
+#[test]
+fn rejected_transfers_leave_balances_untouched() {
+    let (mut app, from_id, to_id, token) = app_with_accounts();
+    let before = balances(&app);
+
+    let cases = [
+        (
+            from_id.clone(),
+            to_id.clone(),
+            "abc",
+            TransferError::InvalidAmount(AmountError::InvalidFormat),
+        ),
+        (
+            from_id.clone(),
+            to_id.clone(),
+            "-1",
+            TransferError::InvalidAmount(AmountError::Negative),
+        ),
+        (
+            from_id.clone(),
+            from_id.clone(),
+            "1",
+            TransferError::SelfTransfer,
+        ),
+        (
+            from_id.clone(),
+            "missing".to_string(),
+            "1",
+            TransferError::AccountNotFound("missing".to_string()),
+        ),
+        (
+            from_id.clone(),
+            to_id.clone(),
+            "10.01",
+            TransferError::InsufficientFunds {
+                available: "10.00".to_string(),
+            },
+        ),
+    ];
+    for (from, to, amount, expected) in cases {
+        assert_eq!(
+            app.create_transaction(&token, from, to, amount.to_string())
+                .unwrap_err(),
+            expected
+        );
+    }
+
+    assert_eq!(balances(&app), before);
+    assert!(app.transactions().is_empty());
+}
+
+#[test]
+fn overflowing_receivers_are_rejected() {
+    let (app, from_id, to_id, _) = app_with_accounts();
+    let mut state = app.state();
+    state.accounts[1].balance = Amount::from_units(u64::MAX);
+    // Sin diario el saldo inicial se reconstruye a partir de los saldos
+    state.journal.clear();
+    let mut storage = MemoryStorage::new();
+    storage.snapshot(&state).unwrap();
+    let mut app = common::open(storage);
+    let token = login(&mut app, "from");
+    let before = balances(&app);
+
+    let result = app.create_transaction(&token, from_id, to_id, "1".to_string());
+
+    assert_eq!(
+        result.unwrap_err(),
+        TransferError::Overflow(AmountError::Overflow)
+    );
+    assert_eq!(balances(&app), before);
+}
+
+#[test]
+fn transfer_errors_map_to_error_codes() {
+    let (app, from_id, to_id, token) = app_with_accounts();
+    let handler = Handler::new("127.0.0.1:5000".to_string(), app);
+    let status = |from_id: &str, to_id: &str, amount: &str| {
+        let request = Request::new(
+            "localhost".to_string(),
+            "127.0.0.1:5000".to_string(),
+            RequestBody::CreateTransaction(CreateTransactionData {
+                token: token.clone(),
+                from_id: from_id.to_string(),
+                to_id: to_id.to_string(),
+                amount: amount.to_string(),
+                idempotency_key: None,
+            }),
+        );
+        let frame = serde_json::to_vec(&request).unwrap();
+        let response = handler.handle_request(&frame, "localhost");
+        (response.status, response.error.map(|error| error.code))
+    };
+
+    assert_eq!(
+        status(&from_id, &to_id, "0"),
+        (Status::BadRequest, Some(ErrorCode::InvalidAmount))
+    );
+    assert_eq!(
+        status(&from_id, &from_id, "1"),
+        (Status::BadRequest, Some(ErrorCode::SelfTransfer))
+    );
+    assert_eq!(
+        status(&from_id, "missing", "1"),
+        (Status::NotFound, Some(ErrorCode::AccountNotFound))
+    );
+    assert_eq!(
+        status(&from_id, &to_id, "11"),
+        (
+            Status::InsufficientFunds,
+            Some(ErrorCode::InsufficientFunds)
+        )
+    );
+    assert_eq!(status(&from_id, &to_id, "1"), (Status::Success, None));
+}